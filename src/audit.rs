@@ -0,0 +1,177 @@
+//! Human verification of a random sample of classified files.
+
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{output, Classification};
+
+/// How many files to draw from a run for auditing.
+#[derive(Clone, Copy, Debug)]
+pub enum SampleSize {
+    Percent(f64),
+    Count(usize)
+}
+
+impl std::str::FromStr for SampleSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent.trim().parse()
+                .map_err(|_| format!("Invalid percentage '{}'", s))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("Percentage out of range '{}'", s));
+            }
+            return Ok(SampleSize::Percent(percent));
+        }
+        s.parse().map(SampleSize::Count)
+            .map_err(|_| format!("Expected a percentage or a count, got '{}'", s))
+    }
+}
+
+impl SampleSize {
+    fn of(&self, total: usize) -> usize {
+        let n = match *self {
+            SampleSize::Percent(percent) =>
+                (total as f64 * percent / 100.0).ceil() as usize,
+            SampleSize::Count(count) => count
+        };
+        n.min(total)
+    }
+}
+
+/// One verdict given by a human reviewer.
+#[derive(Serialize, Deserialize)]
+struct AuditRecord {
+    timestamp: String,
+    #[serde(with = "crate::paths::exact")]
+    source: PathBuf,
+    #[serde(serialize_with = "crate::paths::lossy_all")]
+    destinations: Vec<PathBuf>,
+    correct: bool
+}
+
+fn catalog_path() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().join("catalog.jsonl"))
+}
+
+/// Draw a random sample from `results` and ask the user to verify each
+/// classification. Verdicts are appended to the audit catalog. When stdin
+/// is not a terminal the sample is only printed as a report.
+///
+/// Questions are asked on stderr, and with `format` JSON the reports are
+/// printed there too, as stdout is reserved for the classified files.
+pub fn review(results: &[Classification], sample: SampleSize, format: output::Format)
+    -> anyhow::Result<()> {
+    let report = |line: String| match format {
+        output::Format::Text => println!("{}", line),
+        output::Format::Json => eprintln!("{}", line)
+    };
+    let classified: Vec<&Classification> = results.iter()
+        .filter(|result| !result.matches.is_empty())
+        .collect();
+    let count = sample.of(classified.len());
+    let picked: Vec<&Classification> = classified
+        .choose_multiple(&mut rand::thread_rng(), count)
+        .copied()
+        .collect();
+    if picked.is_empty() {
+        report("Audit: no classified files to review.".to_string());
+        return Ok(());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        report(format!("Audit sample ({} of {} files):", count, classified.len()));
+        for result in picked.iter() {
+            report(describe(result));
+        }
+        return Ok(());
+    }
+
+    let timestamp = chrono::Local::now().to_rfc3339();
+    let mut records = Vec::new();
+    for (i, result) in picked.iter().enumerate() {
+        eprintln!("[{}/{}]", i + 1, count);
+        eprintln!("{}", describe(result));
+        let correct = match prompt("Correct? [y]es/[n]o/[s]kip: ")?.as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => continue
+        };
+        records.push(AuditRecord {
+            timestamp: timestamp.clone(),
+            source: result.source.clone(),
+            destinations: result.matches.iter()
+                .map(|m| m.path.clone()).collect(),
+            correct
+        });
+    }
+
+    let run_correct = records.iter().filter(|r| r.correct).count();
+    report(format!("Audit: {}/{} correct in this run.", run_correct, records.len()));
+
+    let path = catalog_path()?;
+    append(&path, &records)?;
+    let history = load(&path)?;
+    let correct = history.iter().filter(|r| r.correct).count();
+    if !history.is_empty() {
+        report(format!("Audit: {}/{} correct overall ({:.1}%).", correct,
+            history.len(), 100.0 * correct as f64 / history.len() as f64));
+    }
+    Ok(())
+}
+
+/// `result` as shown to the reviewer, over several lines.
+fn describe(result: &Classification) -> String {
+    let mut lines = vec![format!(" src: {}", result.source.display())];
+    for m in result.matches.iter() {
+        lines.push(format!("dest: {:?} using keywords: {:?}", m.path, m.keywords));
+    }
+    lines.join("\n")
+}
+
+fn prompt(question: &str) -> anyhow::Result<String> {
+    eprint!("{}", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+fn append(path: &PathBuf, records: &[AuditRecord]) -> anyhow::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open audit catalog {:?}", path))?;
+    for record in records {
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+    }
+    Ok(())
+}
+
+fn load(path: &PathBuf) -> anyhow::Result<Vec<AuditRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read audit catalog {:?}", path))?;
+    content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line)
+            .with_context(|| format!("Corrupt audit catalog {:?}", path)))
+        .collect()
+}
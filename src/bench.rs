@@ -0,0 +1,242 @@
+//! `classy bench`: time each stage of the classification of a directory,
+//! without filing anything, to find what slows it down.
+//!
+//! Files are read once before being extracted, so that extraction is
+//! timed without the cost of reading them from disk. Extraction bypasses
+//! the cache.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::output::Format;
+use crate::{choose, date, extract, Config, Document, MatchPolicy};
+
+#[derive(Serialize)]
+struct Stage {
+    name: &'static str,
+    ms: f64,
+    /// Megabytes processed per second, for the stages reading files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mb_per_s: Option<f64>
+}
+
+#[derive(Serialize)]
+struct Backend {
+    name: String,
+    files: usize,
+    failed: usize,
+    unusable: usize,
+    ms: f64,
+    mb_per_s: f64
+}
+
+#[derive(Serialize)]
+struct File {
+    #[serde(serialize_with = "crate::paths::lossy")]
+    path: PathBuf,
+    ms: f64,
+    extract_ms: f64,
+    match_ms: f64,
+    date_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>
+}
+
+#[derive(Serialize)]
+struct Rule {
+    #[serde(serialize_with = "crate::paths::lossy")]
+    dir: PathBuf,
+    ms: f64,
+    compile_ms: f64,
+    match_ms: f64
+}
+
+#[derive(Serialize)]
+struct Report {
+    files: usize,
+    bytes: u64,
+    stages: Vec<Stage>,
+    backends: Vec<Backend>,
+    /// Slowest first.
+    slowest_files: Vec<File>,
+    slowest_rules: Vec<Rule>
+}
+
+fn ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 100_000.0).round() / 100.0
+}
+
+fn throughput(bytes: u64, duration: Duration) -> f64 {
+    let seconds = duration.as_secs_f64();
+    if seconds == 0.0 {
+        return 0.0;
+    }
+    (bytes as f64 / 1_048_576.0 / seconds * 100.0).round() / 100.0
+}
+
+/// Time the classification of the files of `dir` with `config`, and print
+/// the stages, the backends and the `top` slowest files and rules.
+pub fn bench(dir: &Path, config: &Config, policy: MatchPolicy, format: Format, top: usize)
+    -> anyhow::Result<()> {
+    if !dir.is_dir() {
+        anyhow::bail!("{:?} is not a directory", dir);
+    }
+    let start = Instant::now();
+    let paths: Vec<PathBuf> = crate::walk_files(dir, &config.filter)
+        .map(walkdir::DirEntry::into_path)
+        .collect();
+    let walked = start.elapsed();
+
+    let start = Instant::now();
+    let mut bytes = 0;
+    for path in paths.iter() {
+        bytes += std::fs::read(path).map_or(0, |content| content.len() as u64);
+    }
+    let read = start.elapsed();
+
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut compiled = Duration::ZERO;
+    for rule in config.paths.iter() {
+        let start = Instant::now();
+        rule.clone().compile(config.fold_diacritics)?;
+        let elapsed = start.elapsed();
+        compiled += elapsed;
+        rules.push(Rule {
+            dir: rule.path.clone(),
+            ms: 0.0,
+            compile_ms: ms(elapsed),
+            match_ms: 0.0
+        });
+    }
+
+    let timings = extract::Timings::default();
+    let mut extractor = extract::Extractor::new(None)
+        .with_chains(&config.extraction)
+        .with_timings(timings.clone());
+    let mut files = Vec::new();
+    let mut extracted_in = Duration::ZERO;
+    let mut matched_in = Duration::ZERO;
+    let mut dated_in = Duration::ZERO;
+    let mut rule_times = vec![Duration::ZERO; config.paths.len()];
+    for path in paths.iter() {
+        let start = Instant::now();
+        let extracted = extractor.extract(path);
+        let extract_time = start.elapsed();
+        extracted_in += extract_time;
+        let mut match_time = Duration::ZERO;
+        let mut date_time = Duration::ZERO;
+        match &extracted {
+            Ok(extracted) => {
+                let start = Instant::now();
+                choose(config, &extracted.text, Some(path), policy);
+                match_time = start.elapsed();
+                let start = Instant::now();
+                date::extract(&extracted.text, extracted.metadata.as_deref(),
+                    &config.date_order);
+                date_time = start.elapsed();
+                let text = config.prepare(&extracted.text);
+                let doc = Document::new(config, &text, Some(path));
+                for (rule, time) in config.paths.iter().zip(rule_times.iter_mut()) {
+                    let start = Instant::now();
+                    rule.matches(&doc);
+                    *time += start.elapsed();
+                }
+            }
+            Err(error) => log::debug!("{:?}: {:#}", path, error)
+        }
+        matched_in += match_time;
+        dated_in += date_time;
+        files.push(File {
+            path: path.clone(),
+            ms: ms(extract_time + match_time + date_time),
+            extract_ms: ms(extract_time),
+            match_ms: ms(match_time),
+            date_ms: ms(date_time),
+            error: extracted.err().map(|error| format!("{:#}", error))
+        });
+    }
+    for (rule, time) in rules.iter_mut().zip(rule_times) {
+        rule.match_ms = ms(time);
+        rule.ms = ms(time) + rule.compile_ms;
+    }
+    files.sort_by(|a, b| b.ms.total_cmp(&a.ms));
+    files.truncate(top);
+    rules.sort_by(|a, b| b.ms.total_cmp(&a.ms));
+    rules.truncate(top);
+
+    let backends = timings.lock().unwrap().iter()
+        .map(|(name, timing)| Backend {
+            name: name.clone(),
+            files: timing.files,
+            failed: timing.failed,
+            unusable: timing.unusable,
+            ms: ms(timing.elapsed),
+            mb_per_s: throughput(timing.bytes, timing.elapsed)
+        })
+        .collect();
+    let stage = |name, duration, bytes: Option<u64>| Stage {
+        name,
+        ms: ms(duration),
+        mb_per_s: bytes.map(|bytes| throughput(bytes, duration))
+    };
+    let report = Report {
+        files: paths.len(),
+        bytes,
+        stages: vec![
+            stage("walk", walked, None),
+            stage("read", read, Some(bytes)),
+            stage("compile", compiled, None),
+            stage("extract", extracted_in, Some(bytes)),
+            stage("match", matched_in, None),
+            stage("date", dated_in, None)
+        ],
+        backends,
+        slowest_files: files,
+        slowest_rules: rules
+    };
+    match format {
+        Format::Text => print_text(&report),
+        Format::Json => println!("{}", serde_json::to_string(&report)?)
+    }
+    Ok(())
+}
+
+fn print_text(report: &Report) {
+    println!("{} files, {:.1} MB", report.files, report.bytes as f64 / 1_048_576.0);
+    println!();
+    println!("{:<12} {:>12} {:>10}", "stage", "ms", "MB/s");
+    for stage in report.stages.iter() {
+        let rate = stage.mb_per_s.map(|rate| format!("{:.2}", rate)).unwrap_or_default();
+        println!("{:<12} {:>12.2} {:>10}", stage.name, stage.ms, rate);
+    }
+    if !report.backends.is_empty() {
+        println!();
+        println!("{:<12} {:>6} {:>6} {:>8} {:>12} {:>10}", "backend", "files", "failed",
+            "unusable", "ms", "MB/s");
+        for backend in report.backends.iter() {
+            println!("{:<12} {:>6} {:>6} {:>8} {:>12.2} {:>10.2}", backend.name, backend.files,
+                backend.failed, backend.unusable, backend.ms, backend.mb_per_s);
+        }
+    }
+    if !report.slowest_files.is_empty() {
+        println!();
+        println!("Slowest files:");
+        for file in report.slowest_files.iter() {
+            let error = file.error.as_ref().map(|error| format!(", {}", error))
+                .unwrap_or_default();
+            println!("{:>12.2} ms  {} (extract {:.2} ms, match {:.2} ms, date {:.2} ms{})",
+                file.ms, file.path.display(), file.extract_ms, file.match_ms, file.date_ms,
+                error);
+        }
+    }
+    if !report.slowest_rules.is_empty() {
+        println!();
+        println!("Slowest rules:");
+        for rule in report.slowest_rules.iter() {
+            println!("{:>12.2} ms  {} (compile {:.2} ms, match {:.2} ms)", rule.ms,
+                rule.dir.display(), rule.compile_ms, rule.match_ms);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Cache of extracted text, so that unchanged files are not extracted
+//! again on the next run.
+//!
+//! Entries are keyed by the path, size and modification time of the file
+//! and stored as one JSON file each in the cache directory.
+
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+use crate::extract::Extracted;
+
+#[derive(Clone)]
+pub struct Cache {
+    dir: PathBuf
+}
+
+fn dir() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application cache directory")?;
+    Ok(dirs.cache_dir().join("text"))
+}
+
+impl Cache {
+    pub fn open() -> anyhow::Result<Cache> {
+        Ok(Cache { dir: dir()? })
+    }
+
+    /// Entry for the current version of `path`, unless the file cannot be
+    /// read.
+    fn entry(&self, path: &Path) -> Option<PathBuf> {
+        let metadata = path.metadata().ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        let path = path.canonicalize().ok()?;
+        let mut hasher = Sha256::new();
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(format!("\0{}\0{}", metadata.len(), modified.as_nanos()));
+        Some(self.dir.join(format!("{:x}.json", hasher.finalize())))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Extracted> {
+        let content = std::fs::read(self.entry(path)?).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    pub fn put(&self, path: &Path, extracted: &Extracted) {
+        let entry = match self.entry(path) {
+            Some(entry) => entry,
+            None => return
+        };
+        let written = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&entry, serde_json::to_vec(extracted)?));
+        if let Err(error) = written {
+            log::debug!("Failed to cache the text of {:?}: {}", path, error);
+        }
+    }
+}
+
+/// Remove every cached text.
+pub fn clear() -> anyhow::Result<()> {
+    let dir = dir()?;
+    let count = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries.count(),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(error) => return Err(error).with_context(|| format!("Failed to read {:?}", dir))
+    };
+    if count > 0 {
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("Failed to remove {:?}", dir))?;
+    }
+    log::info!("Removed {} cached texts", count);
+    Ok(())
+}
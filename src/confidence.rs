@@ -0,0 +1,41 @@
+//! Confidence of a classification, from 0 to 1.
+//!
+//! A rule scores higher the more of its conditions the document meets,
+//! the more conditions it has (a single keyword is weak evidence), and the
+//! further behind the best competing rule is. Rules above or below the
+//! chosen one in the tree do not compete, as subdirectories inherit the
+//! keywords of their parent.
+
+use crate::{ClassifierPath, Document};
+
+/// Conditions of `rule`: its keywords, proximity conditions and document
+/// type.
+fn conditions(rule: &ClassifierPath) -> usize {
+    rule.patterns.len() + rule.near.len() + usize::from(rule.doctype.is_some())
+}
+
+fn related(rule: &ClassifierPath, other: &ClassifierPath) -> bool {
+    rule.path.starts_with(&other.path) || other.path.starts_with(&rule.path)
+}
+
+/// Confidence of filing `doc` with `chosen`, one of `rules`.
+pub fn of(chosen: &ClassifierPath, rules: &[ClassifierPath], doc: &Document) -> f64 {
+    let matched = chosen.score(doc);
+    if matched == 0 {
+        return 0.0;
+    }
+    let runner_up = rules.iter()
+        .filter(|rule| !related(chosen, rule))
+        .map(|rule| rule.score(doc))
+        .max()
+        .unwrap_or(0);
+    let coverage = matched as f64 / conditions(chosen).max(matched) as f64;
+    let evidence = (matched + 1) as f64 / (matched + 2) as f64;
+    let margin = matched as f64 / (matched + runner_up) as f64;
+    coverage * evidence * margin
+}
+
+/// `confidence` rounded for display.
+pub fn round(confidence: f64) -> f64 {
+    (confidence * 100.0).round() / 100.0
+}
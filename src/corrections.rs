@@ -0,0 +1,183 @@
+//! Corrections of misfiled documents, made with `classy correct` or by
+//! reassigning files in the review. They are recorded to suggest keyword
+//! changes and learned by the model.
+
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{extract, model, output, suggest, ClassifierPath, Document, Run};
+
+/// Words suggested from the documents corrected into a directory.
+const SUGGESTED: usize = 5;
+
+#[derive(Serialize, Deserialize)]
+pub struct Correction {
+    timestamp: String,
+    #[serde(serialize_with = "crate::paths::lossy")]
+    source: PathBuf,
+    /// Directory of the rule the document was filed with, if any.
+    #[serde(serialize_with = "crate::paths::lossy_option")]
+    from: Option<PathBuf>,
+    /// Directory of the rule it belongs to.
+    #[serde(serialize_with = "crate::paths::lossy")]
+    pub to: PathBuf,
+    /// Words of the document, as the model learns them.
+    pub tokens: Vec<String>
+}
+
+impl Correction {
+    pub fn new(source: &Path, from: Option<&Path>, to: &Path, text: &str)
+        -> anyhow::Result<Correction> {
+        Ok(Correction {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            source: std::path::absolute(source)?,
+            from: from.map(Path::to_path_buf),
+            to: to.to_path_buf(),
+            tokens: model::tokens(text)
+        })
+    }
+}
+
+/// Location of the recorded corrections.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().join("corrections.jsonl"))
+}
+
+/// Corrections recorded so far.
+pub fn load() -> anyhow::Result<Vec<Correction>> {
+    let path = default_path()?;
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error).with_context(|| format!("Failed to read {:?}", path))
+    };
+    std::io::BufReader::new(file).lines()
+        .map(|line| {
+            let line = line.with_context(|| format!("Failed to read {:?}", path))?;
+            serde_json::from_str(&line)
+                .with_context(|| format!("Corrupt correction in {:?}", path))
+        })
+        .collect()
+}
+
+pub fn record(correction: &Correction) -> anyhow::Result<()> {
+    let path = default_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    writeln!(file, "{}", serde_json::to_string(correction)?)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Keywords of `rule` found in `doc`, or missing from it.
+fn keywords<'r>(rule: &'r ClassifierPath, doc: &Document, found: bool) -> Vec<&'r str> {
+    rule.patterns.iter()
+        .filter(|pattern| pattern.find(doc, rule.fuzzy).is_some() == found)
+        .map(|pattern| pattern.keyword.as_str())
+        .collect()
+}
+
+/// Keyword changes that would have filed `doc` with `to` rather than
+/// `from`, also drawing on the `corrections` recorded so far.
+fn suggest(doc: &Document, from: Option<&ClassifierPath>, to: &ClassifierPath,
+    corrections: &[&Correction]) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    let from = from.filter(|from| from.path != to.path);
+    if let Some(from) = from {
+        let found = keywords(from, doc, true);
+        if !found.is_empty() {
+            suggestions.push(format!("{:?} matched on {}: give it a keyword the documents of \
+                {:?} lack", from.path, found.join(", "), to.path));
+        }
+    }
+    match from {
+        Some(from) if to.matches(doc) => suggestions.push(format!("{:?} matches too but {:?} \
+            was chosen: move it higher or use --match-policy best", to.path, from.path)),
+        _ => {
+            let missing = keywords(to, doc, false);
+            if !missing.is_empty() {
+                suggestions.push(format!("{:?} misses {}: remove them or lower its \
+                    min_matches", to.path, missing.join(", ")));
+            }
+        }
+    }
+    let corpus: Vec<suggest::Document> = corrections.iter()
+        .map(|correction| {
+            (correction.to.clone(), correction.tokens.iter().cloned().collect::<HashSet<_>>())
+        })
+        .collect();
+    let own = corpus.iter().filter(|(label, _)| label.starts_with(&to.path)).count();
+    if own > 1 {
+        let corpus: Vec<&suggest::Document> = corpus.iter().collect();
+        // Keywords of the rule that was wrongly chosen do not tell them apart.
+        let known: Vec<String> = to.keywords.iter()
+            .chain(from.iter().flat_map(|from| from.keywords.iter()))
+            .map(|keyword| keyword.to_lowercase())
+            .collect();
+        let words = suggest::distinctive(&corpus, &to.path, SUGGESTED, &known, 1.0);
+        if !words.is_empty() {
+            suggestions.push(format!("The {} documents corrected into {:?} all contain: {} \
+                (as keywords with case: insensitive and fold_diacritics)", own, to.path,
+                words.join(", ")));
+        }
+    }
+    suggestions
+}
+
+/// File `file` with the rule of `category` rather than the one the rules
+/// choose, record the correction and print how the keywords could be
+/// changed. The model at `model`, if there is one, learns the document.
+pub fn correct(run: &Run, file: &Path, category: &Path, model: &Path) -> anyhow::Result<()> {
+    let config = run.config_for(file)?;
+    let rule = config.paths.iter().find(|rule| rule.path == category).with_context(|| {
+        format!("No rule for {:?}, expected one of: {}", category, config.paths.iter()
+            .map(|rule| rule.path.display().to_string())
+            .collect::<Vec<_>>().join(", "))
+    })?;
+    let extracted = extract::Extractor::new(run.cache.clone())
+        .with_chains(&config.extraction)
+        .with_limits(run.limits.clone())
+        .extract(file)?;
+    let mut result = crate::classify(file, &extracted, &config, run.policy);
+    let from = result.matches.first().cloned();
+    let correction = Correction::new(file, from.as_ref().map(|from| from.path.as_path()),
+        &rule.path, &extracted.text)?;
+    let text = config.prepare(&extracted.text);
+    let doc = Document::new(&config, &text, Some(file));
+    let recorded = load()?;
+    let corrections: Vec<&Correction> = recorded.iter().chain([&correction]).collect();
+    let suggestions = suggest(&doc, from.as_ref(), rule, &corrections);
+
+    result.matches = vec![rule.clone()];
+    result.ambiguous = false;
+    result.confidence = None;
+    run.apply(result, None)?;
+    for suggestion in suggestions {
+        match run.format {
+            output::Format::Text => println!("{}", suggestion),
+            output::Format::Json => eprintln!("{}", suggestion)
+        }
+    }
+    if run.dry_run {
+        return Ok(());
+    }
+    record(&correction)?;
+    if model.exists() {
+        let mut trained = model::load(model, &Vec::new(), 0.0)?;
+        trained.add(correction.to, correction.tokens);
+        trained.save(model)?;
+        log::info!("{:?} learned the correction", model);
+    }
+    Ok(())
+}
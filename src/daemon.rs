@@ -0,0 +1,75 @@
+//! PID file of the daemon, so that service managers can track it and only
+//! one daemon files documents at a time.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+/// Default location of the PID file.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().join("daemon.pid"))
+}
+
+#[cfg(unix)]
+fn is_running(pid: i32) -> bool {
+    use nix::errno::Errno;
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+    matches!(kill(Pid::from_raw(pid), None), Ok(()) | Err(Errno::EPERM))
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: i32) -> bool {
+    true
+}
+
+/// PID file held while the daemon runs, removed when dropped.
+pub struct PidFile {
+    path: PathBuf
+}
+
+impl PidFile {
+    /// Write the PID of this process to `path`, unless another daemon
+    /// holds it. The file of a daemon that is no longer running is
+    /// replaced.
+    pub fn acquire(path: &Path) -> anyhow::Result<PidFile> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        loop {
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", std::process::id())
+                        .with_context(|| format!("Failed to write PID file {:?}", path))?;
+                    return Ok(PidFile { path: path.to_path_buf() });
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let content = std::fs::read_to_string(path).unwrap_or_default();
+                    match content.trim().parse::<i32>() {
+                        Ok(pid) if is_running(pid) => bail!(
+                            "Another daemon is running with PID {} according to {:?}",
+                            pid, path),
+                        _ => {
+                            log::warn!("Replacing stale PID file {:?}", path);
+                            std::fs::remove_file(path).with_context(|| format!(
+                                "Failed to remove PID file {:?}", path))?;
+                        }
+                    }
+                }
+                Err(error) => return Err(error)
+                    .with_context(|| format!("Failed to create PID file {:?}", path))
+            }
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove PID file {:?}: {}", self.path, error);
+        }
+    }
+}
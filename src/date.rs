@@ -0,0 +1,173 @@
+//! Document dates, used to file documents chronologically.
+//!
+//! Dates are read in ISO form (2023-12-31), with numbers (31.12.2023,
+//! 12/31/2023) and with month names in English, German or French, spelled
+//! out or abbreviated (Dec 31, 2023, 31. Dezember 2023, 31 déc. 2023).
+//! Dotted dates are day first, as in German. Whether the day or the month
+//! comes first in other numeric dates such as 03/04/2023 is tried in the
+//! order of `date_order`, day first by default.
+
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::bail;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+/// Names of each month in English, German and French, abbreviations
+/// being their prefixes.
+const MONTHS: [&[&str]; 12] = [
+    &["january", "januar", "jänner", "janvier"],
+    &["february", "februar", "février"],
+    &["march", "märz", "mrz", "mars"],
+    &["april", "avril"],
+    &["may", "mai"],
+    &["june", "juni", "juin"],
+    &["july", "juli", "juillet"],
+    &["august", "août"],
+    &["september", "septembre"],
+    &["october", "oktober", "octobre"],
+    &["november", "novembre"],
+    &["december", "dezember", "décembre"]
+];
+
+/// Number of the month named or abbreviated `name`, if it names a
+/// single one.
+fn month_number(name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    if name.chars().count() < 3 {
+        return None;
+    }
+    let mut months = MONTHS.iter().enumerate()
+        .filter(|(_, names)| names.iter().any(|month| month.starts_with(&name)))
+        .map(|(index, _)| index as u32 + 1);
+    let month = months.next()?;
+    months.next().is_none().then_some(month)
+}
+
+/// Order of the day and the month in numeric dates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// 31/12/2023
+    Dmy,
+    /// 12/31/2023
+    Mdy
+}
+
+impl Order {
+    /// `preferred` orders followed by the others, day first by default.
+    pub fn resolve(preferred: &[Order]) -> Vec<Order> {
+        let mut orders = preferred.to_vec();
+        for order in [Order::Dmy, Order::Mdy] {
+            if !orders.contains(&order) {
+                orders.push(order);
+            }
+        }
+        orders
+    }
+}
+
+impl std::fmt::Display for Order {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Order::Dmy => "dmy",
+            Order::Mdy => "mdy"
+        })
+    }
+}
+
+/// Builds a date from the captures of a date pattern, trying numeric
+/// dates in the given orders.
+type DateBuilder = fn(&regex::Captures, &[Order]) -> Option<NaiveDate>;
+
+fn number(captures: &regex::Captures, index: usize) -> Option<i32> {
+    captures.get(index)?.as_str().parse().ok()
+}
+
+static PATTERNS: LazyLock<Vec<(regex::Regex, DateBuilder)>> = LazyLock::new(|| vec![
+    // 2023-12-31, 2023/12/31
+    (regex::Regex::new(r"\b(\d{4})[-/](\d{1,2})[-/](\d{1,2})\b").unwrap(),
+        |c, _| NaiveDate::from_ymd_opt(number(c, 1)?, number(c, 2)? as u32,
+            number(c, 3)? as u32)),
+    // 31.12.2023, 31/12/2023, 12/31/2023, 12-31-2023
+    (regex::Regex::new(r"\b(\d{1,2})([./-])(\d{1,2})[./-](\d{4})\b").unwrap(),
+        |c, orders| {
+            let (first, second, year) = (number(c, 1)? as u32, number(c, 3)? as u32,
+                number(c, 4)?);
+            let orders = if &c[2] == "." { &[Order::Dmy][..] } else { orders };
+            orders.iter().find_map(|order| match order {
+                Order::Dmy => NaiveDate::from_ymd_opt(year, second, first),
+                Order::Mdy => NaiveDate::from_ymd_opt(year, first, second)
+            })
+        }),
+    // 31 December 2023, 31. Dezember 2023
+    (regex::Regex::new(r"\b(\d{1,2})\.?\s+(\p{L}+)\.?\s+(\d{4})\b").unwrap(),
+        |c, _| NaiveDate::from_ymd_opt(number(c, 3)?, month_number(&c[2])?,
+            number(c, 1)? as u32)),
+    // December 31, 2023
+    (regex::Regex::new(r"\b(\p{L}+)\.?\s+(\d{1,2}),?\s+(\d{4})\b").unwrap(),
+        |c, _| NaiveDate::from_ymd_opt(number(c, 3)?, month_number(&c[1])?,
+            number(c, 2)? as u32))
+]);
+
+static METADATA_DATE: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(
+    r#"xmp:CreateDate(?:>|=["'])\s*(\d{4})-(\d{2})-(\d{2})"#).unwrap());
+
+/// Dates found in `text` with their byte offset, numeric dates being read
+/// in the first of `orders` that makes a valid date.
+fn text_dates(text: &str, orders: &[Order]) -> Vec<(usize, NaiveDate)> {
+    let mut dates = Vec::new();
+    for (regex, build) in PATTERNS.iter() {
+        for captures in regex.captures_iter(text) {
+            if let Some(date) = build(&captures, orders) {
+                dates.push((captures.get(0).unwrap().start(), date));
+            }
+        }
+    }
+    dates
+}
+
+/// Creation date from XMP document metadata.
+fn metadata_date(metadata: &str) -> Option<NaiveDate> {
+    let captures = METADATA_DATE.captures(metadata)?;
+    NaiveDate::from_ymd_opt(number(&captures, 1)?, number(&captures, 2)? as u32,
+        number(&captures, 3)? as u32)
+}
+
+/// Date of a document: the first date appearing in its text, otherwise
+/// its creation date from the metadata.
+pub fn extract(text: &str, metadata: Option<&str>, orders: &[Order]) -> Option<NaiveDate> {
+    text_dates(text, orders).into_iter()
+        .min_by_key(|(offset, _)| *offset)
+        .map(|(_, date)| date)
+        .or_else(|| metadata.and_then(metadata_date))
+}
+
+/// Modification date of a file.
+pub fn modified(path: &Path) -> Option<NaiveDate> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(chrono::DateTime::<chrono::Local>::from(modified).date_naive())
+}
+
+/// Whether `path` contains date placeholders.
+pub fn has_placeholders(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    ["{year}", "{month}", "{day}"].iter().any(|p| path.contains(p))
+}
+
+/// Replace `{year}`, `{month}` and `{day}` in `path` with `date`.
+pub fn expand(path: &Path, date: Option<NaiveDate>) -> anyhow::Result<PathBuf> {
+    if !has_placeholders(path) {
+        return Ok(path.to_path_buf());
+    }
+    let date = match date {
+        Some(date) => date,
+        None => bail!("No date found to expand {:?}", path)
+    };
+    let expanded = path.to_string_lossy()
+        .replace("{year}", &format!("{:04}", date.year()))
+        .replace("{month}", &format!("{:02}", date.month()))
+        .replace("{day}", &format!("{:02}", date.day()));
+    Ok(PathBuf::from(expanded))
+}
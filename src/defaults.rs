@@ -0,0 +1,137 @@
+//! Defaults of the command line options saved with `classy set-default`,
+//! used when an option is not given.
+
+use std::ffi::OsString;
+
+use anyhow::{bail, Context};
+use clap::{Arg, ArgAction, Command};
+use preferences::{AppInfo, Preferences, PreferencesError, PreferencesMap};
+
+/// Next to the configuration file of `crate::project_dirs`, the author
+/// only appearing in the path on Windows.
+const APP_INFO: AppInfo = AppInfo { name: "ddc", author: "classy" };
+const KEY: &str = "defaults";
+
+/// Options that cannot have a default.
+const EXCLUDED: &[&str] = &["help", "version", "print-config"];
+
+fn load() -> anyhow::Result<PreferencesMap<Vec<String>>> {
+    match PreferencesMap::load(&APP_INFO, KEY) {
+        Ok(defaults) => Ok(defaults),
+        Err(PreferencesError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound =>
+            Ok(PreferencesMap::new()),
+        Err(error) => Err(error).context("Failed to read the saved defaults")
+    }
+}
+
+fn save(defaults: &PreferencesMap<Vec<String>>) -> anyhow::Result<()> {
+    defaults.save(&APP_INFO, KEY).context("Failed to save the defaults")
+}
+
+/// Top-level option with the long name `key`.
+fn option<'c, 'h>(command: &'c Command<'h>, key: &str) -> Option<&'c Arg<'h>> {
+    command.get_arguments()
+        .find(|arg| arg.get_long() == Some(key) && !EXCLUDED.contains(&key))
+}
+
+/// Whether `arg` is a flag, given "true" or "false" as a default.
+fn is_flag(arg: &Arg) -> bool {
+    !arg.is_takes_value_set()
+}
+
+/// Command line arguments standing for the default `values` of `arg`.
+fn arguments(arg: &Arg, values: &[String]) -> Vec<OsString> {
+    let long = OsString::from(format!("--{}", arg.get_long().unwrap_or_default()));
+    if is_flag(arg) {
+        return if values.iter().any(|value| value == "true") { vec![long] } else { Vec::new() };
+    }
+    values.iter().flat_map(|value| [long.clone(), value.into()]).collect()
+}
+
+/// The arguments of this process, preceded by the saved defaults of the
+/// options they do not give. Saved defaults are ignored by
+/// `set-default`, so that a default breaking every command can be unset.
+pub fn args(command: Command) -> Vec<OsString> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let defaults = match load() {
+        Ok(defaults) if !defaults.is_empty() => defaults,
+        Ok(_) => return args,
+        Err(error) => {
+            eprintln!("Ignoring the saved defaults: {:#}", error);
+            return args;
+        }
+    };
+    let matches = match command.clone().ignore_errors(true).try_get_matches_from(&args) {
+        Ok(matches) => matches,
+        Err(_) => return args
+    };
+    if matches.subcommand_name() == Some("set-default") {
+        return args;
+    }
+    let mut with_defaults = args[..1].to_vec();
+    for (key, values) in defaults.iter() {
+        let arg = match option(&command, key) {
+            Some(arg) => arg,
+            None => continue
+        };
+        if matches.value_source(arg.get_id()) != Some(clap::ValueSource::CommandLine) {
+            with_defaults.extend(arguments(arg, values));
+        }
+    }
+    with_defaults.extend(args.into_iter().skip(1));
+    with_defaults
+}
+
+/// Save `values` as the default of the option `key`, or forget it with
+/// `unset`.
+pub fn set(command: Command, key: &str, values: &[String], unset: bool)
+    -> anyhow::Result<()> {
+    let mut defaults = load()?;
+    if unset {
+        if defaults.remove(key).is_none() {
+            bail!("No default saved for '{}'", key);
+        }
+        return save(&defaults);
+    }
+    let arg = option(&command, key).with_context(|| format!(
+        "Unknown option '{}', expected the long name of an option such as 'output'", key))?;
+    if values.is_empty() {
+        bail!("No value given for '{}'", key);
+    }
+    if is_flag(arg) {
+        if values.len() > 1 || !["true", "false"].contains(&values[0].as_str()) {
+            bail!("'{}' is a flag, its default should be true or false", key);
+        }
+    } else {
+        if values.len() > 1 && !arg.is_multiple_occurrences_set()
+            && !matches!(arg.get_action(), ArgAction::Append) {
+            bail!("'{}' takes a single value", key);
+        }
+        // Values are checked by parsing them alone.
+        let mut args = vec![OsString::from("classy")];
+        args.extend(arguments(arg, values));
+        let required: Vec<&str> = command.get_arguments()
+            .filter(|arg| arg.is_required_set())
+            .map(Arg::get_id)
+            .collect();
+        let command = required.into_iter()
+            .fold(command, |command, id| command.mut_arg(id, |arg| arg.required(false)));
+        if let Err(error) = command.try_get_matches_from(args) {
+            let message = error.to_string();
+            bail!("{}", message.lines().next().unwrap_or_default().trim_start_matches("error: "));
+        }
+    }
+    defaults.insert(key.to_string(), values.to_vec());
+    save(&defaults)
+}
+
+/// Print the saved defaults.
+pub fn list() -> anyhow::Result<()> {
+    let defaults = load()?;
+    let mut keys: Vec<&String> = defaults.keys().collect();
+    keys.sort();
+    for key in keys {
+        println!("{} = {}", key, defaults[key].join(" "));
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Heuristic detection of common document types.
+//!
+//! Each type is scored from characteristic vocabulary (English and
+//! German) and a few layout cues, such as rows of amounts or dated
+//! transaction lines. The best scoring type wins if it is convincing
+//! enough.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocType {
+    Invoice,
+    Receipt,
+    BankStatement,
+    Payslip,
+    MedicalLetter
+}
+
+const TYPES: &[(&str, DocType)] = &[
+    ("invoice", DocType::Invoice),
+    ("receipt", DocType::Receipt),
+    ("bank_statement", DocType::BankStatement),
+    ("payslip", DocType::Payslip),
+    ("medical_letter", DocType::MedicalLetter)
+];
+
+impl std::str::FromStr for DocType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace([' ', '-'], "_");
+        TYPES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, doctype)| *doctype)
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown document type '{}', expected one of: {}", s,
+                TYPES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl std::fmt::Display for DocType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = TYPES.iter()
+            .find(|(_, doctype)| doctype == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+fn vocabulary(doctype: DocType) -> &'static [&'static str] {
+    match doctype {
+        DocType::Invoice => &[
+            "invoice", "invoice number", "invoice no", "bill to", "due date",
+            "payment terms", "vat", "subtotal", "total due", "amount due",
+            "rechnung", "rechnungsnummer", "rechnungsdatum", "zahlungsziel",
+            "mwst", "ust-idnr", "nettobetrag", "leistungszeitraum"
+        ],
+        DocType::Receipt => &[
+            "receipt", "cash", "change", "card payment", "paid", "cashier",
+            "thank you for your purchase", "kassenbon", "quittung", "beleg",
+            "bar", "rückgeld", "kasse", "summe eur"
+        ],
+        DocType::BankStatement => &[
+            "account statement", "statement period", "opening balance",
+            "closing balance", "balance brought forward", "iban", "bic",
+            "kontoauszug", "kontostand", "alter kontostand",
+            "neuer kontostand", "saldo", "buchungstag", "wertstellung"
+        ],
+        DocType::Payslip => &[
+            "payslip", "pay slip", "gross pay", "net pay", "income tax",
+            "national insurance", "salary", "employee", "gehaltsabrechnung",
+            "lohnabrechnung", "brutto", "netto", "sozialversicherung",
+            "lohnsteuer", "personalnummer", "auszahlungsbetrag"
+        ],
+        DocType::MedicalLetter => &[
+            "patient", "diagnosis", "clinic", "hospital", "treatment",
+            "prescription", "physician", "befund", "diagnose", "arztbrief",
+            "praxis", "patientin", "behandlung", "therapie", "klinik"
+        ]
+    }
+}
+
+/// Layout statistics of a document.
+struct Layout {
+    lines: usize,
+    average_length: f64,
+    amount_lines: usize,
+    transaction_lines: usize
+}
+
+impl Layout {
+    fn of(text: &str) -> Layout {
+        let amount = regex::Regex::new(r"\d+[.,]\d{2}\s*(€|EUR|\$|USD|£)?\s*$")
+            .unwrap();
+        let transaction = regex::Regex::new(
+            r"^\s*\d{1,2}[./]\d{1,2}[./]?(\d{2,4})?\s.*\d+[.,]\d{2}").unwrap();
+        let lines: Vec<&str> = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let total: usize = lines.iter().map(|line| line.chars().count()).sum();
+        Layout {
+            lines: lines.len(),
+            average_length: total as f64 / lines.len().max(1) as f64,
+            amount_lines: lines.iter().filter(|l| amount.is_match(l)).count(),
+            transaction_lines: lines.iter().filter(|l| transaction.is_match(l)).count()
+        }
+    }
+}
+
+fn score(doctype: DocType, words: &str, layout: &Layout) -> usize {
+    let mut score = vocabulary(doctype).iter()
+        .filter(|term| {
+            regex::Regex::new(&format!(r"\b{}\b", regex::escape(term)))
+                .map(|regex| regex.is_match(words))
+                .unwrap_or(false)
+        })
+        .count();
+    let tabular = layout.amount_lines >= 3;
+    score += match doctype {
+        DocType::Invoice if tabular => 1,
+        DocType::Receipt if tabular && layout.average_length < 32.0 => 2,
+        DocType::BankStatement if layout.transaction_lines >= 3 => 3,
+        DocType::Payslip if tabular => 1,
+        DocType::MedicalLetter
+            if layout.average_length > 50.0 && layout.amount_lines == 0
+                && layout.lines >= 5 => 1,
+        _ => 0
+    };
+    score
+}
+
+/// Minimum score for a detection to be trusted.
+const THRESHOLD: usize = 3;
+
+/// Most likely type of the document with the given text, if any.
+pub fn detect(text: &str) -> Option<DocType> {
+    let words = text.to_lowercase();
+    let layout = Layout::of(text);
+    let mut scores: Vec<(DocType, usize)> = TYPES.iter()
+        .map(|(_, doctype)| (*doctype, score(*doctype, &words, &layout)))
+        .collect();
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    let (best, best_score) = scores[0];
+    if best_score < THRESHOLD || scores[1].1 == best_score {
+        return None;
+    }
+    Some(best)
+}
@@ -0,0 +1,110 @@
+//! Detection of documents already present in the output tree, looked up
+//! in the index of classified documents if there is one, then by scanning
+//! the tree.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+
+use crate::{hash, remote};
+
+/// Files of the output tree by size, built on first use. Only files of the
+/// same size as the one looked up are hashed, and each of them only once.
+pub struct Index {
+    root: PathBuf,
+    by_size: RefCell<Option<HashMap<u64, Vec<PathBuf>>>>,
+    hashes: RefCell<HashMap<PathBuf, String>>,
+    /// Index of classified documents, which also knows the files of remote
+    /// outputs.
+    documents: Option<Connection>
+}
+
+impl Index {
+    pub fn new(root: PathBuf) -> Index {
+        Index {
+            root,
+            by_size: RefCell::new(None),
+            hashes: RefCell::new(HashMap::new()),
+            documents: None
+        }
+    }
+
+    /// Also look up the documents filed according to the index at `path`.
+    pub fn with_index(mut self, path: &Path) -> anyhow::Result<Index> {
+        self.documents = Some(crate::index::connect(path)?);
+        Ok(self)
+    }
+
+    /// A document filed with the content hashed `hash`, other than
+    /// `source`, that is still there.
+    fn indexed(&self, hash: &str, source: &Path) -> anyhow::Result<Option<PathBuf>> {
+        let connection = match &self.documents {
+            Some(connection) => connection,
+            None => return Ok(None)
+        };
+        let mut statement = connection.prepare_cached(
+            "SELECT path FROM documents WHERE hash = ?1 AND action NOT IN ('none', 'tag')")?;
+        let paths = statement.query_map([hash], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let source = std::path::absolute(source)?;
+        Ok(paths.into_iter().map(PathBuf::from).find(|path| {
+            *path != source && (remote::is_remote(path) || path.is_file())
+        }))
+    }
+
+    fn scan(&self) -> HashMap<u64, Vec<PathBuf>> {
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        let files = walkdir::WalkDir::new(&self.root).into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file());
+        for file in files {
+            if let Ok(metadata) = file.metadata() {
+                by_size.entry(metadata.len()).or_default().push(file.into_path());
+            }
+        }
+        by_size
+    }
+
+    fn hash(&self, path: &Path) -> Option<String> {
+        if let Some(hash) = self.hashes.borrow().get(path) {
+            return Some(hash.clone());
+        }
+        let hash = hash::hash_file(path).ok()?;
+        self.hashes.borrow_mut().insert(path.to_path_buf(), hash.clone());
+        Some(hash)
+    }
+
+    /// A file of the output tree with the same content as `source`.
+    pub fn find(&self, source: &Path) -> anyhow::Result<Option<PathBuf>> {
+        let size = source.metadata()?.len();
+        let candidates = self.by_size.borrow_mut()
+            .get_or_insert_with(|| self.scan())
+            .get(&size).cloned().unwrap_or_default();
+        if candidates.is_empty() && self.documents.is_none() {
+            return Ok(None);
+        }
+        let hash = hash::hash_file(source)?;
+        if let Some(existing) = self.indexed(&hash, source)? {
+            return Ok(Some(existing));
+        }
+        let source = source.canonicalize()?;
+        Ok(candidates.into_iter().find(|candidate| {
+            candidate.canonicalize().is_ok_and(|path| path != source)
+                && self.hash(candidate).as_ref() == Some(&hash)
+        }))
+    }
+
+    /// Add a file placed into the output tree. Symbolic links are left
+    /// out, like when scanning the tree.
+    pub fn add(&self, path: &Path) {
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return
+        };
+        if let Some(by_size) = self.by_size.borrow_mut().as_mut() {
+            by_size.entry(metadata.len()).or_default().push(path.to_path_buf());
+        }
+    }
+}
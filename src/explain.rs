@@ -0,0 +1,59 @@
+//! Evidence for why a file matched its rules.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{Classification, Document};
+
+/// Characters of context shown on each side of a keyword.
+const CONTEXT: usize = 30;
+
+/// First occurrence of a keyword of a matching rule.
+#[derive(Serialize)]
+pub struct Hit {
+    pub rule: PathBuf,
+    pub keyword: String,
+    /// Byte offset in the extracted text.
+    pub offset: usize,
+    /// Page number, starting at 1.
+    pub page: usize,
+    pub snippet: String
+}
+
+impl std::fmt::Display for Hit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?} at byte {}, page {}: ...{}...", self.keyword, self.offset,
+            self.page, self.snippet)
+    }
+}
+
+/// Text around `start..end`, on a single line.
+fn snippet(text: &str, start: usize, end: usize) -> String {
+    let before = text[..start].char_indices().rev().nth(CONTEXT - 1)
+        .map_or(0, |(index, _)| index);
+    let after = text[end..].char_indices().nth(CONTEXT)
+        .map_or(text.len(), |(index, _)| end + index);
+    text[before..after].split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Where the keywords of the rules matched by `result` appear in `text`.
+pub fn hits(result: &Classification, text: &str) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    let doc = Document { text, doctype: None, file_type: None, words: Default::default() };
+    for rule in result.matches.iter() {
+        for (keyword, pattern) in rule.keywords.iter().zip(rule.patterns.iter()) {
+            if let Some(found) = pattern.find(&doc, rule.fuzzy) {
+                hits.push(Hit {
+                    rule: rule.path.clone(),
+                    keyword: keyword.clone(),
+                    offset: found.start,
+                    // Pages are separated by form feeds.
+                    page: text[..found.start].matches('\x0c').count() + 1,
+                    snippet: snippet(text, found.start, found.end)
+                });
+            }
+        }
+    }
+    hits
+}
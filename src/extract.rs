@@ -0,0 +1,705 @@
+//! Text extraction from documents.
+//!
+//! Every file type, by extension, has a chain of backends tried in order
+//! until one yields usable text. Chains are set under `extraction` in the
+//! configuration file:
+//!
+//! ```yaml
+//! extraction:
+//!   pdf: [poppler, pdf-extract, ocr]
+//!   txt: [plaintext]
+//! ```
+//!
+//! - `poppler`: text layer of PDFs, read with poppler
+//! - `pdf-extract`: text layer of PDFs, read in pure Rust, without page
+//!   breaks
+//! - `ocr`: text recognized by `tesseract` in the pages of PDFs rendered
+//!   by `pdftoppm`
+//! - `plaintext`: content of text files
+//!
+//! Commands printing the text of a file can be added under `extractors`,
+//! with `{file}` replaced by the path of the file. Each one is named after
+//! a file type it handles alone unless `extraction` says otherwise:
+//!
+//! ```yaml
+//! extractors:
+//!   djvu: djvutxt {file}
+//! ```
+//!
+//! PDFs are extracted with `poppler`, then `pdf-extract`, unless set
+//! otherwise.
+//!
+//! `FileLimits` bound the text and time spent on each file. `poppler` and
+//! `ocr` check them page by page, skipping the rest of a document once
+//! they are reached.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::metrics::Metrics;
+
+/// Text and metadata extracted from a document.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Extracted {
+    pub text: String,
+    pub title: Option<String>,
+    /// XMP metadata of the document.
+    pub metadata: Option<String>
+}
+
+/// Extraction backend.
+pub trait Backend {
+    fn extract(&mut self, path: &Path, budget: &Budget) -> anyhow::Result<Extracted>;
+}
+
+/// Tells from the pages of a file extracted so far whether the rest can
+/// be skipped.
+pub type Stop = Arc<dyn Fn(&Path, &str) -> bool + Send + Sync>;
+
+/// Limits of the extraction of each file.
+#[derive(Clone, Default)]
+pub struct FileLimits {
+    /// Bytes of text kept, the rest of the document being skipped.
+    pub max_text_bytes: Option<usize>,
+    /// Time after which the extraction fails.
+    pub timeout: Option<Duration>,
+    pub stop: Option<Stop>
+}
+
+/// What is left of the `FileLimits` while a file is extracted.
+pub struct Budget<'a> {
+    path: &'a Path,
+    limits: &'a FileLimits,
+    deadline: Option<Instant>,
+    /// Set when part of the text was left out.
+    partial: Cell<bool>
+}
+
+impl Budget<'_> {
+    fn timed_out(&self) -> anyhow::Error {
+        anyhow!("Timed out after {}s", self.limits.timeout.unwrap_or_default().as_secs())
+    }
+
+    /// Fail once the deadline passed.
+    fn check(&self) -> anyhow::Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(self.timed_out()),
+            _ => Ok(())
+        }
+    }
+
+    /// Whether the pages of `text` are enough, the next ones being skipped.
+    fn skip_rest(&self, text: &str) -> bool {
+        let full = self.limits.max_text_bytes.is_some_and(|max| text.len() >= max);
+        let skip = full || self.limits.stop.as_ref().is_some_and(|stop| stop(self.path, text));
+        if skip {
+            self.partial.set(true);
+        }
+        skip
+    }
+
+    /// `text` extracted in full, without the pages that would have been
+    /// skipped.
+    fn cut(&self, mut text: String) -> String {
+        if self.limits.stop.is_some() {
+            let breaks: Vec<usize> = text.match_indices('\x0c').map(|(at, _)| at).collect();
+            if let Some(end) = breaks.into_iter().find(|end| self.skip_rest(&text[..*end])) {
+                text.truncate(end);
+            }
+        }
+        self.truncate(text)
+    }
+
+    /// `text` without what exceeds the maximum number of bytes.
+    fn truncate(&self, mut text: String) -> String {
+        if let Some(mut end) = self.limits.max_text_bytes.filter(|max| text.len() > *max) {
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            self.partial.set(true);
+        }
+        text
+    }
+}
+
+/// Output of `command`, killed when the deadline of `budget` passes.
+fn run(command: &mut Command, budget: &Budget) -> anyhow::Result<std::process::Output> {
+    let deadline = match budget.deadline {
+        Some(deadline) => deadline,
+        None => return Ok(command.output()?)
+    };
+    let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let read = |pipe: Option<Box<dyn std::io::Read + Send>>| std::thread::spawn(move || {
+        let mut content = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut content);
+        }
+        content
+    });
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(budget.timed_out());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    Ok(std::process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default()
+    })
+}
+
+/// Backend of a chain, as named in the configuration file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Poppler,
+    PdfExtract,
+    Ocr,
+    Plaintext,
+    /// Command of `extractors`, with its arguments.
+    Command {
+        name: String,
+        args: Vec<String>
+    }
+}
+
+const KINDS: &[(&str, Kind)] = &[
+    ("poppler", Kind::Poppler),
+    ("pdf-extract", Kind::PdfExtract),
+    ("ocr", Kind::Ocr),
+    ("plaintext", Kind::Plaintext)
+];
+
+impl std::str::FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KINDS.iter()
+            .find(|(name, _)| *name == s.trim())
+            .map(|(_, kind)| kind.clone())
+            .ok_or_else(|| anyhow!("Unknown extractor '{}', expected one of: {}", s,
+                names().join(", ")))
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Kind::Command { name, .. } = self {
+            return write!(f, "{}", name);
+        }
+        let name = KINDS.iter()
+            .find(|(_, kind)| kind == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+impl Kind {
+    fn backend(&self) -> Box<dyn Backend> {
+        match self {
+            Kind::Poppler => Box::new(Poppler { password: String::new() }),
+            Kind::PdfExtract => Box::new(PdfExtract),
+            Kind::Ocr => Box::new(Ocr),
+            Kind::Plaintext => Box::new(Plaintext),
+            Kind::Command { args, .. } => Box::new(External { args: args.clone() })
+        }
+    }
+}
+
+/// Names of the backends.
+pub fn names() -> Vec<&'static str> {
+    KINDS.iter().map(|(name, _)| *name).collect()
+}
+
+/// Backends of each file type, by lowercase extension.
+pub type Chains = BTreeMap<String, Vec<Kind>>;
+
+/// Chains of the file types the configuration file does not set.
+pub fn default_chains() -> Chains {
+    Chains::from([("pdf".to_string(), vec![Kind::Poppler, Kind::PdfExtract])])
+}
+
+/// `configured` chains, by extension, added to the default ones and to
+/// those of the external `commands`, given by name.
+pub fn chains(configured: &BTreeMap<String, Vec<String>>,
+    commands: &BTreeMap<String, Vec<String>>) -> anyhow::Result<Chains> {
+    let mut chains = default_chains();
+    let mut custom = HashMap::new();
+    for (name, args) in commands {
+        if names().contains(&name.as_str()) {
+            bail!("Extractor '{}' is built in, give the command another name", name);
+        }
+        let kind = Kind::Command { name: name.clone(), args: args.clone() };
+        chains.insert(name.trim_start_matches('.').to_lowercase(), vec![kind.clone()]);
+        custom.insert(name.as_str(), kind);
+    }
+    for (extension, names) in configured {
+        let kinds = names.iter()
+            .map(|name| match custom.get(name.as_str()) {
+                Some(kind) => Ok(kind.clone()),
+                None => name.parse()
+            })
+            .collect::<anyhow::Result<Vec<Kind>>>()
+            .with_context(|| format!("In 'extraction' of '{}'", extension))?;
+        chains.insert(extension.trim_start_matches('.').to_lowercase(), kinds);
+    }
+    Ok(chains)
+}
+
+/// Whether `text` looks like text, rather than nothing or the garbage
+/// left by fonts without a usable encoding.
+fn is_usable(text: &str) -> bool {
+    let mut total = 0;
+    let mut readable = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        total += 1;
+        if c.is_alphanumeric() || c.is_ascii_punctuation() {
+            readable += 1;
+        }
+    }
+    total > 0 && readable * 4 >= total * 3
+}
+
+struct Poppler {
+    password: String
+}
+
+impl Backend for Poppler {
+    fn extract(&mut self, path: &Path, budget: &Budget) -> anyhow::Result<Extracted> {
+        let doc = poppler::PopplerDocument::new_from_file(path, &self.password)
+            .map_err(|error| anyhow!("Failed to read the document: {}", error))?;
+        // Pages are separated by form feeds.
+        let count = doc.get_n_pages();
+        let mut text = String::new();
+        let mut first = true;
+        for index in 0..count {
+            budget.check()?;
+            if let Some(page) = doc.get_page(index) {
+                if !first {
+                    text.push('\x0c');
+                }
+                first = false;
+                text.push_str(&crate::normalize::nfkc(page.get_text().unwrap_or_default()));
+            }
+            if index + 1 < count && budget.skip_rest(&text) {
+                break;
+            }
+        }
+        Ok(Extracted { text, title: doc.get_title(), metadata: doc.get_metadata() })
+    }
+}
+
+struct PdfExtract;
+
+impl Backend for PdfExtract {
+    fn extract(&mut self, path: &Path, _budget: &Budget) -> anyhow::Result<Extracted> {
+        // pdf-extract panics on some malformed documents.
+        let text = std::panic::catch_unwind(|| pdf_extract::extract_text(path))
+            .map_err(|_| anyhow!("Failed to read the document"))?
+            .map_err(|error| anyhow!("Failed to read the document: {}", error))?;
+        Ok(Extracted { text: crate::normalize::nfkc(&text), title: None, metadata: None })
+    }
+}
+
+struct Ocr;
+
+impl Ocr {
+    fn recognize(path: &Path, dir: &Path, budget: &Budget) -> anyhow::Result<String> {
+        let status = run(Command::new("pdftoppm")
+            .args(["-r", "300", "-png"])
+            .arg(path)
+            .arg(dir.join("page"))
+            .stderr(Stdio::null()), budget)
+            .context("Failed to run pdftoppm")?
+            .status;
+        if !status.success() {
+            bail!("pdftoppm failed: {}", status);
+        }
+        // Page numbers are padded to the same width, so they sort by name.
+        let mut images: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        images.sort();
+        let mut text = String::new();
+        for (index, image) in images.iter().enumerate() {
+            let output = run(Command::new("tesseract")
+                .arg(image)
+                .arg("stdout")
+                .stderr(Stdio::null()), budget)
+                .context("Failed to run tesseract")?;
+            if !output.status.success() {
+                bail!("tesseract failed: {}", output.status);
+            }
+            if index > 0 {
+                text.push('\x0c');
+            }
+            text.push_str(&crate::normalize::nfkc(&String::from_utf8_lossy(&output.stdout)));
+            if index + 1 < images.len() && budget.skip_rest(&text) {
+                break;
+            }
+        }
+        Ok(text)
+    }
+}
+
+impl Backend for Ocr {
+    fn extract(&mut self, path: &Path, budget: &Budget) -> anyhow::Result<Extracted> {
+        // Rendered pages, removed when dropped.
+        let dir = tempfile::Builder::new().prefix("classy-ocr-").tempdir()
+            .context("Failed to create a directory for rendered pages")?;
+        let text = Ocr::recognize(path, dir.path(), budget);
+        Ok(Extracted { text: text?, title: None, metadata: None })
+    }
+}
+
+struct Plaintext;
+
+impl Backend for Plaintext {
+    fn extract(&mut self, path: &Path, _budget: &Budget) -> anyhow::Result<Extracted> {
+        let content = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let text = crate::normalize::nfkc(&String::from_utf8_lossy(&content));
+        Ok(Extracted { text, title: None, metadata: None })
+    }
+}
+
+/// Backends of a file type, in the order they are tried.
+type Chain = Vec<(Kind, Box<dyn Backend>)>;
+
+/// Text printed by a command of `extractors`.
+struct External {
+    args: Vec<String>
+}
+
+impl Backend for External {
+    fn extract(&mut self, path: &Path, budget: &Budget) -> anyhow::Result<Extracted> {
+        let args: Vec<String> = self.args.iter()
+            .map(|arg| arg.replace("{file}", &path.to_string_lossy()))
+            .collect();
+        let output = run(Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null()), budget)
+            .with_context(|| format!("Failed to run {:?}", args[0]))?;
+        if !output.status.success() {
+            match String::from_utf8_lossy(&output.stderr).trim() {
+                "" => bail!("{:?} failed: {}", args[0], output.status),
+                errors => bail!("{:?} failed: {}: {}", args[0], output.status, errors)
+            }
+        }
+        let text = crate::normalize::nfkc(&String::from_utf8_lossy(&output.stdout));
+        Ok(Extracted { text, title: None, metadata: None })
+    }
+}
+
+/// Use of a backend, recorded with `Extractor::with_timings`.
+#[derive(Clone, Default)]
+pub struct Timing {
+    /// Files the backend was tried on.
+    pub files: usize,
+    /// Size of those files.
+    pub bytes: u64,
+    pub failed: usize,
+    /// Files it gave no usable text for.
+    pub unusable: usize,
+    pub elapsed: Duration
+}
+
+/// Use of each backend, by name.
+pub type Timings = Arc<Mutex<BTreeMap<String, Timing>>>;
+
+/// Extracts documents with the chain of their file type. An extractor is
+/// created once per worker thread so that the initialization cost of its
+/// backends is only paid once.
+pub struct Extractor {
+    chains: HashMap<String, Chain>,
+    cache: Option<Cache>,
+    metrics: Option<Arc<Metrics>>,
+    limits: FileLimits,
+    timings: Option<Timings>
+}
+
+impl Extractor {
+    /// Extractor with the default chains.
+    pub fn new(cache: Option<Cache>) -> Extractor {
+        Extractor {
+            chains: HashMap::new(),
+            cache,
+            metrics: None,
+            limits: Default::default(),
+            timings: None
+        }.with_chains(&default_chains())
+    }
+
+    /// Extract files with `chains`.
+    pub fn with_chains(mut self, chains: &Chains) -> Extractor {
+        self.chains = chains.iter()
+            .map(|(extension, kinds)| (extension.clone(),
+                kinds.iter().map(|kind| (kind.clone(), kind.backend())).collect()))
+            .collect();
+        self
+    }
+
+    /// Record the time taken by extractions in `metrics`.
+    pub fn with_metrics(mut self, metrics: Option<Arc<Metrics>>) -> Extractor {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Record the use of each backend in `timings`.
+    pub fn with_timings(mut self, timings: Timings) -> Extractor {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Extract each file within `limits`. Text cut short is not cached.
+    pub fn with_limits(mut self, limits: FileLimits) -> Extractor {
+        self.limits = limits;
+        self
+    }
+
+    /// Extract `path`, or reuse the text cached for it.
+    pub fn extract(&mut self, path: &Path) -> anyhow::Result<Extracted> {
+        let start = Instant::now();
+        let limits = self.limits.clone();
+        let budget = Budget {
+            path,
+            limits: &limits,
+            deadline: limits.timeout.map(|timeout| start + timeout),
+            partial: Cell::new(false)
+        };
+        if let Some(mut extracted) = self.cache.as_ref().and_then(|cache| cache.get(path)) {
+            extracted.text = budget.cut(extracted.text);
+            return Ok(extracted);
+        }
+        let extracted = self.extract_file(path, &budget);
+        if let Some(metrics) = &self.metrics {
+            metrics.extracted(start.elapsed());
+        }
+        if let (Some(cache), Ok(extracted)) = (&self.cache, &extracted) {
+            // Text that is not usable is left for other backends to retry.
+            if !budget.partial.get() && is_usable(&extracted.text) {
+                cache.put(path, extracted);
+            }
+        }
+        extracted
+    }
+
+    /// Text of the first backend of the chain of `path` yielding usable
+    /// text, or else of the first one that succeeded.
+    fn extract_file(&mut self, path: &Path, budget: &Budget) -> anyhow::Result<Extracted> {
+        // Files without an extension are only classified as sniffed PDFs.
+        let extension = path.extension()
+            .map_or("pdf".to_string(), |extension| extension.to_string_lossy().to_lowercase());
+        let chain = self.chains.get_mut(&extension)
+            .with_context(|| format!("No extractor for .{} files", extension))?;
+        let mut fallback = None;
+        let mut errors = Vec::new();
+        for (kind, backend) in chain.iter_mut() {
+            if let Err(error) = budget.check() {
+                errors.push(format!("{}: {:#}", kind, error));
+                break;
+            }
+            let start = Instant::now();
+            let extracted = backend.extract(path, budget).map(|mut extracted| {
+                extracted.text = budget.truncate(extracted.text);
+                extracted
+            });
+            if let Some(timings) = &self.timings {
+                let mut timings = timings.lock().unwrap();
+                let timing = timings.entry(kind.to_string()).or_default();
+                timing.files += 1;
+                timing.bytes += std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+                timing.elapsed += start.elapsed();
+                match &extracted {
+                    Ok(extracted) if !is_usable(&extracted.text) => timing.unusable += 1,
+                    Ok(_) => {}
+                    Err(_) => timing.failed += 1
+                }
+            }
+            match extracted {
+                Ok(extracted) if is_usable(&extracted.text) => return Ok(extracted),
+                Ok(extracted) => {
+                    log::debug!("{:?}: no usable text from {}", path, kind);
+                    fallback.get_or_insert(extracted);
+                }
+                Err(error) => {
+                    log::debug!("{:?}: {} failed: {:#}", path, kind, error);
+                    errors.push(format!("{}: {:#}", kind, error));
+                }
+            }
+        }
+        match fallback {
+            Some(extracted) => Ok(extracted),
+            None => bail!("{}", errors.join("; "))
+        }
+    }
+}
+
+type Reply = (usize, PathBuf, anyhow::Result<Extracted>);
+type Job = (usize, PathBuf, mpsc::Sender<Reply>);
+
+/// How far extraction may run ahead of the processing of its results.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Files handed to the workers and not yet returned.
+    pub queue: usize,
+    /// Bytes of extracted text waiting to be returned. At least one file
+    /// is extracted whatever its size.
+    pub buffered: usize
+}
+
+/// Long-lived worker threads, each owning an `Extractor`.
+pub struct Pool {
+    jobs: Option<mpsc::Sender<Job>>,
+    workers: Vec<std::thread::JoinHandle<()>>
+}
+
+impl Pool {
+    pub fn new(size: usize, chains: &Chains, cache: Option<Cache>,
+        metrics: Option<Arc<Metrics>>, limits: &FileLimits) -> Pool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..size.max(1)).map(|_| {
+            let receiver = Arc::clone(&receiver);
+            let cache = cache.clone();
+            let metrics = metrics.clone();
+            let chains = chains.clone();
+            let limits = limits.clone();
+            std::thread::spawn(move || {
+                let new = || Extractor::new(cache.clone())
+                    .with_chains(&chains)
+                    .with_metrics(metrics.clone())
+                    .with_limits(limits.clone());
+                let mut extractor = new();
+                loop {
+                    let job = receiver.lock().unwrap().recv();
+                    let (index, path, reply) = match job {
+                        Ok(job) => job,
+                        Err(_) => break
+                    };
+                    // A backend panicking fails its file, not the worker.
+                    let extracted = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                        extractor.extract(&path)
+                    }));
+                    let extracted = extracted.unwrap_or_else(|_| {
+                        extractor = new();
+                        Err(anyhow!("Extraction of {:?} panicked", path))
+                    });
+                    let _ = reply.send((index, path, extracted));
+                }
+            })
+        }).collect();
+        Pool { jobs: Some(sender), workers }
+    }
+
+    /// Extract the files of `paths` as they come, within `limits`,
+    /// returning the results in the same order.
+    pub fn stream<I: Iterator<Item = PathBuf>>(&self, paths: I, limits: Limits)
+        -> Stream<'_, I> {
+        let (reply, results) = mpsc::channel();
+        Stream {
+            pool: self,
+            paths,
+            limits,
+            reply,
+            results,
+            submitted: 0,
+            returned: 0,
+            pending: BTreeMap::new(),
+            buffered: 0,
+            exhausted: false
+        }
+    }
+}
+
+/// Results of `Pool::stream`.
+pub struct Stream<'p, I> {
+    pool: &'p Pool,
+    paths: I,
+    limits: Limits,
+    reply: mpsc::Sender<Reply>,
+    results: mpsc::Receiver<Reply>,
+    submitted: usize,
+    returned: usize,
+    /// Results that arrived before the ones of earlier files, by index.
+    pending: BTreeMap<usize, (PathBuf, anyhow::Result<Extracted>)>,
+    /// Bytes of text in `pending`.
+    buffered: usize,
+    exhausted: bool
+}
+
+fn size(extracted: &anyhow::Result<Extracted>) -> usize {
+    extracted.as_ref().map_or(0, |extracted| extracted.text.len())
+}
+
+impl<I: Iterator<Item = PathBuf>> Stream<'_, I> {
+    /// Hand files to the workers until a limit is reached.
+    fn submit(&mut self) {
+        let jobs = self.pool.jobs.as_ref().unwrap();
+        while !self.exhausted {
+            let in_flight = self.submitted - self.returned;
+            if in_flight > 0 && (in_flight >= self.limits.queue
+                || self.buffered >= self.limits.buffered) {
+                break;
+            }
+            match self.paths.next() {
+                Some(path) => {
+                    let job = (self.submitted, path, self.reply.clone());
+                    if let Err(mpsc::SendError((index, path, _))) = jobs.send(job) {
+                        let error = Err(anyhow!("No extraction worker left"));
+                        self.pending.insert(index, (path, error));
+                    }
+                    self.submitted += 1;
+                }
+                None => self.exhausted = true
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = PathBuf>> Iterator for Stream<'_, I> {
+    type Item = (PathBuf, anyhow::Result<Extracted>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.submit();
+        loop {
+            if let Some((path, extracted)) = self.pending.remove(&self.returned) {
+                self.buffered -= size(&extracted);
+                self.returned += 1;
+                return Some((path, extracted));
+            }
+            if self.returned == self.submitted {
+                return None;
+            }
+            let (index, path, extracted) = self.results.recv().ok()?;
+            self.buffered += size(&extracted);
+            self.pending.insert(index, (path, extracted));
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Selection of the files of the input directory by path, size and
+//! modification time.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use glob::{MatchOptions, Pattern};
+
+const OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false
+};
+
+/// Files to classify, by their path relative to the input directory.
+/// With no include pattern, every file is included.
+#[derive(Clone, Default)]
+pub struct Filter {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+    /// Number of levels walked below the input directory, 1 for the files
+    /// directly in it.
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    /// Lowercase extensions of the files to classify.
+    pub extensions: Vec<String>,
+    /// Take files without an extension for PDFs when their content starts
+    /// like one.
+    pub sniff: bool,
+    pub min_size: Option<Size>,
+    pub max_size: Option<Size>,
+    pub newer_than: Option<Time>,
+    pub older_than: Option<Time>
+}
+
+/// A number of bytes, optionally with a `k`, `M` or `G` binary suffix.
+#[derive(Clone, Copy, Debug)]
+pub struct Size(pub u64);
+
+impl std::str::FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let unit = match s[digits.len()..].to_lowercase().trim_end_matches('b') {
+            "" => 1,
+            "k" => 1 << 10,
+            "m" => 1 << 20,
+            "g" => 1 << 30,
+            _ => return Err(format!("Unknown size unit in '{}', expected k, M or G", s))
+        };
+        let number: u64 = digits.trim().parse()
+            .map_err(|_| format!("Invalid size '{}'", s))?;
+        number.checked_mul(unit).map(Size).ok_or_else(|| format!("Size too large '{}'", s))
+    }
+}
+
+/// A point in time, given as a date (`2024-03-01`) or as a duration before
+/// now in minutes, hours, days or weeks (`30m`, `12h`, `7d`, `2w`).
+#[derive(Clone, Copy, Debug)]
+pub struct Time(pub SystemTime);
+
+impl std::str::FromStr for Time {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            let time = date.and_hms_opt(0, 0, 0).unwrap()
+                .and_local_timezone(chrono::Local)
+                .earliest()
+                .ok_or_else(|| format!("Invalid local date '{}'", s))?;
+            return Ok(Time(time.into()));
+        }
+        let duration = parse_duration(s)
+            .map_err(|_| format!("Expected a date or a duration like '7d', got '{}'", s))?;
+        SystemTime::now().checked_sub(duration)
+            .map(Time)
+            .ok_or_else(|| format!("Duration too long '{}'", s))
+    }
+}
+
+/// Parse a duration in minutes, hours, days or weeks, such as `30m`, `12h`,
+/// `7d` or `2w`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let seconds = match s.chars().last() {
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        Some('w') => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Expected a duration like '7d', got '{}'", s))
+    };
+    let count: u64 = s[..s.len() - 1].trim().parse()
+        .map_err(|_| format!("Invalid duration '{}'", s))?;
+    Ok(Duration::from_secs(count.saturating_mul(seconds)))
+}
+
+/// `duration` in the largest unit `parse_duration` reads it back from.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    [('w', 7 * 24 * 60 * 60), ('d', 24 * 60 * 60), ('h', 60 * 60)].into_iter()
+        .find(|(_, unit)| seconds.is_multiple_of(*unit))
+        .map_or_else(|| format!("{}m", seconds / 60),
+            |(suffix, unit)| format!("{}{}", seconds / unit, suffix))
+}
+
+pub fn patterns(globs: &[String]) -> anyhow::Result<Vec<Pattern>> {
+    globs.iter()
+        .map(|glob| Pattern::new(glob)
+            .with_context(|| format!("Invalid glob pattern '{}'", glob)))
+        .collect()
+}
+
+impl Filter {
+    /// Whether the file or directory at `path` is skipped with everything
+    /// below it.
+    pub fn excludes(&self, path: &Path) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches_path_with(path, OPTIONS))
+    }
+
+    /// Whether the file at `path` is classified.
+    pub fn includes(&self, path: &Path) -> bool {
+        !self.excludes(path) && (self.include.is_empty()
+            || self.include.iter().any(|pattern| pattern.matches_path_with(path, OPTIONS)))
+    }
+
+    /// Whether a file with `metadata` is within the size and time limits.
+    pub fn accepts(&self, metadata: &std::fs::Metadata) -> bool {
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min.0)
+            || self.max_size.is_some_and(|max| size > max.0) {
+            return false;
+        }
+        if self.newer_than.is_none() && self.older_than.is_none() {
+            return true;
+        }
+        match metadata.modified() {
+            Ok(modified) => self.newer_than.is_none_or(|time| modified >= time.0)
+                && self.older_than.is_none_or(|time| modified < time.0),
+            Err(_) => false
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Approximate matching of keywords, for text with OCR errors such as
+//! "lnvoice" or "Rechnunq".
+
+use std::ops::Range;
+
+/// Words of `text` with their byte offset.
+pub fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(begin)) => {
+                words.push((begin, &text[begin..index]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        words.push((begin, &text[begin..]));
+    }
+    words
+}
+
+/// Edit distance between `a` and `b`, or `None` if it exceeds `limit`.
+fn distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        if current.iter().min().is_some_and(|&min| min > limit) {
+            return None;
+        }
+        previous = current;
+    }
+    previous.last().copied().filter(|&d| d <= limit)
+}
+
+/// Byte range of the first run of `words` within `limit` edits of
+/// `keyword`, compared word by word.
+pub fn find(keyword: &str, words: &[(usize, &str)], limit: usize) -> Option<Range<usize>> {
+    let terms: Vec<&str> = keyword.split_whitespace().collect();
+    if terms.is_empty() {
+        return None;
+    }
+    words.windows(terms.len()).find_map(|window| {
+        let edits = window.iter().zip(terms.iter())
+            .try_fold(0, |edits, ((_, word), term)| {
+                distance(word, term, limit - edits).map(|d| edits + d)
+            });
+        edits.map(|_| {
+            let (start, _) = window[0];
+            let (last, word) = window[window.len() - 1];
+            start..last + word.len()
+        })
+    })
+}
@@ -0,0 +1,28 @@
+//! Content hashes of files.
+
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+/// Hex encoded SHA-256 of the content of `path`.
+pub fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    hash_reader(file).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// Hex encoded SHA-256 of what `reader` reads.
+pub fn hash_reader(mut reader: impl Read) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
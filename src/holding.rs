@@ -0,0 +1,121 @@
+//! Holding directory of the output tree, where unmatched files wait with
+//! their extracted text. Every run classifies them again, so that they are
+//! filed once the rules improve, and reports the files that have waited
+//! longer than the retention period.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::extract::Extracted;
+
+/// File of the holding directory listing the files it holds.
+const MANIFEST: &str = ".classy-holding.json";
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// When the file was first parked.
+    parked: String,
+    /// Where it came from.
+    #[serde(with = "crate::paths::exact")]
+    source: PathBuf,
+    extracted: Extracted
+}
+
+pub struct Holding {
+    dir: PathBuf,
+    retention: Option<Duration>,
+    /// Entries by file name.
+    entries: RefCell<BTreeMap<String, Entry>>
+}
+
+impl Holding {
+    /// Holding directory `dir`, with the files parked by previous runs.
+    pub fn open(dir: PathBuf, retention: Option<Duration>) -> anyhow::Result<Holding> {
+        let path = dir.join(MANIFEST);
+        let entries = match std::fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content)
+                .with_context(|| format!("Corrupt holding manifest {:?}", path))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(error).with_context(|| format!("Failed to read {:?}", path))
+        };
+        Ok(Holding { dir, retention, entries: RefCell::new(entries) })
+    }
+
+    fn name(file: &Path) -> Option<String> {
+        file.file_name().map(|name| name.to_string_lossy().into_owned())
+    }
+
+    /// Whether `file` is in the holding directory.
+    pub fn holds(&self, file: &Path) -> bool {
+        file.parent().is_some_and(|dir| dir == self.dir)
+    }
+
+    /// Files waiting to be classified again, with their extracted text.
+    /// Files removed by hand are forgotten.
+    pub fn files(&self) -> Vec<(PathBuf, Extracted)> {
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|name, _| self.dir.join(name).is_file());
+        entries.iter()
+            .map(|(name, entry)| (self.dir.join(name), entry.extracted.clone()))
+            .collect()
+    }
+
+    /// Keep `file`, parked from `source`, until it is classified. Files
+    /// parked again keep their original date.
+    pub fn park(&self, file: &Path, source: &Path, extracted: Extracted) {
+        if let Some(name) = Holding::name(file) {
+            self.entries.borrow_mut().entry(name).or_insert_with(|| Entry {
+                parked: chrono::Local::now().to_rfc3339(),
+                source: source.to_path_buf(),
+                extracted
+            });
+        }
+    }
+
+    /// Forget `file`, which was filed.
+    pub fn release(&self, file: &Path) {
+        if let Some(name) = Holding::name(file) {
+            self.entries.borrow_mut().remove(&name);
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = self.dir.join(MANIFEST);
+        let entries = self.entries.borrow();
+        if entries.is_empty() && !path.exists() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec_pretty(&*entries)?)
+            .with_context(|| format!("Failed to write {:?}", temporary))?;
+        std::fs::rename(&temporary, &path)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Warn about the files that have waited longer than the retention
+    /// period.
+    pub fn report_overdue(&self) {
+        let retention = match self.retention {
+            Some(retention) => retention,
+            None => return
+        };
+        let now = chrono::Local::now();
+        for (name, entry) in self.entries.borrow().iter() {
+            let parked = match chrono::DateTime::parse_from_rfc3339(&entry.parked) {
+                Ok(parked) => parked,
+                Err(_) => continue
+            };
+            let waited = now.signed_duration_since(parked).to_std().unwrap_or_default();
+            if waited > retention {
+                log::warn!("{:?} has waited {} days in the holding directory, coming from {:?}",
+                    self.dir.join(name), waited.as_secs() / (24 * 60 * 60), entry.source);
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Commands run after a file is filed, from `exec:` in the rules.
+//!
+//! A command is a list of arguments, or a string split into words like a
+//! shell would, with single and double quotes, but run without a shell.
+//! These fields are replaced in every argument:
+//!
+//! - `{source}`: path the file was taken from
+//! - `{destination}`: path the file was filed at
+//! - `{rule}`: directory of the matching rule
+
+use std::cell::Cell;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+
+/// Command of a rule.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    args: Vec<String>
+}
+
+/// Words of `line`, separated by whitespace outside of quotes.
+pub fn split(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated quote in '{}'", line)
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(chars.next()),
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated quote in '{}'", line)
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parse a command given as a list of arguments, or as a single string.
+pub fn parse(args: Vec<String>) -> anyhow::Result<Hook> {
+    let args: Vec<String> = match &args[..] {
+        [command] => split(command)?,
+        _ => args
+    };
+    if args.is_empty() || args[0].is_empty() {
+        bail!("The command to execute is empty");
+    }
+    Ok(Hook { args })
+}
+
+impl Hook {
+    /// Command and arguments, before their fields are replaced.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+}
+
+/// Runs the hooks of a run and counts their failures.
+pub struct Runner {
+    pub timeout: Duration,
+    failures: Cell<usize>
+}
+
+impl Runner {
+    pub fn new(timeout: Duration) -> Runner {
+        Runner { timeout, failures: Cell::new(0) }
+    }
+
+    /// Number of hooks that failed or timed out.
+    pub fn failures(&self) -> usize {
+        self.failures.get()
+    }
+
+    /// Run `hook` for the file filed from `source` at `destination`. A
+    /// failure is reported without affecting the file.
+    pub fn run(&self, hook: &Hook, source: &Path, destination: &Path, rule: &Path) {
+        let args: Vec<String> = hook.args.iter()
+            .map(|arg| arg
+                .replace("{source}", &source.to_string_lossy())
+                .replace("{destination}", &destination.to_string_lossy())
+                .replace("{rule}", &rule.to_string_lossy()))
+            .collect();
+        log::debug!("running hook {:?}", args);
+        if let Err(error) = self.execute(&args) {
+            log::error!("{:?}: hook {:?} failed: {:#}", destination, args[0], error);
+            self.failures.set(self.failures.get() + 1);
+        }
+    }
+
+    fn execute(&self, args: &[String]) -> anyhow::Result<()> {
+        let mut child = Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // Read the errors as they come, so that a chatty command does not
+        // block on a full pipe.
+        let mut stderr = child.stderr.take().expect("piped stderr");
+        let reader = std::thread::spawn(move || {
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            errors
+        });
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("timed out after {} seconds", self.timeout.as_secs());
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        let errors = reader.join().unwrap_or_default();
+        if !status.success() {
+            match errors.trim() {
+                "" => bail!("{}", status),
+                errors => bail!("{}: {}", status, errors)
+            }
+        }
+        Ok(())
+    }
+}
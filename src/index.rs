@@ -0,0 +1,199 @@
+//! SQLite database of classified documents, with one row for every path a
+//! document was filed at, or left at when it was not filed.
+//!
+//! The rules and keywords of a document are stored as JSON arrays, so they
+//! can be queried with the JSON functions of SQLite. The full text is kept
+//! in the `texts` table for `classy search`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::output::Format;
+use crate::Classification;
+
+/// Number of characters of the text kept as the summary of a document.
+const SUMMARY_LENGTH: usize = 500;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS documents (
+        path TEXT PRIMARY KEY,
+        source TEXT NOT NULL,
+        hash TEXT,
+        rules TEXT NOT NULL,
+        keywords TEXT NOT NULL,
+        doctype TEXT,
+        ambiguous INTEGER NOT NULL,
+        title TEXT,
+        date TEXT,
+        summary TEXT NOT NULL,
+        action TEXT NOT NULL,
+        run_id TEXT NOT NULL,
+        classified_at TEXT NOT NULL,
+        modified TEXT
+    );
+    CREATE INDEX IF NOT EXISTS documents_hash ON documents (hash);
+    CREATE VIRTUAL TABLE IF NOT EXISTS texts USING fts5 (path UNINDEXED, title, text);
+";
+
+/// Document found by `search`.
+#[derive(Serialize)]
+struct Hit {
+    path: PathBuf,
+    rules: Vec<PathBuf>,
+    title: Option<String>,
+    date: Option<String>,
+    /// Text around the matching words, which are put in brackets.
+    snippet: String,
+    /// Whether the file is still where it was filed, always true for files
+    /// filed on remote hosts.
+    exists: bool
+}
+
+/// Beginning of `text`, with runs of whitespace collapsed.
+pub fn summary(text: &str) -> String {
+    text.split_whitespace()
+        .flat_map(|word| std::iter::once(' ').chain(word.chars()))
+        .skip(1)
+        .take(SUMMARY_LENGTH)
+        .collect()
+}
+
+/// Modification time of `path` in RFC 3339 format.
+fn modified(path: &Path) -> Option<String> {
+    let modified = path.metadata().and_then(|metadata| metadata.modified()).ok()?;
+    Some(chrono::DateTime::<chrono::Local>::from(modified).to_rfc3339())
+}
+
+pub fn connect(path: &Path) -> anyhow::Result<Connection> {
+    let connection = Connection::open(path)
+        .with_context(|| format!("Failed to open index {:?}", path))?;
+    connection.execute_batch(SCHEMA)
+        .with_context(|| format!("Failed to create the tables of index {:?}", path))?;
+    Ok(connection)
+}
+
+/// `query` in the syntax of FTS5, where every word is quoted so that
+/// punctuation is not taken for operators.
+fn fts_query(query: &str) -> String {
+    query.split_whitespace()
+        .map(|word| match word.strip_suffix('*').filter(|word| !word.is_empty()) {
+            Some(prefix) => format!("\"{}\"*", prefix.replace('"', "\"\"")),
+            None => format!("\"{}\"", word.replace('"', "\"\""))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Print the documents of the index at `path` containing the words of
+/// `query`, the most relevant first.
+pub fn search(path: &Path, query: &str, limit: usize, format: Format) -> anyhow::Result<()> {
+    if !path.is_file() {
+        bail!("No index at {:?}", path);
+    }
+    let query = fts_query(query);
+    if query.is_empty() {
+        bail!("Nothing to search for");
+    }
+    let connection = connect(path)?;
+    let mut statement = connection.prepare(
+        "SELECT documents.path, documents.rules, documents.title, documents.date,
+            snippet(texts, 2, '[', ']', '...', 12)
+        FROM texts JOIN documents ON documents.path = texts.path
+        WHERE texts MATCH ?1
+        ORDER BY rank
+        LIMIT ?2")?;
+    let hits = statement.query_map(params![query, limit], |row| {
+        let path = PathBuf::from(row.get::<_, String>(0)?);
+        Ok(Hit {
+            exists: crate::remote::is_remote(&path) || path.exists(),
+            path,
+            rules: serde_json::from_str(&row.get::<_, String>(1)?).unwrap_or_default(),
+            title: row.get(2)?,
+            date: row.get(3)?,
+            snippet: row.get::<_, String>(4)?.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+    }).with_context(|| format!("Failed to search index {:?}", path))?;
+    for hit in hits {
+        let hit = hit?;
+        match format {
+            Format::Text => {
+                let rules = hit.rules.iter()
+                    .map(|rule| rule.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let missing = if hit.exists { "" } else { " (missing)" };
+                println!("{}{}", hit.path.display(), missing);
+                if !rules.is_empty() {
+                    println!("    rules: {}", rules);
+                }
+                println!("    {}", hit.snippet);
+            }
+            Format::Json => println!("{}", serde_json::to_string(&hit)?)
+        }
+    }
+    Ok(())
+}
+
+pub struct Index {
+    connection: Connection,
+    run_id: String
+}
+
+impl Index {
+    /// Open the database at `path`, creating it if needed.
+    pub fn open(path: &Path, run_id: &str) -> anyhow::Result<Index> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(Index { connection: connect(path)?, run_id: run_id.to_string() })
+    }
+
+    /// Record `result`, filed with `action`, replacing what was recorded
+    /// for the same paths.
+    pub fn record(&self, result: &Classification, hash: Option<&str>, action: &str,
+        text: &str) -> anyhow::Result<()> {
+        let source = std::path::absolute(&result.source)?;
+        let paths: Vec<PathBuf> = match &result.destinations[..] {
+            [] => vec![source.clone()],
+            destinations => destinations.iter()
+                .map(crate::paths::absolute)
+                .collect::<Result<_, _>>()?
+        };
+        let rules: Vec<&Path> = result.matches.iter().map(|rule| rule.path.as_path()).collect();
+        let doctype = result.matches.iter()
+            .find_map(|rule| rule.doctype)
+            .map(|doctype| doctype.to_string());
+        let classified_at = chrono::Local::now().to_rfc3339();
+        let mut statement = self.connection.prepare_cached(
+            "INSERT OR REPLACE INTO documents (path, source, hash, rules, keywords, doctype,
+                ambiguous, title, date, summary, action, run_id, classified_at, modified)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)")?;
+        for path in paths.iter() {
+            statement.execute(params![
+                path.to_string_lossy(),
+                source.to_string_lossy(),
+                hash,
+                serde_json::to_string(&rules)?,
+                serde_json::to_string(&result.keywords())?,
+                doctype,
+                result.ambiguous,
+                result.title,
+                result.date.map(|date| date.to_string()),
+                summary(text),
+                action,
+                self.run_id,
+                classified_at,
+                modified(path)
+            ]).with_context(|| format!("Failed to index {:?}", path))?;
+            let path = path.to_string_lossy();
+            self.connection.execute("DELETE FROM texts WHERE path = ?1", [&path])?;
+            self.connection.execute("INSERT INTO texts (path, title, text) VALUES (?1, ?2, ?3)",
+                params![path, result.title, text])
+                .with_context(|| format!("Failed to index the text of {:?}", path))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Interactive creation of a starter configuration file.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+
+use crate::settings::Format;
+
+#[derive(Serialize)]
+struct Directory {
+    dir: String,
+    keywords: Vec<String>
+}
+
+#[derive(Serialize)]
+struct Starter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unmatched_dir: Option<String>,
+    layout: Vec<Directory>
+}
+
+fn ask(input: &mut impl BufRead, question: &str) -> anyhow::Result<String> {
+    print!("{} ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        bail!("No answer given");
+    }
+    Ok(answer.trim().to_string())
+}
+
+fn list(answer: &str) -> Vec<String> {
+    answer.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Write `config` at `path`, in the format given by its extension.
+pub fn write(path: &Path, config: &impl Serialize) -> anyhow::Result<()> {
+    let content = match Format::of(path) {
+        Format::Yaml => serde_yaml::to_string(config)?,
+        Format::Toml => toml::to_string_pretty(config)?,
+        Format::Json => serde_json::to_string_pretty(config)? + "\n"
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Ask for the categories to file documents into and write a
+/// configuration file for them at `path`, in the format given by its
+/// extension.
+pub fn init(path: &Path, force: bool) -> anyhow::Result<()> {
+    if path.exists() && !force {
+        bail!("{:?} already exists, use --force to replace it", path);
+    }
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+
+    let categories = loop {
+        let answer = ask(&mut input,
+            "Top-level categories, separated by commas (e.g. Invoices, Bank, Taxes):")?;
+        let categories = list(&answer);
+        if !categories.is_empty() {
+            break categories;
+        }
+    };
+    let mut layout = Vec::new();
+    for category in categories.iter() {
+        let answer = ask(&mut input, &format!(
+            "Keywords for {}, separated by commas [{}]:", category,
+            category.to_lowercase()))?;
+        let mut keywords = list(&answer);
+        if keywords.is_empty() {
+            keywords.push(category.to_lowercase());
+        }
+        layout.push(Directory { dir: category.clone(), keywords });
+    }
+    let unmatched = ask(&mut input,
+        "Directory for unmatched files, relative to the output (empty to leave them in place):")?;
+
+    let starter = Starter {
+        unmatched_dir: (!unmatched.is_empty()).then_some(unmatched),
+        layout
+    };
+    write(path, &starter)?;
+    println!("Wrote {:?}. Edit it to refine the rules, then check it with \
+        `classy config validate`.", path);
+    Ok(())
+}
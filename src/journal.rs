@@ -0,0 +1,373 @@
+//! Journal of file operations, used to undo runs.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::place::relocate;
+
+/// One operation performed on the file system.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub run_id: String,
+    pub timestamp: String,
+    /// "move", "copy", "symlink", "hardlink", "mkdir", "create", "remove"
+    /// or "tag".
+    pub action: String,
+    #[serde(with = "crate::paths::exact_option")]
+    pub source: Option<PathBuf>,
+    #[serde(with = "crate::paths::exact")]
+    pub destination: PathBuf,
+    /// Content hash of the destination after the operation.
+    pub hash: Option<String>,
+    /// Where a file replaced by this operation was saved.
+    #[serde(default, with = "crate::paths::exact_option")]
+    pub backup: Option<PathBuf>,
+    /// Output directory of the run, locked while it is undone.
+    #[serde(default, with = "crate::paths::exact_option")]
+    pub output: Option<PathBuf>
+}
+
+/// Appends the operations of one run to the journal file.
+pub struct Journal {
+    path: PathBuf,
+    run_id: String,
+    output: PathBuf
+}
+
+fn data_dir() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().to_path_buf())
+}
+
+fn journal_path() -> anyhow::Result<PathBuf> {
+    Ok(data_dir()?.join("journal.jsonl"))
+}
+
+/// Exclusive lock on the journal at `path`, held until dropped, so that
+/// no run appends to it while it is rewritten. As rewriting replaces the
+/// file, the lock is taken on a file next to it.
+fn lock(path: &Path) -> anyhow::Result<std::fs::File> {
+    let path = path.with_extension("lock");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {:?}", path))?;
+    file.lock().with_context(|| format!("Failed to lock {:?}", path))?;
+    Ok(file)
+}
+
+impl Journal {
+    /// Start the journal of a new run filing into `output`.
+    pub fn new(output: &Path) -> anyhow::Result<Journal> {
+        let run_id = format!("{}-{}",
+            chrono::Local::now().format("%Y%m%dT%H%M%S"), std::process::id());
+        let output = crate::paths::absolute(output)?;
+        Ok(Journal { path: journal_path()?, run_id, output })
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    pub fn record(&self, action: &str, source: Option<&Path>, destination: &Path,
+        backup: Option<PathBuf>) -> anyhow::Result<()> {
+        let hash = if destination.is_file() {
+            Some(crate::hash::hash_file(destination)?)
+        } else {
+            None
+        };
+        let entry = Entry {
+            run_id: self.run_id.clone(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            action: action.to_string(),
+            source: source.map(std::path::absolute).transpose()?,
+            destination: crate::paths::absolute(destination)?,
+            hash,
+            backup,
+            output: Some(self.output.clone())
+        };
+        let _lock = lock(&self.path)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open journal {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    /// Move a file about to be replaced out of the way so that the run can
+    /// be undone, and return where it was saved. Backups are named after
+    /// the path and the content of the file, so that files with the same
+    /// content replaced at different places are kept apart.
+    pub fn back_up(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let dir = self.path.with_file_name("backup").join(&self.run_id);
+        std::fs::create_dir_all(&dir)?;
+        let place = crate::hash::hash_reader(crate::paths::absolute(path)?
+            .as_os_str().as_encoded_bytes())?;
+        let name = format!("{}-{}", &place[..16], crate::hash::hash_file(path)?);
+        let backup = dir.join(name);
+        relocate(path, &backup)
+            .with_context(|| format!("Failed to back up {:?}", path))?;
+        Ok(backup)
+    }
+}
+
+fn load(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read journal {:?}", path))?;
+    content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line)
+            .with_context(|| format!("Corrupt journal {:?}", path)))
+        .collect()
+}
+
+/// Replace the journal at `path` with `entries`, which must be done while
+/// holding its lock.
+fn save(path: &Path, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    let temporary = path.with_extension("jsonl.tmp");
+    std::fs::write(&temporary, content)
+        .with_context(|| format!("Failed to write journal {:?}", temporary))?;
+    std::fs::rename(&temporary, path)
+        .with_context(|| format!("Failed to write journal {:?}", path))
+}
+
+/// Print the runs recorded in the journal, oldest first.
+pub fn list() -> anyhow::Result<()> {
+    let entries = load(&journal_path()?)?;
+    let mut runs: Vec<(&str, &str, usize)> = Vec::new();
+    for entry in entries.iter().filter(|e| e.action != "mkdir") {
+        match runs.last_mut() {
+            Some(run) if run.0 == entry.run_id => run.2 += 1,
+            _ => runs.push((&entry.run_id, &entry.timestamp, 1))
+        }
+    }
+    for (run_id, timestamp, count) in runs {
+        println!("{}  {}  {} operations", run_id, timestamp, count);
+    }
+    Ok(())
+}
+
+fn unchanged(entry: &Entry) -> bool {
+    match &entry.hash {
+        Some(hash) => crate::hash::hash_file(&entry.destination)
+            .is_ok_and(|current| &current == hash),
+        None => true
+    }
+}
+
+fn revert(entry: &Entry) -> anyhow::Result<()> {
+    let destination = &entry.destination;
+    match entry.action.as_str() {
+        "mkdir" => {
+            // Only remove directories left empty.
+            let _ = std::fs::remove_dir(destination);
+            return Ok(());
+        }
+        "tag" => return crate::tag::remove(destination),
+        "symlink" => {
+            if !destination.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+                bail!("{:?} is no longer a symbolic link", destination);
+            }
+        }
+        "remove" => {
+            if destination.exists() {
+                bail!("{:?} already exists", destination);
+            }
+        }
+        _ => {
+            if !destination.exists() {
+                bail!("{:?} no longer exists", destination);
+            }
+            if !unchanged(entry) {
+                bail!("{:?} was modified since it was filed", destination);
+            }
+        }
+    }
+
+    if entry.action == "move" {
+        let source = entry.source.as_ref()
+            .context("Journal entry without source")?;
+        if source.exists() {
+            bail!("{:?} already exists", source);
+        }
+        if let Some(parent) = source.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        relocate(destination, source)
+            .with_context(|| format!("Failed to move {:?} back", destination))?;
+    } else if entry.action != "remove" {
+        std::fs::remove_file(destination)
+            .with_context(|| format!("Failed to remove {:?}", destination))?;
+    }
+
+    if let Some(backup) = &entry.backup {
+        relocate(backup, destination)
+            .with_context(|| format!("Failed to restore {:?}", destination))?;
+    }
+    Ok(())
+}
+
+/// Reverse the operations of the run `run_id`, or of the last run. Runs
+/// that were fully reverted are removed from the journal.
+///
+/// The output directories of the run are locked like for a run, waiting
+/// for other runs with `wait`, unless `force` is set.
+pub fn undo(run_id: Option<&str>, wait: bool, force: bool) -> anyhow::Result<()> {
+    undo_in(&journal_path()?, run_id, wait, force)
+}
+
+/// `undo` with the journal at `path`.
+fn undo_in(path: &Path, run_id: Option<&str>, wait: bool, force: bool)
+    -> anyhow::Result<()> {
+    let entries = load(path)?;
+    let run_id = match run_id {
+        Some(run_id) => run_id.to_string(),
+        None => match entries.last() {
+            Some(entry) => entry.run_id.clone(),
+            None => bail!("The journal is empty, nothing to undo")
+        }
+    };
+    let outputs: std::collections::BTreeSet<PathBuf> = entries.iter()
+        .filter(|entry| entry.run_id == run_id)
+        .filter_map(|entry| entry.output.clone())
+        .collect();
+    let _locks = if force {
+        Vec::new()
+    } else {
+        outputs.iter()
+            .map(|output| crate::lock::acquire(output, wait))
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+    // Runs may have written to the journal while the locks were taken, and
+    // none may append to it until it is saved.
+    let _journal = lock(path)?;
+    let (run, mut kept): (Vec<Entry>, Vec<Entry>) = load(path)?.into_iter()
+        .partition(|entry| entry.run_id == run_id);
+    if run.is_empty() {
+        bail!("No run '{}' in the journal", run_id);
+    }
+
+    let mut failed = Vec::new();
+    for entry in run.iter().rev() {
+        match revert(entry) {
+            Ok(()) => if entry.action != "mkdir" {
+                log::info!("undone: {:?}", entry.destination);
+            },
+            Err(error) => {
+                log::error!("Cannot undo {:?}: {:#}", entry.destination, error);
+                failed.push(entry.clone());
+            }
+        }
+    }
+    let failures = failed.len();
+    failed.reverse();
+    kept.extend(failed);
+    save(path, &kept)?;
+    if failures > 0 {
+        bail!("{} operations of run {} could not be undone", failures, run_id);
+    }
+    log::info!("Run {} undone.", run_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Journal of the run `run_id` in `dir`, filing into `dir/output`.
+    fn journal(dir: &Path, run_id: &str) -> Journal {
+        Journal {
+            path: dir.join("journal.jsonl"),
+            run_id: run_id.to_string(),
+            output: dir.join("output")
+        }
+    }
+
+    fn run_ids(dir: &Path) -> Vec<String> {
+        load(&dir.join("journal.jsonl")).unwrap().into_iter().map(|entry| entry.run_id).collect()
+    }
+
+    #[test]
+    fn undoes_moves_of_the_last_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, destination) = (dir.path().join("in.pdf"), dir.path().join("out.pdf"));
+        let (other, kept) = (dir.path().join("other.pdf"), dir.path().join("kept.pdf"));
+        std::fs::write(&other, "other").unwrap();
+        std::fs::rename(&other, &kept).unwrap();
+        journal(dir.path(), "first").record("move", Some(&other), &kept, None).unwrap();
+        std::fs::write(&source, "document").unwrap();
+        std::fs::rename(&source, &destination).unwrap();
+        journal(dir.path(), "second").record("move", Some(&source), &destination, None)
+            .unwrap();
+
+        undo_in(&dir.path().join("journal.jsonl"), None, false, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "document");
+        assert!(!destination.exists());
+        assert!(kept.exists());
+        assert_eq!(run_ids(dir.path()), ["first"]);
+    }
+
+    #[test]
+    fn restores_replaced_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = journal(dir.path(), "run");
+        let (source, destination) = (dir.path().join("in.pdf"), dir.path().join("out.pdf"));
+        std::fs::write(&destination, "previous").unwrap();
+        let backup = journal.back_up(&destination).unwrap();
+        assert!(!destination.exists());
+        std::fs::write(&source, "new").unwrap();
+        std::fs::copy(&source, &destination).unwrap();
+        journal.record("copy", Some(&source), &destination, Some(backup)).unwrap();
+
+        undo_in(&dir.path().join("journal.jsonl"), Some("run"), false, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "previous");
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "new");
+        assert!(run_ids(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn keeps_backups_of_identical_files_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = journal(dir.path(), "run");
+        let (first, second) = (dir.path().join("a.pdf"), dir.path().join("b.pdf"));
+        std::fs::write(&first, "same").unwrap();
+        std::fs::write(&second, "same").unwrap();
+        let backups = [journal.back_up(&first).unwrap(), journal.back_up(&second).unwrap()];
+        assert_ne!(backups[0], backups[1]);
+        assert!(backups.iter().all(|backup| backup.is_file()));
+    }
+
+    #[test]
+    fn keeps_operations_that_cannot_be_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, destination) = (dir.path().join("in.pdf"), dir.path().join("out.pdf"));
+        std::fs::write(&destination, "document").unwrap();
+        journal(dir.path(), "run").record("move", Some(&source), &destination, None).unwrap();
+        std::fs::write(&destination, "edited since").unwrap();
+
+        let path = dir.path().join("journal.jsonl");
+        assert!(undo_in(&path, None, false, true).is_err());
+        assert!(!source.exists());
+        assert_eq!(run_ids(dir.path()), ["run"]);
+        assert!(undo_in(&path, Some("unknown"), false, true).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Append-only audit log of classifications.
+//!
+//! Unlike the journal, which is rewritten when runs are undone, entries
+//! are never removed from this log.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::Classification;
+
+/// What happened to one file.
+#[derive(Serialize)]
+struct LogEntry<'a> {
+    timestamp: String,
+    run_id: &'a str,
+    #[serde(serialize_with = "crate::paths::lossy")]
+    source: PathBuf,
+    /// Content hash of the file before it was filed.
+    hash: Option<String>,
+    rules: Vec<&'a Path>,
+    keywords: Vec<&'a str>,
+    doctype: Option<String>,
+    ambiguous: bool,
+    confidence: Option<f64>,
+    /// "move", "copy", "symlink", "hardlink", "set_aside", "hold",
+    /// "quarantine" or "none".
+    action: &'a str,
+    #[serde(serialize_with = "crate::paths::lossy_all")]
+    destinations: Vec<PathBuf>,
+    error: Option<String>
+}
+
+/// Default location of the audit log.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().join("audit.jsonl"))
+}
+
+pub struct Ledger {
+    path: PathBuf,
+    run_id: String
+}
+
+impl Ledger {
+    pub fn new(path: PathBuf, run_id: &str) -> Ledger {
+        Ledger { path, run_id: run_id.to_string() }
+    }
+
+    /// Append the outcome of filing `result` with `action`.
+    pub fn record(&self, result: &Classification, hash: Option<String>,
+        action: &str, error: Option<&anyhow::Error>) -> anyhow::Result<()> {
+        let entry = LogEntry {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            run_id: &self.run_id,
+            source: std::path::absolute(&result.source)?,
+            hash,
+            rules: result.matches.iter().map(|rule| rule.path.as_path()).collect(),
+            keywords: result.keywords(),
+            doctype: result.matches.iter()
+                .find_map(|rule| rule.doctype)
+                .map(|doctype| doctype.to_string()),
+            ambiguous: result.ambiguous,
+            confidence: result.confidence.map(crate::confidence::round),
+            action,
+            destinations: result.destinations.iter()
+                .map(crate::paths::absolute)
+                .collect::<Result<_, _>>()?,
+            error: error.map(|error| format!("{:#}", error))
+        };
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open audit log {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+            .with_context(|| format!("Failed to write audit log {:?}", self.path))
+    }
+}
@@ -0,0 +1,98 @@
+//! Checks of the rules a configuration resolves to, for the cruft that
+//! large configurations accumulate: duplicate destinations, rules with the
+//! same conditions, rules shadowed by an earlier broader one and rules
+//! that can never match.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::bail;
+
+use crate::{ClassifierPath, Config, MatchPolicy};
+
+/// Compiled keywords of `rule`, which differ when their options do.
+fn keywords(rule: &ClassifierPath) -> BTreeSet<(&str, Option<usize>)> {
+    rule.patterns.iter()
+        .map(|pattern| (pattern.regex.as_str(), pattern.options.fuzzy))
+        .collect()
+}
+
+fn can_match(rule: &ClassifierPath) -> bool {
+    !rule.keywords.is_empty() || rule.doctype.is_some() || !rule.near.is_empty()
+}
+
+/// Whether `a` and `b` match the same documents.
+fn same_conditions(a: &ClassifierPath, b: &ClassifierPath) -> bool {
+    let near = |rule: &ClassifierPath| -> BTreeSet<(String, String, usize)> {
+        rule.near.iter()
+            .map(|near| (near.keywords[0].clone(), near.keywords[1].clone(), near.within))
+            .collect()
+    };
+    let types = |rule: &ClassifierPath| -> BTreeSet<String> { rule.types.iter().cloned().collect() };
+    keywords(a) == keywords(b) && near(a) == near(b) && a.doctype == b.doctype
+        && types(a) == types(b) && a.pages == b.pages && a.min_matches == b.min_matches
+        && a.fuzzy == b.fuzzy
+}
+
+/// Whether every document `rule` matches is also matched by `broader`.
+fn covers(broader: &ClassifierPath, rule: &ClassifierPath) -> bool {
+    let near = broader.near.iter().all(|near| rule.near.iter()
+        .any(|other| other.keywords == near.keywords && other.within <= near.within));
+    let types = broader.types.is_empty()
+        || (!rule.types.is_empty() && rule.types.iter().all(|t| broader.types.contains(t)));
+    can_match(broader)
+        && keywords(broader).is_subset(&keywords(rule))
+        && rule.min_matches.is_none()
+        && near
+        && broader.doctype.is_none_or(|doctype| rule.doctype == Some(doctype))
+        && types
+        && (broader.pages.is_empty() || broader.pages == rule.pages)
+        && rule.fuzzy.unwrap_or(0) <= broader.fuzzy.unwrap_or(0)
+}
+
+/// Print the problems of the rules of `config`, read from `path`.
+/// Shadowed rules are only reported with the `first` match policy, as the
+/// others consider every rule.
+pub fn lint(path: &Path, config: &Config, policy: MatchPolicy) -> anyhow::Result<()> {
+    let rules = &config.paths;
+    let mut problems = Vec::new();
+    let mut destinations: HashMap<&Path, &ClassifierPath> = HashMap::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let earlier = &rules[..index];
+        if let Some(first) = destinations.get(rule.path.as_path()) {
+            problems.push(if same_conditions(first, rule) {
+                format!("{:?} is declared twice with the same keywords", rule.path)
+            } else {
+                format!("{:?} is declared twice with different keywords", rule.path)
+            });
+            continue;
+        }
+        destinations.insert(&rule.path, rule);
+        let has_subdirectories = rules.iter()
+            .any(|other| other.path != rule.path && other.path.starts_with(&rule.path));
+        if !can_match(rule) {
+            if !has_subdirectories {
+                problems.push(format!("{:?} has no keywords and can never match", rule.path));
+            }
+            continue;
+        }
+        if let Some(twin) = earlier.iter().find(|other| same_conditions(other, rule)) {
+            problems.push(format!("{:?} has the same keywords as {:?}", rule.path, twin.path));
+            continue;
+        }
+        if policy == MatchPolicy::First {
+            if let Some(broader) = earlier.iter().find(|other| covers(other, rule)) {
+                problems.push(format!("{:?} is shadowed by {:?}, which comes first and \
+                    matches every document it does", rule.path, broader.path));
+            }
+        }
+    }
+    for problem in problems.iter() {
+        println!("{}: {}", path.display(), problem);
+    }
+    if !problems.is_empty() {
+        bail!("{} problems found in {:?}", problems.len(), path);
+    }
+    println!("{}: OK", path.display());
+    Ok(())
+}
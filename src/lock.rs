@@ -0,0 +1,73 @@
+//! Advisory lock on an output directory, so that runs started by cron, the
+//! daemon or by hand do not file into it at the same time.
+//!
+//! The lock is a file of the runtime directory, or of the cache directory
+//! where there is none, named after the output directory. The operating
+//! system releases it when the process exits, however it exits.
+
+use std::fs::{File, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use sha2::{Digest, Sha256};
+
+/// Lock file of `output`.
+fn path(output: &Path) -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application cache directory")?;
+    let dir = dirs.runtime_dir().unwrap_or(dirs.cache_dir());
+    let output = if crate::remote::is_remote(output) {
+        output.to_path_buf()
+    } else {
+        output.canonicalize().or_else(|_| std::path::absolute(output))?
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(output.to_string_lossy().as_bytes());
+    Ok(dir.join(format!("run-{:x}.lock", hasher.finalize())))
+}
+
+/// Lock held until dropped.
+pub struct RunLock {
+    _file: File
+}
+
+/// Lock `output` for this run. If another run holds it, wait for it to be
+/// released with `wait`, or else fail.
+pub fn acquire(output: &Path, wait: bool) -> anyhow::Result<RunLock> {
+    let path = path(output)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {:?}", path))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut holder = String::new();
+            file.read_to_string(&mut holder).ok();
+            let holder = match holder.trim() {
+                "" => String::new(),
+                pid => format!(" (PID {})", pid)
+            };
+            if !wait {
+                bail!("Another run is filing into {:?}{}, pass --wait to wait for it or \
+                    --force to run anyway", output, holder);
+            }
+            log::info!("Waiting for another run filing into {:?}{}", output, holder);
+            file.lock().with_context(|| format!("Failed to lock {:?}", path))?;
+        }
+        Err(TryLockError::Error(error)) => return Err(error)
+            .with_context(|| format!("Failed to lock {:?}", path))
+    }
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())
+        .with_context(|| format!("Failed to write lock file {:?}", path))?;
+    Ok(RunLock { _file: file })
+}
@@ -0,0 +1,41 @@
+//! Log setup.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use log::LevelFilter;
+
+/// Log at the level selected by `-v` (repeated) and `--quiet`, on stderr
+/// or appended to `file`. `RUST_LOG` takes precedence when set.
+pub fn init(verbose: u8, quiet: bool, file: Option<&Path>) -> anyhow::Result<()> {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace
+    };
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level).parse_default_env();
+    match file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {:?}", path))?;
+            builder.target(env_logger::Target::Pipe(Box::new(file)))
+                .format(|buf, record| writeln!(buf, "{} {:<5} {}",
+                    chrono::Local::now().to_rfc3339(), record.level(), record.args()));
+        }
+        None => {
+            builder.format(|buf, record| match record.level() {
+                log::Level::Info => writeln!(buf, "{}", record.args()),
+                level => writeln!(buf, "{}: {}", level.as_str().to_lowercase(),
+                    record.args())
+            });
+        }
+    }
+    builder.try_init()?;
+    Ok(())
+}
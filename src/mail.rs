@@ -0,0 +1,233 @@
+//! Ingestion of the PDF attachments of the messages in an IMAP folder.
+//!
+//! The unread messages of the folder, or all of them with
+//! `processed_folder`, are downloaded, their PDF attachments are filed like
+//! the files of an input directory, and the messages are then marked as
+//! read, or moved to `processed_folder`.
+//! Messages with an attachment that failed to be filed, or that was left
+//! unmatched without `unmatched_dir`, are left as they were, to be tried
+//! again by the next run.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use mailparse::{DispositionType, ParsedMail};
+use serde::Deserialize;
+
+use crate::output::Summary;
+use crate::{extract, hook, place, Run};
+
+/// How the connection to the server is secured.
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    Tls,
+    Starttls,
+    None
+}
+
+/// Mailbox attachments are taken from, under `imap` in the configuration
+/// file.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    pub host: String,
+    /// Defaults to 993 with TLS and 143 otherwise.
+    pub port: Option<u16>,
+    #[serde(default = "default_security")]
+    pub security: Security,
+    pub username: String,
+    pub password: Option<String>,
+    /// Command printing the password, such as "pass show mail".
+    pub password_command: Option<String>,
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    /// Folder messages are moved to once processed, instead of being
+    /// marked as read.
+    pub processed_folder: Option<String>
+}
+
+fn default_security() -> Security {
+    Security::Tls
+}
+
+fn default_folder() -> String {
+    "INBOX".to_string()
+}
+
+impl Account {
+    fn password(&self) -> anyhow::Result<String> {
+        let command = match (&self.password, &self.password_command) {
+            (Some(password), _) => return Ok(password.clone()),
+            (None, Some(command)) => command,
+            (None, None) => bail!("No password for {}, set `password` or \
+                `password_command` under `imap`", self.username)
+        };
+        let args = hook::split(command)?;
+        let output = std::process::Command::new(args.first().context("Empty password command")?)
+            .args(&args[1..])
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .with_context(|| format!("Failed to run '{}'", command))?;
+        if !output.status.success() {
+            bail!("'{}' failed: {}", command, output.status);
+        }
+        let password = String::from_utf8(output.stdout)
+            .with_context(|| format!("'{}' printed an invalid password", command))?;
+        // Only the first line, as `pass` prints other fields below it.
+        Ok(password.lines().next().unwrap_or_default().to_string())
+    }
+}
+
+/// File name of an attachment, from its disposition or content type.
+fn file_name(part: &ParsedMail) -> Option<String> {
+    let disposition = part.get_content_disposition();
+    disposition.params.get("filename")
+        .or_else(|| part.ctype.params.get("name"))
+        .map(|name| name.to_string())
+}
+
+/// PDF attachments of `part` and its subparts, with their file names.
+fn attachments(part: &ParsedMail, found: &mut Vec<(String, Vec<u8>)>) -> anyhow::Result<()> {
+    for subpart in part.subparts.iter() {
+        attachments(subpart, found)?;
+    }
+    if !part.subparts.is_empty() {
+        return Ok(());
+    }
+    let name = file_name(part);
+    let is_pdf = part.ctype.mimetype == "application/pdf"
+        || name.as_ref().is_some_and(|name| name.to_lowercase().ends_with(".pdf"));
+    if is_pdf {
+        let name = name.unwrap_or_else(|| format!("attachment-{}.pdf", found.len() + 1));
+        found.push((name, part.get_body_raw()?));
+    } else if part.get_content_disposition().disposition == DispositionType::Attachment {
+        log::info!("Skipping attachment {:?} of type {}", name.unwrap_or_default(),
+            part.ctype.mimetype);
+    }
+    Ok(())
+}
+
+/// File the attachments of `message` from the spool directory `dir`.
+/// Returns whether they were all filed.
+fn ingest_message(run: &Run, message: &[u8], dir: &Path, summary: &mut Summary)
+    -> anyhow::Result<bool> {
+    let mail = mailparse::parse_mail(message).context("Failed to parse the message")?;
+    let subject = mail.headers.iter()
+        .find(|header| header.get_key_ref().eq_ignore_ascii_case("subject"))
+        .map(|header| header.get_value())
+        .unwrap_or_default();
+    let mut found = Vec::new();
+    attachments(&mail, &mut found)?;
+    log::info!("{:?}: {} attachments", subject, found.len());
+    let mut filed = true;
+    for (name, content) in found {
+        // Only the file name of the attachment, never a path.
+        let name = Path::new(&name).file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("attachment.pdf"));
+        let path = dir.join(name);
+        std::fs::OpenOptions::new().write(true).create_new(true).open(&path)
+            .and_then(|mut file| file.write_all(&content))
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        let extracted = extract::Extractor::new(None)
+            .with_chains(&run.config.extraction)
+            .with_metrics(run.metrics.clone())
+            .with_limits(run.limits.clone())
+            .extract(&path);
+        match run.process(&path, extracted) {
+            Ok(result) => {
+                summary.add(&result);
+                if result.destinations.is_empty() {
+                    filed = false;
+                }
+            }
+            Err(error) => {
+                log::error!("{:?}: {:#}", path, error);
+                summary.errors += 1;
+                filed = false;
+            }
+        }
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+    }
+    Ok(filed)
+}
+
+fn ingest_folder<T: Read + Write>(run: &Run, account: &Account, client: imap::Client<T>,
+    spool: &Path) -> anyhow::Result<Summary> {
+    let mut session = client.login(&account.username, account.password()?)
+        .map_err(|(error, _)| anyhow!("Failed to log in as {}: {}", account.username, error))?;
+    session.select(&account.folder)
+        .with_context(|| format!("Failed to select folder {}", account.folder))?;
+    let query = if account.processed_folder.is_some() { "ALL" } else { "UNSEEN" };
+    let mut uids: Vec<u32> = session.uid_search(query)?.into_iter().collect();
+    uids.sort();
+    log::info!("{} messages to process in {}", uids.len(), account.folder);
+    let mut summary = Summary::default();
+    for uid in uids {
+        let messages = session.uid_fetch(uid.to_string(), "BODY.PEEK[]")?;
+        let message = match messages.iter().find_map(|message| message.body()) {
+            Some(message) => message,
+            None => continue
+        };
+        let dir = spool.join(uid.to_string());
+        std::fs::create_dir(&dir)?;
+        let filed = match ingest_message(run, message, &dir, &mut summary) {
+            Ok(filed) => filed,
+            Err(error) => {
+                log::error!("Message {}: {:#}", uid, error);
+                summary.errors += 1;
+                false
+            }
+        };
+        std::fs::remove_dir_all(&dir)?;
+        if !filed || run.dry_run {
+            continue;
+        }
+        match &account.processed_folder {
+            Some(folder) => session.uid_mv(uid.to_string(), folder)
+                .with_context(|| format!("Failed to move message {} to {}", uid, folder))?,
+            None => {
+                session.uid_store(uid.to_string(), "+FLAGS (\\Seen)")
+                    .with_context(|| format!("Failed to mark message {} as read", uid))?;
+            }
+        }
+    }
+    session.logout()?;
+    Ok(summary)
+}
+
+/// File the attachments of the unread messages of `account`.
+pub fn ingest(run: &Run, account: &Account) -> anyhow::Result<Summary> {
+    if matches!(run.placer.action, place::Action::Symlink | place::Action::Tag) {
+        bail!("Attachments cannot be filed with --action {}, use move, copy or hardlink",
+            run.placer.action.verb());
+    }
+    // Only readable by this user, and removed when dropped.
+    let spool = tempfile::Builder::new().prefix("classy-mail-").tempdir()
+        .context("Failed to create a directory for attachments")?;
+    let host = account.host.as_str();
+    let connect = || -> anyhow::Result<Summary> {
+        let tls = native_tls::TlsConnector::new()?;
+        match account.security {
+            Security::Tls => ingest_folder(run, account,
+                imap::connect((host, account.port.unwrap_or(993)), host, &tls)?, spool.path()),
+            Security::Starttls => ingest_folder(run, account,
+                imap::connect_starttls((host, account.port.unwrap_or(143)), host, &tls)?,
+                spool.path()),
+            Security::None => {
+                let stream = std::net::TcpStream::connect((host, account.port.unwrap_or(143)))?;
+                let mut client = imap::Client::new(stream);
+                client.read_greeting()?;
+                ingest_folder(run, account, client, spool.path())
+            }
+        }
+    };
+    let summary = connect().with_context(|| format!("Failed to fetch mail from {}", host));
+    spool.close().context("Failed to remove the attachments directory")?;
+    summary
+}
@@ -1,67 +1,1409 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::string::String;
 
 extern crate serde;
-extern crate yaml_rust;
 extern crate pdf;
 extern crate preferences;
 extern crate directories;
 
-use anyhow::Context;
-use clap::Parser;
+use anyhow::{bail, Context};
+use clap::{CommandFactory, Parser};
 use directories::ProjectDirs;
-// use preferences::{AppInfo, PreferencesMap, Preferences};
 // use serde::{Serialize, Deserialize};
 use walkdir::WalkDir;
-use yaml_rust::YamlLoader;
-use yaml_rust::yaml;
+
+mod audit;
+mod bench;
+mod cache;
+mod confidence;
+mod corrections;
+mod daemon;
+mod date;
+mod defaults;
+mod doctype;
+mod duplicate;
+mod explain;
+mod extract;
+mod filter;
+mod fuzzy;
+mod hash;
+mod hook;
+mod holding;
+mod index;
+mod init;
+mod journal;
+mod ledger;
+mod lint;
+mod lock;
+mod logging;
+mod mail;
+mod man;
+mod metrics;
+mod model;
+mod normalize;
+mod output;
+mod pages;
+mod paths;
+mod place;
+mod preview;
+mod processed;
+mod progress;
+mod remote;
+mod rename;
+mod report;
+mod review;
+mod schedule;
+mod serve;
+mod settings;
+mod show;
+mod similar;
+mod state;
+mod suggest;
+mod tag;
+mod validate;
+mod watch;
+mod webhook;
+
+/// Exit status when some files could not be processed.
+const EXIT_ERRORS: i32 = 2;
+/// Exit status with `--fail-on-unmatched` when some files were not
+/// matched.
+const EXIT_UNMATCHED: i32 = 3;
+/// Exit status with `--fail-on-ambiguous` when some files matched
+/// competing rules.
+const EXIT_AMBIGUOUS: i32 = 4;
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about)]
+#[clap(author, version, about, subcommand_negates_reqs = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count
+    )]
+    /// Log more details, twice for even more.
+    verbose: u8,
+
     #[clap(
         short,
         long,
+        global = true,
+        conflicts_with = "verbose"
+    )]
+    /// Only log errors, and don't print the outcome of each file unless
+    /// it is formatted as JSON.
+    quiet: bool,
+
+    #[clap(
+        long,
+        global = true,
         parse(from_os_str)
     )]
-    /// Input directory containing files to be classified.
-    input: std::path::PathBuf,
+    /// Append log messages to this file instead of stderr.
+    log_file: Option<std::path::PathBuf>,
 
     #[clap(
         short,
         long,
+        required = true,
         parse(from_os_str)
     )]
-    /// Output directory.
-    output: std::path::PathBuf,
+    /// Input directory containing files to be classified. Can be repeated
+    /// to classify several directories into the same output. With "-",
+    /// the paths of the files are read from stdin, one per line or
+    /// separated by NUL characters.
+    input: Vec<std::path::PathBuf>,
 
     #[clap(
+        short,
         long,
+        required = true,
+        parse(from_os_str)
+    )]
+    /// Output directory, or the URL of a remote one: `sftp://host/path`,
+    /// or the `http://` or `https://` URL of a WebDAV collection.
+    output: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        global = true,
         parse(from_os_str)
     )]
     config: Option<std::path::PathBuf>,
 
     #[clap(long)]
-    /// Display configuration file.
-    print_config: bool
+    /// Display configuration file, see `config show --resolved` for the
+    /// rules it resolves to.
+    print_config: bool,
+
+    #[clap(long, global = true)]
+    /// Use the directories and settings of this profile from the
+    /// configuration file.
+    profile: Option<String>,
+
+    #[clap(long, global = true)]
+    /// Extract every file again instead of reusing the text cached by
+    /// previous runs.
+    no_cache: bool,
+
+    #[clap(long)]
+    /// Skip files processed by previous incremental runs, unless they
+    /// changed since, and remember the files processed by this one.
+    incremental: bool,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = MatchPolicy::First
+    )]
+    /// What to do when several rules match the same file.
+    match_policy: MatchPolicy,
+
+    #[clap(long)]
+    /// Move, copy, link or tag the files. Without it, runs only print where
+    /// files would go; the daemon, `serve`, `correct` and `--review` always
+    /// file.
+    apply: bool,
+
+    #[clap(long, conflicts_with = "apply")]
+    /// Only print where files would go, which is the default without
+    /// --apply.
+    dry_run: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with_all = &["apply", "watch"],
+        default_value_t = preview::Preview::Flat
+    )]
+    /// How a dry run shows where files would go.
+    preview: preview::Preview,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = output::Format::Text
+    )]
+    /// How the outcome of each file is printed.
+    format: output::Format,
+
+    #[clap(long)]
+    /// Show where the keywords of the matching rules appear in each file.
+    explain: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["watch", "dry-run"]
+    )]
+    /// Review and adjust the proposed destinations in a full-screen
+    /// interface before filing anything.
+    review: bool,
+
+    #[clap(long)]
+    /// Same as --fail-on-unmatched --fail-on-ambiguous --fail-on-error.
+    /// When several apply, errors take precedence over ambiguous files,
+    /// and those over unmatched files.
+    strict: bool,
+
+    #[clap(long)]
+    /// Exit with status 3 when some files were not matched by any rule.
+    fail_on_unmatched: bool,
+
+    #[clap(long)]
+    /// Exit with status 4 when some files matched competing rules, whether
+    /// or not they are sent to an ambiguous directory.
+    fail_on_ambiguous: bool,
+
+    #[clap(long)]
+    /// Exit with status 2 when some files could not be processed or hooks
+    /// failed. Runs always do, for compatibility with scripts checking the
+    /// status of plain runs, so the flag only makes it explicit.
+    fail_on_error: bool,
+
+    #[clap(long)]
+    /// Don't run the `exec` commands of the rules.
+    no_hooks: bool,
+
+    #[clap(long)]
+    /// Don't create the directories of the rules before filing anything,
+    /// only check that they can be created. They are never created in a
+    /// dry run.
+    no_create_dirs: bool,
+
+    #[clap(long)]
+    /// Wait for another run filing into the same output directory to
+    /// finish, instead of failing.
+    wait: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "wait"
+    )]
+    /// File even while another run files into the same output directory.
+    force: bool,
+
+    #[clap(long)]
+    /// Give destination files lowercase names made of ASCII letters, digits
+    /// and dashes. Rules can override it with `slugify`.
+    slugify: bool,
+
+    #[clap(
+        long,
+        default_value_t = 60
+    )]
+    /// Seconds after which an `exec` command is stopped and reported as
+    /// failed.
+    hook_timeout: u64,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = place::Action::Move
+    )]
+    /// How files are put into the output directory.
+    action: place::Action,
+
+    #[clap(long)]
+    /// Don't give copied files the access and modification times of the
+    /// originals, nor their owner when running as root.
+    no_preserve: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = place::ConflictPolicy::Error
+    )]
+    /// What to do when a file with the same name exists at the destination.
+    on_conflict: place::ConflictPolicy,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = place::DuplicatePolicy::KeepBoth
+    )]
+    /// What to do when a file with the same content exists anywhere in the
+    /// output directory, as recorded in the index if there is one or found
+    /// by hashing its files.
+    on_duplicate: place::DuplicatePolicy,
+
+    #[clap(
+        long,
+        parse(from_os_str)
+    )]
+    /// Directory receiving files matched by several rules with the same
+    /// score, relative to the output directory. Overrides `ambiguous_dir`
+    /// from the configuration file.
+    ambiguous_dir: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        parse(from_os_str)
+    )]
+    /// Directory receiving files matched by no rule, relative to the output
+    /// directory. Overrides `unmatched_dir` from the configuration file.
+    unmatched: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        parse(from_os_str)
+    )]
+    /// Directory where unmatched files wait to be classified again by later
+    /// runs, relative to the output directory. Overrides `holding_dir` from
+    /// the configuration file.
+    holding_dir: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        parse(from_os_str)
+    )]
+    /// Directory receiving files that cannot be read, relative to the
+    /// output directory. Overrides `errors_dir` from the configuration file.
+    errors_dir: Option<std::path::PathBuf>,
+
+    #[clap(long)]
+    /// Keep running and classify files as they appear in the input
+    /// directory.
+    watch: bool,
+
+    #[clap(
+        long,
+        default_value_t = 5
+    )]
+    /// Seconds between two scans of the input directory in watch mode.
+    interval: u64,
+
+    #[clap(long)]
+    /// Number of extraction workers. Defaults to the number of CPUs.
+    jobs: Option<usize>,
+
+    #[clap(long)]
+    /// Files extracted ahead of their filing. Defaults to four per
+    /// worker.
+    queue: Option<usize>,
+
+    #[clap(
+        long,
+        default_value_t = 256
+    )]
+    /// Megabytes of extracted text held ahead of filing. Extraction waits
+    /// when it is reached.
+    max_buffered: usize,
+
+    #[clap(long)]
+    /// Keep at most this much text of each file, e.g. "1M", skipping the
+    /// remaining pages.
+    max_text_bytes: Option<filter::Size>,
+
+    #[clap(long)]
+    /// Seconds after which the extraction of a file fails. Poppler gives
+    /// up between pages and commands are killed, but pdf-extract always
+    /// runs to the end.
+    extract_timeout: Option<u64>,
+
+    #[clap(long, conflicts_with = "watch")]
+    /// Stop extracting a file at the first page by which a rule matches.
+    /// Rules then only see the pages read so far: an earlier rule matching
+    /// further on loses, and `pages: last` is the last page read.
+    stop_at_match: bool,
+
+    #[clap(long)]
+    /// After the run, review a random sample of classified files, given
+    /// as a percentage ("5%") or a number of files ("20").
+    audit_sample: Option<audit::SampleSize>,
+
+    #[clap(
+        long,
+        conflicts_with = "watch",
+        parse(from_os_str)
+    )]
+    /// Write a CSV summary of the run to this file.
+    report: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        conflicts_with = "watch",
+        parse(from_os_str)
+    )]
+    /// Write an HTML report of the run to this file, with the files filed
+    /// into each directory, the unmatched files and the errors.
+    report_html: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        parse(from_os_str)
+    )]
+    /// File the audit log of classified files is appended to. Overrides
+    /// `audit_log` from the configuration file.
+    audit_log: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        global = true,
+        parse(from_os_str)
+    )]
+    /// SQLite database recording every classified file. Overrides `index`
+    /// from the configuration file.
+    index: Option<std::path::PathBuf>,
+
+    #[clap(long)]
+    /// Only classify files matching this glob, relative to the input
+    /// directory. Can be repeated. Overrides `include_files` from the
+    /// configuration file.
+    include: Vec<String>,
+
+    #[clap(long)]
+    /// Skip files and directories matching this glob, relative to the
+    /// input directory, e.g. "**/archive". Can be repeated. Overrides
+    /// `exclude_files` from the configuration file.
+    exclude: Vec<String>,
+
+    #[clap(long)]
+    /// Only walk this many levels below the input directory, 1 for the
+    /// files directly in it.
+    max_depth: Option<usize>,
+
+    #[clap(long)]
+    /// Follow symbolic links when walking the input directory.
+    follow_symlinks: bool,
+
+    #[clap(long)]
+    /// Also classify files without an extension whose content starts like
+    /// a PDF.
+    sniff: bool,
+
+    #[clap(long)]
+    /// Skip files smaller than this size, e.g. "10k".
+    min_size: Option<filter::Size>,
+
+    #[clap(long)]
+    /// Skip files larger than this size, e.g. "500M".
+    max_size: Option<filter::Size>,
+
+    #[clap(long)]
+    /// Only classify files modified after this date ("2024-03-01") or
+    /// within this duration ("7d", "12h", "2w").
+    newer_than: Option<filter::Time>,
+
+    #[clap(long)]
+    /// Only classify files modified before this date or longer ago than
+    /// this duration.
+    older_than: Option<filter::Time>,
+
+    #[clap(
+        long,
+        global = true,
+        parse(from_os_str)
+    )]
+    /// Model learned by `classy train`, filing the files no rule matches
+    /// into the directory it predicts. The file `train` writes to.
+    model: Option<std::path::PathBuf>,
+
+    #[clap(
+        long,
+        default_value_t = 0.8
+    )]
+    /// Probability below which the prediction of the model is ignored.
+    model_threshold: f64,
+
+    #[clap(long)]
+    /// Leave files unmatched, for `--unmatched` or the review, when the
+    /// confidence of their classification is below this value, from 0 to 1.
+    min_confidence: Option<f64>,
+
+    #[clap(long)]
+    /// When no rule matches, file documents into the directory of the
+    /// output holding the most similar document, if their similarity is at
+    /// least this value, from 0 to 1.
+    similarity: Option<f64>
+}
+
+impl Args {
+    /// Status a run with `summary` and `failed_hooks` exits with, if it
+    /// fails.
+    fn exit_status(&self, summary: &output::Summary, failed_hooks: usize) -> Option<i32> {
+        if summary.errors > 0 || failed_hooks > 0 {
+            Some(EXIT_ERRORS)
+        } else if (self.strict || self.fail_on_ambiguous) && summary.ambiguous > 0 {
+            Some(EXIT_AMBIGUOUS)
+        } else if (self.strict || self.fail_on_unmatched) && summary.unmatched > 0 {
+            Some(EXIT_UNMATCHED)
+        } else {
+            None
+        }
+    }
+
+    /// Expand `~` and environment variables in path arguments.
+    fn expand_paths(&mut self) -> anyhow::Result<()> {
+        let paths = [
+            &mut self.output, &mut self.config, &mut self.log_file,
+            &mut self.ambiguous_dir, &mut self.unmatched, &mut self.holding_dir,
+            &mut self.errors_dir, &mut self.report, &mut self.report_html, &mut self.audit_log,
+            &mut self.index, &mut self.model
+        ];
+        for path in paths.into_iter().flatten().chain(self.input.iter_mut()) {
+            *path = paths::expand(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Read text from stdin and print the destinations the rules would
+    /// choose for it, one per line.
+    MatchText,
+
+    /// Learn a model from a directory tree of already sorted files, each
+    /// directory being the destination of the files in it, and from the
+    /// recorded corrections.
+    Train {
+        #[clap(parse(from_os_str))]
+        labeled_dir: PathBuf
+    },
+
+    /// Print the most distinctive terms of the files in each directory of
+    /// an already sorted tree, as keywords for the configuration file.
+    Suggest {
+        #[clap(parse(from_os_str))]
+        dir: PathBuf,
+
+        #[clap(
+            long,
+            default_value_t = 5
+        )]
+        /// Number of keywords suggested for each directory.
+        count: usize
+    },
+
+    /// Extract and match the files of a directory without filing them,
+    /// and print the time taken by each stage and backend and the slowest
+    /// files and rules.
+    Bench {
+        #[clap(parse(from_os_str))]
+        dir: PathBuf,
+
+        #[clap(
+            long,
+            default_value_t = 5
+        )]
+        /// Number of slowest files and rules to print.
+        top: usize
+    },
+
+    /// Classify a single file and print the path it would be filed at,
+    /// below the output directory if given, without touching it. Exits
+    /// with status 1 if no rule matches.
+    Query {
+        #[clap(parse(from_os_str))]
+        file: PathBuf
+    },
+
+    /// File a misfiled document with the rule of another directory, record
+    /// the correction and print keyword changes that would have filed it
+    /// there. The model learns the correction.
+    Correct {
+        #[clap(parse(from_os_str))]
+        file: PathBuf,
+
+        #[clap(parse(from_os_str))]
+        /// Directory of the rule the document belongs to, as written in the
+        /// configuration file.
+        category: PathBuf
+    },
+
+    /// File the PDF attachments of the unread messages in the mailbox
+    /// configured under `imap`, then mark the messages as read.
+    Mail,
+
+    /// Search the text of the documents recorded in the index.
+    Search {
+        /// Words the documents must all contain. A word ending with `*`
+        /// also matches the longer words it begins.
+        query: String,
+
+        #[clap(
+            long,
+            default_value_t = 20
+        )]
+        /// Maximum number of documents to print.
+        limit: usize
+    },
+
+    /// Keep filing the files appearing in the input directories, like
+    /// `--watch --incremental`, until stopped by SIGTERM or SIGINT. Meant
+    /// to run as a service.
+    Daemon {
+        #[clap(
+            long,
+            parse(from_os_str)
+        )]
+        /// File holding the process identifier while the daemon runs.
+        /// Defaults to daemon.pid in the data directory.
+        pid_file: Option<PathBuf>,
+
+        #[clap(long)]
+        /// Only sweep the input directories at the times of `schedule` from
+        /// the configuration file, instead of also watching them.
+        no_watch: bool,
+
+        #[clap(long)]
+        /// Address and port to serve Prometheus metrics on, at /metrics,
+        /// e.g. "127.0.0.1:9090".
+        metrics: Option<String>
+    },
+
+    /// Classify documents uploaded over HTTP: POST them to /classify,
+    /// with `?store=true` to also file them into the output directory.
+    Serve {
+        #[clap(
+            long,
+            default_value = "127.0.0.1:8080"
+        )]
+        /// Address and port to listen on.
+        listen: String
+    },
+
+    /// Work with the configuration file.
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand
+    },
+
+    /// Save or restore classy's internal state.
+    State {
+        #[clap(subcommand)]
+        command: StateCommand
+    },
+
+    /// Save a default for an option, used when it is not given, e.g.
+    /// `classy set-default output ~/Documents`. Flags take "true" or
+    /// "false".
+    SetDefault {
+        #[clap(required_unless_present = "list")]
+        /// Long name of the option, without dashes.
+        key: Option<String>,
+
+        /// Value of the option, several for options that can be repeated.
+        values: Vec<String>,
+
+        #[clap(long)]
+        /// Forget the default of the option instead.
+        unset: bool,
+
+        #[clap(long, conflicts_with_all = &["key", "unset"])]
+        /// Print the saved defaults.
+        list: bool
+    },
+
+    /// Manage the cache of extracted text.
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommand
+    },
+
+    /// Reverse the file operations of a previous run.
+    Undo {
+        #[clap(long, conflicts_with = "run-id")]
+        /// Undo the most recent run (the default).
+        last: bool,
+
+        #[clap(long)]
+        /// Undo the run with this identifier.
+        run_id: Option<String>,
+
+        #[clap(long, conflicts_with_all = &["last", "run-id"])]
+        /// List the runs recorded in the journal.
+        list: bool
+    },
+
+    /// Print the completion script for a shell.
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell
+    },
+
+    /// Print the man page, or write the pages of every command to a
+    /// directory.
+    Man {
+        #[clap(long)]
+        /// Directory to write classy.1 and the pages of the subcommands to.
+        dir: Option<PathBuf>
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum CacheCommand {
+    /// Remove every cached text.
+    Clear
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ConfigCommand {
+    /// Check the configuration file and report every problem found.
+    Validate,
+
+    /// Report duplicate rules and rules that can never match.
+    Lint,
+
+    /// Print the configuration file.
+    Show {
+        #[clap(long)]
+        /// Print the rules the matcher uses instead, with the directories
+        /// flattened, the keywords and settings they inherit, and the
+        /// included files, profile and options applied.
+        resolved: bool
+    },
+
+    /// Create a starter configuration file by answering a few questions.
+    Init {
+        #[clap(long)]
+        /// Replace an existing configuration file.
+        force: bool
+    },
+
+    /// Create a configuration file mirroring the directories of an already
+    /// sorted tree, with keywords suggested from the files in them.
+    Learn {
+        #[clap(parse(from_os_str))]
+        sorted_root: PathBuf,
+
+        #[clap(
+            long,
+            default_value_t = 5
+        )]
+        /// Number of keywords suggested for each directory.
+        count: usize,
+
+        #[clap(long)]
+        /// Replace an existing configuration file.
+        force: bool
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum StateCommand {
+    /// Write the configuration, data and cache directories to a tar archive.
+    Export {
+        #[clap(parse(from_os_str))]
+        archive: std::path::PathBuf
+    },
+
+    /// Restore the state directories from a tar archive.
+    Import {
+        #[clap(parse(from_os_str))]
+        archive: std::path::PathBuf,
+
+        #[clap(long)]
+        /// Overwrite existing state.
+        force: bool
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MatchPolicy {
+    /// Stop at the first matching rule.
+    First,
+    /// Pick the rule with the most matched keywords.
+    Best,
+    /// Keep every matching rule. Files are moved into the first one, or
+    /// copied or linked into all of them with `--action`.
+    All
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct ClassifierPath {
     path: std::path::PathBuf,
-    keywords: Vec<String>
+    keywords: Vec<String>,
+    /// Options of `keywords`, in the same order.
+    keyword_options: Vec<KeywordOptions>,
+    /// Compiled `keywords`, in the same order.
+    patterns: Vec<Pattern>,
+    /// Edits allowed when looking for keywords that are plain words.
+    fuzzy: Option<usize>,
+    /// Keywords that must appear close to each other.
+    near: Vec<Near>,
+    /// Pages the rule is matched against, all of them when empty.
+    pages: Vec<pages::Page>,
+    /// Lowercase extensions of the files the rule applies to, all of them
+    /// when empty.
+    types: Vec<String>,
+    /// Number of keywords that must be found, all of them when unset.
+    min_matches: Option<usize>,
+    permissions: place::Permissions,
+    /// Template for the destination file name.
+    rename: Option<String>,
+    /// Whether to slugify the destination file name, following
+    /// `--slugify` when unset.
+    slugify: Option<bool>,
+    /// Document type the file must have been detected as.
+    doctype: Option<doctype::DocType>,
+    /// Command run after a file is filed by this rule.
+    exec: Option<hook::Hook>
 }
 type ClassifierPaths = Vec<ClassifierPath>;
+/// Keywords with their options, by name of the set they are defined in.
+type KeywordSets = BTreeMap<String, Vec<(String, KeywordOptions)>>;
+
+#[derive(Clone, Default)]
+struct Config {
+    paths: ClassifierPaths,
+    ambiguous_dir: Option<PathBuf>,
+    unmatched_dir: Option<PathBuf>,
+    /// Directory of the output where unmatched files wait to be classified
+    /// again by later runs.
+    holding_dir: Option<PathBuf>,
+    /// How long files may wait in the holding directory before being
+    /// reported.
+    holding_retention: Option<std::time::Duration>,
+    /// Directory receiving the files that cannot be read.
+    errors_dir: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+    /// Database of classified files.
+    index: Option<PathBuf>,
+    filter: filter::Filter,
+    /// Match keywords regardless of diacritics.
+    fold_diacritics: bool,
+    /// Orders tried for numeric dates such as 03/04/2023.
+    date_order: Vec<date::Order>,
+    webhook: Option<webhook::Webhook>,
+    /// Model filing the files no rule matches.
+    model: Option<Rc<model::Model>>,
+    /// Documents of the output tree filing the files no rule matches.
+    similar: Option<Rc<similar::Index>>,
+    /// Mailbox `classy mail` takes attachments from.
+    imap: Option<mail::Account>,
+    /// Extraction backends of each file type.
+    extraction: extract::Chains,
+    /// Times at which watch mode sweeps every file of the input
+    /// directories again.
+    schedule: Option<schedule::Schedule>,
+    /// Sets rules add to their keywords with `use`.
+    keyword_sets: KeywordSets,
+    /// Confidence below which files are left unmatched.
+    min_confidence: Option<f64>,
+    /// Configuration files read, with the files they include.
+    sources: Vec<PathBuf>
+}
+
+impl Config {
+    /// `text` as the rules see it.
+    fn prepare<'t>(&self, text: &'t str) -> std::borrow::Cow<'t, str> {
+        if self.fold_diacritics {
+            normalize::fold(text).into()
+        } else {
+            text.into()
+        }
+    }
+
+    fn uses_doctype(&self) -> bool {
+        self.paths.iter().any(|path| path.doctype.is_some())
+    }
+
+    /// Directory unmatched files are moved into, the holding directory
+    /// taking precedence.
+    fn set_aside_dir(&self) -> Option<&std::path::Path> {
+        self.holding_dir.as_deref().or(self.unmatched_dir.as_deref())
+    }
+}
+
+/// A file of a batch run, ready to be filed.
+enum Work {
+    Extracted(anyhow::Result<extract::Extracted>),
+    /// Classified and approved in the review.
+    Reviewed(Classification)
+}
+
+/// A document as seen by the rules.
+struct Document<'a> {
+    text: &'a str,
+    /// Detected type, only computed when a rule needs it.
+    doctype: Option<doctype::DocType>,
+    /// Lowercase extension of the file, if the text comes from a file.
+    file_type: Option<String>,
+    /// Words of the text, only split when a rule is fuzzy.
+    words: std::cell::OnceCell<Vec<(usize, &'a str)>>
+}
+
+impl<'a> Document<'a> {
+    fn new(config: &Config, text: &'a str, source: Option<&std::path::Path>) -> Document<'a> {
+        let doctype = if config.uses_doctype() { doctype::detect(text) } else { None };
+        let file_type = source
+            .and_then(|source| source.extension())
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        Document { text, doctype, file_type, words: Default::default() }
+    }
+
+    fn words(&self) -> &[(usize, &'a str)] {
+        self.words.get_or_init(|| fuzzy::words(self.text))
+    }
+}
+
+/// Options of a keyword given as `{ word: acme, case: insensitive,
+/// whole_word: false, fuzzy: 1 }`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct KeywordOptions {
+    ignore_case: bool,
+    /// Only match whole words, rather than inside longer ones.
+    whole_word: bool,
+    /// Edits allowed instead of the `fuzzy` of the rule.
+    fuzzy: Option<usize>
+}
+
+impl Default for KeywordOptions {
+    fn default() -> KeywordOptions {
+        KeywordOptions { ignore_case: false, whole_word: true, fuzzy: None }
+    }
+}
+
+/// A compiled keyword.
+#[derive(Clone)]
+struct Pattern {
+    /// Normalized keyword.
+    keyword: String,
+    regex: regex::Regex,
+    options: KeywordOptions
+}
+
+impl Pattern {
+    /// Byte range of the first occurrence in `doc`. When the keyword is not
+    /// found and is made of plain words, they may differ by up to `fuzzy`
+    /// edits.
+    fn find(&self, doc: &Document, fuzzy: Option<usize>) -> Option<std::ops::Range<usize>> {
+        if let Some(found) = self.regex.find(doc.text) {
+            return Some(found.range());
+        }
+        match self.options.fuzzy.or(fuzzy) {
+            Some(limit) if limit > 0 && regex::escape(&self.keyword) == self.keyword => {
+                if !self.options.ignore_case {
+                    return fuzzy::find(&self.keyword, doc.words(), limit);
+                }
+                let lowercase: Vec<(usize, String)> = doc.words().iter()
+                    .map(|(start, word)| (*start, word.to_lowercase()))
+                    .collect();
+                let words: Vec<(usize, &str)> = lowercase.iter()
+                    .map(|(start, word)| (*start, word.as_str()))
+                    .collect();
+                fuzzy::find(&self.keyword.to_lowercase(), &words, limit)
+            }
+            _ => None
+        }
+    }
+}
+
+/// Two keywords that must appear within `within` words of each other.
+#[derive(Clone, Default)]
+struct Near {
+    keywords: [String; 2],
+    patterns: Vec<Pattern>,
+    within: usize
+}
+
+impl Near {
+    fn matches(&self, doc: &Document) -> bool {
+        let positions = |pattern: &Pattern| -> Vec<usize> {
+            let words = doc.words();
+            pattern.regex.find_iter(doc.text)
+                .map(|found| words.partition_point(|(start, _)| *start < found.start()))
+                .collect()
+        };
+        let (first, second) = match &self.patterns[..] {
+            [first, second] => (positions(first), positions(second)),
+            _ => return false
+        };
+        first.iter().any(|a| second.iter().any(|b| a.abs_diff(*b) <= self.within))
+    }
+}
+
+/// Compile `keyword` for the rule at `path`. Keywords are regular
+/// expressions matched on word boundaries unless `options` say otherwise,
+/// normalized like the text and without diacritics if `fold` is set.
+fn compile(keyword: &str, options: KeywordOptions, fold: bool, path: &std::path::Path)
+    -> anyhow::Result<Pattern> {
+    if keyword.is_empty() {
+        bail!("Empty keyword for {:?}", path);
+    }
+    let mut keyword = normalize::nfkc(keyword);
+    if fold {
+        keyword = normalize::fold(&keyword);
+    }
+    let case = if options.ignore_case { "(?i)" } else { "" };
+    let boundary = if options.whole_word { "\\b" } else { "" };
+    let regex = regex::Regex::new(&[case, boundary, &keyword, boundary].join(""))
+        .with_context(|| format!(
+            "Keyword '{}' of {:?} is not a valid pattern, escape special characters \
+             such as '+' or '(' with a backslash", keyword, path))?;
+    Ok(Pattern { keyword, regex, options })
+}
 
 impl ClassifierPath {
-    fn matches(&self, text: &str) -> bool {
-        let contains = self.keywords.iter().all(|word| {
-            regex::Regex::new(&["\\b", word, "\\b"].join(""))
-                .unwrap()
-                .is_match(text)
+    /// Compile the keywords of the rule.
+    fn compile(&mut self, fold: bool) -> anyhow::Result<()> {
+        if let Some(min) = self.min_matches {
+            if min == 0 || min > self.keywords.len() {
+                bail!("'min_matches' of {:?} should be between 1 and its {} keywords",
+                    self.path, self.keywords.len());
+            }
+        }
+        self.patterns = self.keywords.iter().enumerate()
+            .map(|(index, keyword)| {
+                let options = self.keyword_options.get(index).copied().unwrap_or_default();
+                compile(keyword, options, fold, &self.path)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        for near in self.near.iter_mut() {
+            near.patterns = near.keywords.iter()
+                .map(|keyword| compile(keyword, KeywordOptions::default(), fold, &self.path))
+                .collect::<anyhow::Result<_>>()?;
+        }
+        Ok(())
+    }
+
+    fn matches(&self, doc: &Document) -> bool {
+        self.matched(doc).is_some()
+    }
+
+    /// Number of conditions this rule matched in `doc`.
+    fn score(&self, doc: &Document) -> usize {
+        self.matched(doc).unwrap_or(0)
+    }
+
+    /// Number of conditions matched in `doc`, or `None` if the rule does
+    /// not match.
+    fn matched(&self, doc: &Document) -> Option<usize> {
+        if !self.types.is_empty()
+            && !doc.file_type.as_ref().is_some_and(|file_type| self.types.contains(file_type)) {
+            return None;
+        }
+        if self.pages.is_empty() {
+            return self.matched_text(doc);
+        }
+        let text = pages::select(doc.text, &self.pages);
+        self.matched_text(&Document { text: &text, doctype: doc.doctype,
+            file_type: doc.file_type.clone(), words: Default::default() })
+    }
+
+    fn matched_text(&self, doc: &Document) -> Option<usize> {
+        if self.keywords.is_empty() && self.doctype.is_none() && self.near.is_empty() {
+            return None;
+        }
+        let found = self.patterns.iter()
+            .filter(|pattern| pattern.find(doc, self.fuzzy).is_some())
+            .count();
+        let contains = found >= self.min_matches.unwrap_or(self.patterns.len());
+        let near = self.near.iter().all(|near| near.matches(doc));
+        let doctype = self.doctype.is_none() || self.doctype == doc.doctype;
+        (contains && near && doctype)
+            .then(|| found + self.near.len() + usize::from(self.doctype.is_some()))
+    }
+}
+
+/// Outcome of classifying a single file.
+struct Classification {
+    source: PathBuf,
+    /// Rules the file was matched with, empty when it is unmatched.
+    matches: Vec<ClassifierPath>,
+    /// Whether competing rules that could not be told apart matched the
+    /// file. They are the `matches` when it goes to the ambiguous
+    /// directory.
+    ambiguous: bool,
+    /// Confidence of the first of `matches`, from 0 to 1. Also kept for
+    /// files left unmatched because it was below `--min-confidence`.
+    confidence: Option<f64>,
+    /// Document title from the file metadata.
+    title: Option<String>,
+    /// Document date found in the text or metadata.
+    date: Option<chrono::NaiveDate>,
+    /// Extracted text, kept until the file is filed to be recorded in the
+    /// index.
+    text: String,
+    /// Where the file was put in the output tree.
+    destinations: Vec<PathBuf>
+}
+
+impl Classification {
+    /// Keywords of the matching rules, without duplicates.
+    fn keywords(&self) -> Vec<&str> {
+        let mut keywords: Vec<&str> = Vec::new();
+        for rule in self.matches.iter() {
+            let near = rule.near.iter().flat_map(|near| near.keywords.iter());
+            for keyword in rule.keywords.iter().chain(near) {
+                if !keywords.contains(&keyword.as_str()) {
+                    keywords.push(keyword);
+                }
+            }
+        }
+        keywords
+    }
+}
+
+/// Settings shared by every file of a run.
+struct Run {
+    config: Rc<Config>,
+    /// Input directories, in which `.classy.yml` files add directories.
+    inputs: Vec<PathBuf>,
+    /// Configuration of input directories that were seen.
+    overrides: RefCell<HashMap<PathBuf, Rc<Config>>>,
+    policy: MatchPolicy,
+    placer: place::Placer,
+    ledger: ledger::Ledger,
+    index: Option<index::Index>,
+    dry_run: bool,
+    preview: preview::Preview,
+    format: output::Format,
+    explain: bool,
+    quiet: bool,
+    cache: Option<cache::Cache>,
+    /// Files processed by previous runs, in incremental runs.
+    processed: Option<RefCell<processed::Processed>>,
+    /// Files filed since the last summary posted to the webhook.
+    notifications: RefCell<(output::Summary, Vec<serde_json::Value>)>,
+    /// Metrics of the daemon and the HTTP API.
+    metrics: Option<Arc<metrics::Metrics>>,
+    /// Limits of the extraction of each file.
+    limits: extract::FileLimits,
+    /// Unmatched files waiting to be classified again.
+    holding: Option<holding::Holding>,
+    /// Output directory to lock while filing, unless the run is dry or
+    /// forced.
+    locked: Option<PathBuf>,
+    /// Whether missing directories of the output tree are created.
+    create_dirs: bool
+}
+
+impl Run {
+    /// Classify an extracted document and file it away.
+    fn process(&self, source: &std::path::Path, extracted: anyhow::Result<extract::Extracted>)
+        -> anyhow::Result<Classification> {
+        let outcome = match extracted {
+            Ok(extracted) => self.config_for(source).and_then(|config| {
+                let result = classify(source, &extracted, &config, self.policy);
+                let hits = self.explain
+                    .then(|| explain::hits(&result, &config.prepare(&extracted.text)));
+                self.apply(result, hits.as_deref())
+            }),
+            Err(error) => Err(self.unreadable(source, error))
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.file_done(outcome.as_ref().ok());
+        }
+        outcome
+    }
+
+    /// `error` reading `source`, after setting the file aside in
+    /// `errors_dir`, if any.
+    fn unreadable(&self, source: &std::path::Path, error: anyhow::Error) -> anyhow::Error {
+        let dir = match &self.config.errors_dir {
+            Some(dir) if self.placer.action != place::Action::Tag => dir,
+            _ => return error
+        };
+        match self.placer.set_aside(source, dir) {
+            Ok(Some(destination)) => log::info!("{:?} set aside in {:?}", source, destination),
+            Ok(None) => {}
+            Err(set_aside) => log::error!("{:?}: {:#}", source, set_aside)
+        }
+        error
+    }
+
+    /// Configuration for `source`, with the overrides of the input
+    /// directories it is in.
+    fn config_for(&self, source: &std::path::Path) -> anyhow::Result<Rc<Config>> {
+        let dir = match source.parent() {
+            Some(dir) => dir,
+            None => return Ok(self.config.clone())
+        };
+        // The innermost input, should inputs be nested.
+        let input = self.inputs.iter()
+            .filter(|input| dir.starts_with(input))
+            .max_by_key(|input| input.components().count());
+        match input {
+            Some(input) => self.dir_config(input, dir),
+            None => Ok(self.config.clone())
+        }
+    }
+
+    fn dir_config(&self, input: &std::path::Path, dir: &std::path::Path)
+        -> anyhow::Result<Rc<Config>> {
+        if let Some(config) = self.overrides.borrow().get(dir) {
+            return Ok(config.clone());
+        }
+        let parent = match dir.parent() {
+            Some(parent) if dir != input => self.dir_config(input, parent)?,
+            _ => self.config.clone()
+        };
+        let config = match settings::find_override(dir) {
+            Some(path) => Rc::new(settings::apply_override(&parent, &path)?),
+            None => parent
+        };
+        self.overrides.borrow_mut().insert(dir.to_path_buf(), config.clone());
+        Ok(config)
+    }
+
+    /// File away a classified document, or only print where it would go
+    /// in a dry run.
+    fn apply(&self, mut result: Classification, hits: Option<&[explain::Hit]>)
+        -> anyhow::Result<Classification> {
+        let action = self.action(&result);
+        let text = std::mem::take(&mut result.text);
+        if self.dry_run {
+            let planned = self.planned(&result)?;
+            self.print(&result, action, &planned, hits)?;
+            return Ok(result);
+        }
+        let hash = hash::hash_file(&result.source).ok();
+        let source = std::path::absolute(&result.source)?;
+        let stamp = processed::Stamp::of(&result.source);
+        match file_away(&result, &self.config, &self.placer) {
+            Ok(destinations) => {
+                result.destinations = destinations;
+                self.ledger.record(&result, hash.clone(), action, None)?;
+                if let Some(index) = &self.index {
+                    index.record(&result, hash.as_deref(), action, &text)?;
+                }
+                if let (Some(processed), Some(hash), Some(stamp))
+                    = (&self.processed, hash, stamp) {
+                    processed.borrow_mut().record(source.clone(), hash, stamp,
+                        output::status(&result));
+                }
+                self.hold(&result, &source, &text);
+                self.notify(&result, action)?;
+                self.print(&result, action, &result.destinations, hits)?;
+                Ok(result)
+            }
+            Err(error) => {
+                self.ledger.record(&result, hash, action, Some(&error))?;
+                Err(error)
+            }
+        }
+    }
+
+    fn print(&self, result: &Classification, action: &str, destinations: &[PathBuf],
+        hits: Option<&[explain::Hit]>) -> anyhow::Result<()> {
+        if (self.quiet && self.format == output::Format::Text)
+            || (self.dry_run && self.preview == preview::Preview::Tree) {
+            return Ok(());
+        }
+        output::print(self.format, result, self.config.set_aside_dir(), action, destinations,
+            self.dry_run, hits)
+    }
+
+    /// Directories `result` would be filed into.
+    fn planned(&self, result: &Classification) -> anyhow::Result<Vec<PathBuf>> {
+        let matches: Vec<&ClassifierPath> = result.matches.iter().collect();
+        let date = result.date.or_else(|| date::modified(&result.source));
+        Ok(destinations(&self.config, &matches, result.ambiguous, date)?
+            .iter()
+            .map(|dir| self.placer.output.join(dir))
+            .collect())
+    }
+
+    /// Skip files processed by previous incremental runs.
+    fn is_done(&self, path: &std::path::Path) -> bool {
+        let done = self.processed.as_ref()
+            .is_some_and(|processed| processed.borrow().is_done(path));
+        if done {
+            log::debug!("{:?}: already processed", path);
+        }
+        done
+    }
+
+    /// Skip files filed by previous incremental runs, trying again the
+    /// unmatched ones left in place.
+    fn is_filed(&self, path: &std::path::Path) -> bool {
+        let filed = self.processed.as_ref().is_some_and(|processed| {
+            processed.borrow().outcome(path).is_some_and(|outcome| outcome != "unmatched")
         });
-        !self.keywords.is_empty() && contains
-    }    
+        if filed {
+            log::debug!("{:?}: already filed", path);
+        }
+        filed
+    }
+
+    /// Post `result` to the webhook, or keep it for the next summary. Files
+    /// left in place are only counted in summaries.
+    fn notify(&self, result: &Classification, action: &str) -> anyhow::Result<()> {
+        let webhook = match &self.config.webhook {
+            Some(webhook) => webhook,
+            None => return Ok(())
+        };
+        if webhook.batch {
+            self.notifications.borrow_mut().0.add(result);
+        }
+        if result.destinations.is_empty() {
+            return Ok(());
+        }
+        let record = output::record(&result.source, result, action, &result.destinations,
+            false, None)?;
+        if webhook.batch {
+            self.notifications.borrow_mut().1.push(record);
+        } else {
+            webhook.post(&record);
+        }
+        Ok(())
+    }
+
+    /// Post the files filed since the last summary to the webhook, if
+    /// there are any.
+    fn post_summary(&self) {
+        let (summary, files) = self.notifications.take();
+        if let Some(webhook) = self.config.webhook.as_ref().filter(|_| !files.is_empty()) {
+            webhook.post_summary(&summary, files);
+        }
+    }
+
+    /// Park `result` in the holding directory when it is unmatched, or
+    /// release it when a held file was filed.
+    fn hold(&self, result: &Classification, source: &std::path::Path, text: &str) {
+        let holding = match &self.holding {
+            Some(holding) => holding,
+            None => return
+        };
+        match result.destinations.first() {
+            Some(held) if result.matches.is_empty() => holding.park(held, source,
+                extract::Extracted {
+                    text: text.to_string(),
+                    title: result.title.clone(),
+                    metadata: None
+                }),
+            _ if holding.holds(&result.source) => holding.release(&result.source),
+            _ => {}
+        }
+    }
+
+    /// Lock the output directory until the lock is dropped, waiting for
+    /// other runs with `wait`.
+    fn lock(&self, wait: bool) -> anyhow::Result<Option<lock::RunLock>> {
+        self.locked.as_deref().map(|output| lock::acquire(output, wait)).transpose()
+    }
+
+    /// Files of the holding directory, to be classified again.
+    fn held(&self) -> Vec<(PathBuf, extract::Extracted)> {
+        self.holding.as_ref().map(holding::Holding::files).unwrap_or_default()
+    }
+
+    /// Write the files processed so far to the state file, and the files
+    /// held to the holding directory.
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(holding) = self.holding.as_ref().filter(|_| !self.dry_run) {
+            holding.save()?;
+        }
+        match &self.processed {
+            Some(processed) => processed.borrow_mut().save(),
+            None => Ok(())
+        }
+    }
+
+    /// Use `config` for the files processed from now on.
+    /// Use `config` from now on, once the output tree is prepared for it
+    /// as at startup. The current configuration is kept on errors.
+    fn reload(&mut self, config: Config) -> anyhow::Result<()> {
+        if let Some(holding) = &self.holding {
+            holding.save()?;
+        }
+        self.holding = prepare(&self.placer, &config, self.create_dirs)?;
+        if self.limits.stop.is_some() {
+            self.limits.stop = Some(stop_at_match(&config));
+        }
+        self.config = Rc::new(config);
+        self.overrides.get_mut().clear();
+        Ok(())
+    }
+
+    /// Configuration files in use, and the override files of the input
+    /// directories seen, whether they exist or not.
+    fn config_sources(&self) -> Vec<PathBuf> {
+        let overrides = self.overrides.borrow();
+        let dirs = overrides.keys()
+            .flat_map(|dir| settings::OVERRIDE_NAMES.iter().map(|name| dir.join(name)));
+        self.config.sources.iter().cloned().chain(dirs).collect()
+    }
+
+    /// What `file_away` does with `result`.
+    fn action(&self, result: &Classification) -> &'static str {
+        if self.placer.action == place::Action::Tag {
+            return if result.matches.is_empty() { "none" } else { "tag" };
+        }
+        if result.matches.is_empty() {
+            return match &self.config {
+                config if config.holding_dir.is_some() => "hold",
+                config if config.unmatched_dir.is_some() => "set_aside",
+                _ => "none"
+            };
+        }
+        if result.ambiguous && self.config.ambiguous_dir.is_some() {
+            return "quarantine";
+        }
+        self.placer.action.verb()
+    }
 }
 
 impl std::fmt::Display for ClassifierPath {
@@ -71,13 +1413,15 @@ impl std::fmt::Display for ClassifierPath {
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse_from(defaults::args(Args::command()));
+    args.expand_paths()?;
+    logging::init(args.verbose, args.quiet, args.log_file.as_deref())?;
 
     let mut config_path = PathBuf::new();
-    if let Some(path) = args.config {
+    if let Some(path) = &args.config {
         config_path = path.clone();
-    } else if let Some(proj_dirs) = ProjectDirs::from("", "", "ddc") {
-        config_path = proj_dirs.config_dir().join("config.yml");
+    } else if let Some(proj_dirs) = project_dirs() {
+        config_path = settings::default_path(proj_dirs.config_dir());
     }
 
     if args.print_config {
@@ -85,129 +1429,898 @@ fn main() -> anyhow::Result<()> {
         return Ok(())
     }
 
-    let config = parse_config(&config_path)?;
+    match &args.command {
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(*shell, &mut Args::command(), "classy",
+                &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Command::Man { dir }) => return man::generate(Args::command(), dir.as_deref()),
+        Some(Command::SetDefault { list: true, .. }) => return defaults::list(),
+        Some(Command::SetDefault { key: Some(key), values, unset, .. }) =>
+            return defaults::set(Args::command(), key, values, *unset),
+        Some(Command::Config { command: ConfigCommand::Validate }) =>
+            return validate::validate(&config_path),
+        Some(Command::Config { command: ConfigCommand::Show { resolved: false } }) =>
+            return print_config(&config_path),
+        Some(Command::Config { command: ConfigCommand::Init { force } }) =>
+            return init::init(&config_path, *force),
+        Some(Command::Config { command: ConfigCommand::Learn { sorted_root, count, force } }) => {
+            let cache = if args.no_cache { None } else { Some(cache::Cache::open()?) };
+            return suggest::learn(&paths::expand(sorted_root)?, &config_path, *count, *force,
+                &mut extract::Extractor::new(cache));
+        }
+        Some(Command::State { command: StateCommand::Export { archive } }) =>
+            return state::export(&paths::expand(archive)?),
+        Some(Command::State { command: StateCommand::Import { archive, force } }) =>
+            return state::import(&paths::expand(archive)?, *force),
+        Some(Command::Cache { command: CacheCommand::Clear }) => return cache::clear(),
+        Some(Command::Train { labeled_dir }) =>
+            return train(&paths::expand(labeled_dir)?, args.model, args.no_cache),
+        Some(Command::Suggest { dir, count }) => {
+            let cache = if args.no_cache { None } else { Some(cache::Cache::open()?) };
+            return suggest::suggest(&paths::expand(dir)?, *count,
+                &mut extract::Extractor::new(cache));
+        }
+        Some(Command::Undo { list: true, .. }) => return journal::list(),
+        Some(Command::Undo { run_id, .. }) =>
+            return journal::undo(run_id.as_deref(), args.wait, args.force),
+        _ => {}
+    }
 
-    let extensions: std::collections::HashSet<&str>
-        = vec!["pdf"].into_iter().collect();
-    let files = WalkDir::new(args.input)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| {
-            let extension = e.path().extension();
-            if extension == None { return false; };
-            let extension = extension.unwrap().to_str().unwrap();
-            e.file_type().is_file() && extensions.contains(&extension)
+    let load = || -> anyhow::Result<Config> {
+        let mut config = settings::load(&config_path, args.profile.as_deref())?;
+        if args.ambiguous_dir.is_some() {
+            config.ambiguous_dir = args.ambiguous_dir.clone();
+        }
+        if args.unmatched.is_some() {
+            config.unmatched_dir = args.unmatched.clone();
+        }
+        if args.holding_dir.is_some() {
+            config.holding_dir = args.holding_dir.clone();
+        }
+        if args.errors_dir.is_some() {
+            config.errors_dir = args.errors_dir.clone();
+        }
+        if args.audit_log.is_some() {
+            config.audit_log = args.audit_log.clone();
+        }
+        if args.index.is_some() {
+            config.index = args.index.clone();
+        }
+        if !args.include.is_empty() {
+            config.filter.include = filter::patterns(&args.include)?;
+        }
+        if !args.exclude.is_empty() {
+            config.filter.exclude = filter::patterns(&args.exclude)?;
+        }
+        config.filter.max_depth = args.max_depth;
+        config.filter.follow_symlinks = args.follow_symlinks;
+        config.filter.sniff = args.sniff;
+        config.filter.min_size = args.min_size;
+        config.filter.max_size = args.max_size;
+        config.filter.newer_than = args.newer_than;
+        config.filter.older_than = args.older_than;
+        config.min_confidence = args.min_confidence;
+        if let Some(path) = &args.model {
+            config.model = Some(Rc::new(
+                model::load(path, &config.paths, args.model_threshold)?));
+        }
+        if let (Some(threshold), Some(output)) = (args.similarity, &args.output) {
+            if remote::is_remote(output) {
+                bail!("--similarity needs a local output directory");
+            }
+            let excluded = config.unmatched_dir.iter()
+                .chain(config.holding_dir.iter())
+                .chain(config.ambiguous_dir.iter())
+                .chain(config.errors_dir.iter())
+                .cloned()
+                .collect();
+            let cache = if args.no_cache { None } else { Some(cache::Cache::open()?) };
+            config.similar = Some(Rc::new(similar::Index::new(output.clone(), threshold,
+                &config.paths, excluded, cache)));
+        }
+        Ok(config)
+    };
+    let config = load()?;
+    let cache = if args.no_cache { None } else { Some(cache::Cache::open()?) };
+
+    if let Some(Command::Config { command: ConfigCommand::Show { resolved: true } }) =
+        args.command {
+        return show::resolved(&config, args.format);
+    }
+    if let Some(Command::Config { command: ConfigCommand::Lint }) = args.command {
+        return lint::lint(&config_path, &config, args.match_policy);
+    }
+    if let Some(Command::MatchText) = args.command {
+        return match_text(&config, args.match_policy);
+    }
+    if let Some(Command::Query { file }) = &args.command {
+        return query(file, &config, args.match_policy, args.action, args.output.as_deref(),
+            args.slugify, cache);
+    }
+    if let Some(Command::Bench { dir, top }) = &args.command {
+        return bench::bench(&paths::expand(dir)?, &config, args.match_policy, args.format,
+            *top);
+    }
+    if let Some(Command::Search { query, limit }) = &args.command {
+        let path = config.index.as_deref()
+            .context("No index to search, set `index` in the configuration file or pass \
+                --index")?;
+        return index::search(path, query, *limit, args.format);
+    }
+    for i in config.paths.iter() {
+        log::debug!("rule {}", i);
+    }
+    let listen = match &args.command {
+        Some(Command::Serve { listen }) => Some(listen.clone()),
+        _ => None
+    };
+    let mail = matches!(args.command, Some(Command::Mail));
+    let correct = matches!(args.command, Some(Command::Correct { .. }));
+    if args.input.is_empty() && listen.is_none() && !mail && !correct {
+        bail!("No input directory given");
+    }
+    let pid_file = match &args.command {
+        Some(Command::Daemon { pid_file, .. }) => Some(match pid_file {
+            Some(path) => paths::expand(path)?,
+            None => daemon::default_path()?
+        }),
+        _ => None
+    };
+    let watch = args.watch || pid_file.is_some();
+    if !args.apply && pid_file.is_none() && listen.is_none() && !correct && !args.review {
+        args.dry_run = true;
+    }
+    let inputs = args.input.clone();
+    let stdin = std::path::Path::new("-");
+    if watch && inputs.iter().any(|input| input == stdin) {
+        bail!("Files cannot be read from stdin in watch mode");
+    }
+    let output = args.output.clone().context("No output directory given")?;
+    let remote = remote::Remote::parse(&output)?;
+    let output = remote::without_credentials(&output);
+    if remote.is_some() {
+        if matches!(args.action, place::Action::Symlink | place::Action::Hardlink
+            | place::Action::Tag) {
+            bail!("Files cannot be filed with --action {} into a remote output, use move \
+                or copy", args.action.verb());
+        }
+        // Replaced remote files could not be restored by --undo.
+        if args.on_conflict == place::ConflictPolicy::Overwrite {
+            bail!("Files in a remote output cannot be overwritten, use --on-conflict skip, \
+                rename or error");
+        }
+        let indexed = config.index.is_some()
+            && args.on_duplicate != place::DuplicatePolicy::Link;
+        if args.on_duplicate != place::DuplicatePolicy::KeepBoth && !indexed {
+            bail!("Duplicates in a remote output are only found in the index, set `index` \
+                and use --on-duplicate skip or report, or use keep-both");
+        }
+    }
+    let journal = journal::Journal::new(&output)?;
+    // Dry runs file nothing, so they need not wait for other runs.
+    let locked = (!args.force && !args.dry_run).then(|| output.clone());
+    let audit_log = match config.audit_log.clone() {
+        Some(path) => path,
+        None => ledger::default_path()?
+    };
+    let ledger = ledger::Ledger::new(audit_log, journal.run_id());
+    let index = config.index.as_deref()
+        .map(|path| index::Index::open(path, journal.run_id()))
+        .transpose()?;
+    let mut duplicates = duplicate::Index::new(output.clone());
+    if let Some(path) = config.index.as_deref()
+        .filter(|_| args.on_duplicate != place::DuplicatePolicy::KeepBoth) {
+        duplicates = duplicates.with_index(path)?;
+    }
+    let placer = place::Placer {
+        duplicates,
+        output,
+        action: args.action,
+        preserve: !args.no_preserve,
+        on_conflict: args.on_conflict,
+        on_duplicate: args.on_duplicate,
+        hooks: (!args.no_hooks)
+            .then(|| hook::Runner::new(std::time::Duration::from_secs(args.hook_timeout))),
+        journal: Some(journal),
+        remote,
+        slugify: args.slugify
+    };
+    let create_dirs = !args.no_create_dirs && !args.dry_run;
+    let holding = prepare(&placer, &config, create_dirs)?;
+    let processed = if args.incremental || pid_file.is_some() {
+        Some(RefCell::new(processed::Processed::load(processed::default_path()?)?))
+    } else {
+        None
+    };
+    let limits = extract::FileLimits {
+        max_text_bytes: args.max_text_bytes.map(|size| size.0 as usize),
+        timeout: args.extract_timeout.map(std::time::Duration::from_secs),
+        stop: args.stop_at_match.then(|| stop_at_match(&config))
+    };
+    let mut run = Run {
+        config: Rc::new(config),
+        inputs: inputs.iter().filter(|input| *input != stdin).cloned().collect(),
+        overrides: RefCell::new(HashMap::new()),
+        policy: args.match_policy,
+        placer,
+        ledger,
+        index,
+        dry_run: args.dry_run,
+        preview: args.preview,
+        format: args.format,
+        explain: args.explain,
+        quiet: args.quiet,
+        cache,
+        processed,
+        notifications: Default::default(),
+        metrics: None,
+        limits,
+        holding,
+        locked,
+        create_dirs
+    };
+
+    if let Some(listen) = listen {
+        run.metrics = Some(Arc::default());
+        return serve::serve(&run, &listen);
+    }
+    if let Some(Command::Correct { file, category }) = &args.command {
+        let model = match &args.model {
+            Some(path) => path.clone(),
+            None => model::default_path()?
+        };
+        let _lock = run.lock(args.wait)?;
+        return corrections::correct(&run, &paths::expand(file)?, category, &model);
+    }
+    if mail {
+        let account = run.config.imap.clone()
+            .context("No mailbox to fetch, set `imap` in the configuration file")?;
+        let lock = run.lock(args.wait)?;
+        let summary = mail::ingest(&run, &account)?;
+        run.save()?;
+        drop(lock);
+        run.post_summary();
+        if !args.quiet {
+            summary.print(args.format);
+        }
+        if let Some(status) = args.exit_status(&summary, 0) {
+            std::process::exit(status);
+        }
+        return Ok(());
+    }
+    let jobs = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
     });
-    for file in files
-    {
-        classify(&file, &config);
+    let limits = extract::Limits {
+        queue: args.queue.unwrap_or(jobs * 4).max(1),
+        buffered: args.max_buffered.saturating_mul(1024 * 1024)
+    };
+    if watch {
+        let _pid_file = pid_file.as_deref().map(daemon::PidFile::acquire).transpose()?;
+        if let Some(Command::Daemon { metrics: Some(listen), .. }) = &args.command {
+            let metrics = Arc::new(metrics::Metrics::default());
+            metrics::serve(metrics.clone(), listen)?;
+            run.metrics = Some(metrics);
+        }
+        let poll = !matches!(args.command, Some(Command::Daemon { no_watch: true, .. }));
+        if !poll && run.config.schedule.is_none() {
+            bail!("--no-watch requires a 'schedule' in the configuration file");
+        }
+        let workers = watch::Workers { jobs, limits };
+        return watch::watch(&mut run, &inputs,
+            std::time::Duration::from_secs(args.interval), workers, poll, &config_path, load);
+    }
+
+    let _lock = run.lock(args.wait)?;
+    // Files listed on stdin, walked in the place of "-".
+    let listed = if inputs.iter().any(|input| input == stdin) {
+        read_files(&run.config.filter)?.into_iter().map(walkdir::DirEntry::into_path).collect()
+    } else {
+        Vec::new()
+    };
+    // Files of overlapping inputs are only classified once.
+    let seen = RefCell::new(std::collections::HashSet::new());
+    let is_new = |path: &std::path::Path| {
+        (inputs.len() < 2 || seen.borrow_mut().insert(path.to_path_buf())) && !run.is_done(path)
+    };
+    let pool;
+    let mut total = None;
+    let work: Box<dyn Iterator<Item = (PathBuf, Work)>> = if args.review {
+        let files: Vec<PathBuf> = run.held().into_iter().map(|(path, _)| path)
+            .chain(walk(inputs.clone(), listed, run.config.filter.clone())
+                .into_iter().filter(|path| is_new(path)))
+            .collect();
+        let mut extractor = extract::Extractor::new(run.cache.clone())
+            .with_chains(&run.config.extraction)
+            .with_limits(run.limits.clone());
+        let mut classified = Vec::new();
+        let mut reviewed = Vec::new();
+        for file in files {
+            let extracted = match extractor.extract(&file) {
+                Ok(extracted) => extracted,
+                Err(error) => {
+                    reviewed.push((file, Some(Work::Extracted(Err(error)))));
+                    continue;
+                }
+            };
+            let config = run.config_for(&file)?;
+            let result = classify(&file, &extracted, &config, run.policy);
+            classified.push((result, config.prepare(&extracted.text).into_owned()));
+            reviewed.push((file, None));
+        }
+        let mut approved = match review::review(&run, classified)? {
+            Some(approved) => approved.into_iter(),
+            None => return Ok(())
+        };
+        let work: Vec<(PathBuf, Work)> = reviewed.into_iter()
+            .filter_map(|(file, unreadable)| match unreadable {
+                Some(work) => Some((file, work)),
+                None => approved.next().flatten().map(|result| (file, Work::Reviewed(result)))
+            })
+            .collect();
+        total = Some(work.len());
+        Box::new(work.into_iter())
+    } else {
+        pool = extract::Pool::new(jobs, &run.config.extraction, run.cache.clone(),
+            run.metrics.clone(), &run.limits);
+        let paths = walk(inputs.clone(), listed, run.config.filter.clone());
+        // Held files are classified again from the text extracted when they
+        // were parked.
+        let held = run.held().into_iter()
+            .map(|(path, extracted)| (path, Work::Extracted(Ok(extracted))));
+        Box::new(held.chain(pool.stream(paths.into_iter().filter(|path| is_new(path)), limits)
+            .map(|(path, extracted)| (path, Work::Extracted(extracted)))))
+    };
+    let mut progress = progress::Progress::new(total);
+    // Only kept for what needs every file once the run is over.
+    let keep = args.report.is_some() || args.report_html.is_some()
+        || args.audit_sample.is_some() || args.preview == preview::Preview::Tree;
+    let mut results = Vec::new();
+    let mut summary = output::Summary::default();
+    let mut errors = Vec::new();
+    for (file, work) in work {
+        let outcome = match work {
+            Work::Extracted(extracted) => progress.suspend(|| run.process(&file, extracted)),
+            Work::Reviewed(result) => progress.suspend(|| run.apply(result, None))
+        };
+        match outcome {
+            Ok(result) => {
+                progress.file_done(&file, output::status(&result));
+                summary.add(&result);
+                if keep {
+                    results.push(result);
+                }
+            }
+            Err(error) => {
+                progress.suspend(|| log::error!("{:?}: {:#}", file, error));
+                progress.file_done(&file, "error");
+                summary.errors += 1;
+                errors.push((file, format!("{:#}", error)));
+            }
+        }
+    }
+    progress.finish();
+    if args.dry_run && args.preview == preview::Preview::Tree {
+        preview::print_tree(&run, &results)?;
+    }
+    run.save()?;
+    run.post_summary();
+    if let Some(holding) = &run.holding {
+        holding.report_overdue();
+    }
+    if !args.quiet {
+        summary.print(args.format);
+    }
+
+    if let Some(path) = &args.report {
+        report::write(path, &results, args.dry_run)?;
+    }
+    if let Some(path) = &args.report_html {
+        report::write_html(path, &results, &errors, &summary, args.dry_run)?;
+    }
+    if let Some(sample) = args.audit_sample {
+        audit::review(&results, sample, args.format)?;
+    }
+    let failed_hooks = run.placer.hooks.as_ref().map_or(0, hook::Runner::failures);
+    if failed_hooks > 0 {
+        log::error!("{} hooks failed", failed_hooks);
+    }
+    if let Some(status) = args.exit_status(&summary, failed_hooks) {
+        std::process::exit(status);
     }
     Ok(())
 }
 
-fn is_pdf(file: &walkdir::DirEntry) -> bool {
-    file.path().extension().unwrap().to_str().unwrap() == "pdf"
+/// Create the directories of `config` in the output tree with `create`,
+/// or check that they could be, and open its holding directory.
+fn prepare(placer: &place::Placer, config: &Config, create: bool)
+    -> anyhow::Result<Option<holding::Holding>> {
+    if placer.action != place::Action::Tag {
+        let others: Vec<&std::path::Path> = [&config.unmatched_dir, &config.holding_dir,
+            &config.ambiguous_dir, &config.errors_dir].into_iter().flatten()
+            .map(PathBuf::as_path).collect();
+        placer.prepare(&config.paths, &others, create)?;
+    }
+    Ok(match &config.holding_dir {
+        Some(_) if placer.action == place::Action::Tag => None,
+        Some(_) if placer.remote.is_some() =>
+            bail!("Files cannot be held in a remote output, unset the holding directory"),
+        Some(_) if placer.action != place::Action::Move =>
+            bail!("Files are moved in and out of the holding directory, use --action move"),
+        Some(dir) =>
+            Some(holding::Holding::open(placer.output.join(dir), config.holding_retention)?),
+        None => None
+    })
 }
 
-fn classify(file: &walkdir::DirEntry, config: &ClassifierPaths) {
-    if is_pdf(file) {
-        classify_pdf(&file, &config);
+/// Put a classified file where `config` says it belongs and return the
+/// resulting paths. Nothing is returned if the file stays in place, such
+/// as unmatched files without `unmatched_dir`. Unmatched files already in
+/// the holding directory stay there.
+fn file_away(result: &Classification, config: &Config, placer: &place::Placer)
+    -> anyhow::Result<Vec<PathBuf>> {
+    if placer.action == place::Action::Tag {
+        if result.matches.is_empty() {
+            return Ok(Vec::new());
+        }
+        let date = result.date.or_else(|| date::modified(&result.source));
+        return Ok(placer.tag(&result.source, &result.matches, date)?.into_iter().collect());
+    }
+    if result.matches.is_empty() {
+        return match config.set_aside_dir() {
+            Some(dir) if config.holding_dir.is_some()
+                && result.source.parent() == Some(&placer.output.join(dir)) =>
+                Ok(vec![result.source.clone()]),
+            Some(dir) => Ok(placer.set_aside(&result.source, dir)?.into_iter().collect()),
+            None => Ok(Vec::new())
+        };
+    }
+    if let Some(dir) = config.ambiguous_dir.as_ref().filter(|_| result.ambiguous) {
+        return Ok(placer.quarantine(&result.source, dir, &result.matches)?
+            .into_iter().collect());
     }
+    // A moved file can only land in one place.
+    let rules = if placer.action == place::Action::Move {
+        &result.matches[..1]
+    } else {
+        &result.matches[..]
+    };
+    let mut destinations = Vec::new();
+    for rule in rules {
+        destinations.extend(placer.place(&rename::Fields {
+            source: &result.source,
+            rule,
+            title: result.title.as_deref(),
+            date: result.date.or_else(|| date::modified(&result.source))
+        })?);
+    }
+    Ok(destinations)
 }
 
-fn classify_pdf(file: &walkdir::DirEntry, config: &ClassifierPaths) {
-    let doc = poppler::PopplerDocument::new_from_file(
-        file.path(),
-        std::path::Path::new("").to_str().unwrap()).unwrap();
-    let page = doc.get_page(0).unwrap();
-    let text = page.get_text().unwrap();
-    let matches: Vec<&ClassifierPath> =
-        config.iter().filter(|path| path.matches(text)).collect();
-    if !matches.is_empty() {
-        println!(" src: {}", file.path().to_str().unwrap());
-        for m in matches.iter() {
-            println!("dest: {:?} using keywords: {:?}", m.path, m.keywords);
+/// Directories, relative to the output directory, that a file with the
+/// given matches and document date is filed into.
+fn destinations(config: &Config, matches: &[&ClassifierPath], ambiguous: bool,
+    date: Option<chrono::NaiveDate>) -> anyhow::Result<Vec<PathBuf>> {
+    if matches.is_empty() {
+        return Ok(config.set_aside_dir().into_iter().map(std::path::Path::to_path_buf)
+            .collect());
+    }
+    match &config.ambiguous_dir {
+        Some(dir) if ambiguous => Ok(vec![dir.clone()]),
+        _ => matches.iter().map(|m| date::expand(&m.path, date)).collect()
+    }
+}
+
+fn match_text(config: &Config, policy: MatchPolicy) -> anyhow::Result<()> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)
+        .context("Failed to read text from stdin")?;
+    let (matches, ambiguous, _) = choose(config, &text, None, policy);
+    let destinations = destinations(config, &matches, ambiguous,
+        date::extract(&text, None, &config.date_order))?;
+    if destinations.is_empty() {
+        std::process::exit(1);
+    }
+    for destination in destinations {
+        println!("{}", destination.display());
+    }
+    Ok(())
+}
+
+fn query(file: &std::path::Path, config: &Config, policy: MatchPolicy,
+    action: place::Action, output: Option<&std::path::Path>, slugify: bool,
+    cache: Option<cache::Cache>) -> anyhow::Result<()> {
+    if !file.is_file() {
+        bail!("{:?} is not a file", file);
+    }
+    let extracted = extract::Extractor::new(cache)
+        .with_chains(&config.extraction)
+        .extract(file)?;
+    let result = classify(file, &extracted, config, policy);
+    if result.matches.is_empty() {
+        std::process::exit(1);
+    }
+    let date = result.date.or_else(|| date::modified(file));
+    // Tagged files stay in place, only their categories are recorded.
+    if action == place::Action::Tag {
+        for rule in result.matches.iter() {
+            println!("{}", date::expand(&rule.path, date)?.display());
         }
-        println!("");
+        return Ok(());
     }
+    let output = output.unwrap_or(std::path::Path::new(""));
+    let file_name = file.file_name().context("No file name")?;
+    if let Some(dir) = config.ambiguous_dir.as_ref().filter(|_| result.ambiguous) {
+        println!("{}", output.join(dir).join(file_name).display());
+        return Ok(());
+    }
+    // Like `file_away`, a moved file only lands in one place.
+    let rules = if action == place::Action::Move {
+        &result.matches[..1]
+    } else {
+        &result.matches[..]
+    };
+    for rule in rules {
+        let dir = output.join(date::expand(&rule.path, date)?);
+        let slugify = rule.slugify.unwrap_or(slugify);
+        let destination = match &rule.rename {
+            Some(template) => {
+                let name = rename::render(template, &rename::Fields {
+                    source: file,
+                    rule,
+                    title: result.title.as_deref(),
+                    date
+                }, &dir, slugify)?;
+                dir.join(rename::unique(&dir, &name))
+            }
+            None if slugify => dir.join(rename::slugify(&file_name.to_string_lossy())),
+            None => dir.join(file_name)
+        };
+        println!("{}", destination.display());
+    }
+    Ok(())
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "ddc")
 }
 
-fn config_to_str(path: &std::path::PathBuf) -> anyhow::Result<String> {
-    return std::fs::read_to_string(&path).with_context(|| {
-        format!("Failed to read configuration file '{}'",
-            path.to_str().unwrap())
+/// Paths walked ahead of their extraction in a batch run.
+const WALK_BUFFER: usize = 1024;
+
+/// Paths of the files of `inputs`, walked by a thread of their own as they
+/// are received. `listed` are the files given on stdin, taking the place
+/// of "-".
+fn walk(inputs: Vec<PathBuf>, listed: Vec<PathBuf>, filter: filter::Filter)
+    -> std::sync::mpsc::Receiver<PathBuf> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(WALK_BUFFER);
+    std::thread::spawn(move || {
+        let mut listed = Some(listed);
+        for input in inputs.iter() {
+            let files: Box<dyn Iterator<Item = PathBuf>> = if input.as_os_str() == "-" {
+                Box::new(listed.take().unwrap_or_default().into_iter())
+            } else {
+                Box::new(walk_files(input, &filter).map(walkdir::DirEntry::into_path))
+            };
+            for file in files {
+                if sender.send(file).is_err() {
+                    return;
+                }
+            }
+        }
     });
+    receiver
 }
 
-fn print_config(path: &std::path::PathBuf) -> anyhow::Result<()> {
-    println!("{}", config_to_str(&path)?);
-    Ok(())
+/// Files below `input` with a supported extension, as they are walked.
+fn walk_files<'a>(input: &'a std::path::Path, filter: &'a filter::Filter)
+    -> impl Iterator<Item = walkdir::DirEntry> + 'a {
+    let relative = move |e: &walkdir::DirEntry| e.path().strip_prefix(input)
+        .unwrap_or(e.path()).to_path_buf();
+    let mut walk = WalkDir::new(input).follow_links(filter.follow_symlinks);
+    if let Some(depth) = filter.max_depth {
+        walk = walk.max_depth(depth);
+    }
+    walk.into_iter()
+        .filter_entry(move |e| e.depth() == 0 || !filter.excludes(&relative(e)))
+        .filter_map(Result::ok)
+        .filter(move |e| {
+            e.file_type().is_file()
+                && filter.includes(&relative(e))
+                && e.metadata().is_ok_and(|metadata| filter.accepts(&metadata))
+                && is_document(e.path(), filter)
+    })
 }
 
-fn parse_config(path: &std::path::PathBuf) -> anyhow::Result<ClassifierPaths> {
-    let config = config_to_str(&path)?;
-    let config = YamlLoader::load_from_str(&config)
-        .with_context(|| {
-            format!("Failed to parse configuration file '{}'",
-                path.to_str().unwrap())
-        })?;
-    let root = config.first().with_context(|| {
-        "No root element found"
-    })?;
-    let config = root.as_vec().context(
-        "Unexpected configuration file format"
-    )?;
-
-    let config = parse_layout(config)?;
-    for i in config.iter() {
-        println!("{}", i);
-    }
-    Ok(config)
-}
-
-fn parse_layout(layout: &yaml::Array) -> anyhow::Result<ClassifierPaths> {
-    let dir_key = yaml::Yaml::from_str("dir");
-    let sub_key = yaml::Yaml::from_str("sub");
-    let keywords_key = yaml::Yaml::from_str("keywords");
-    let mut paths: ClassifierPaths = Default::default();
-
-    for dir in layout.iter() {
-        let dir_params = dir.as_hash().context(
-            "Unexpected configuration file format. Expected a hash map."
-        )?;
-        let dir_name = dir_params.get(&dir_key).context(
-            format!("No '{}' key found !", dir_key.as_str().unwrap())
-        )?;
-        let mut path: ClassifierPath = Default::default();
-        path.path = std::path::PathBuf::from(dir_name.as_str().unwrap());
-        if let Some(keywords) = dir_params.get(&keywords_key) {
-            let keywords = keywords.as_vec().context(
-                format!("Unexpected keywords format for directory {:?}", path.path)
-            )?;
-            path.keywords = keywords.into_iter().map(|yaml| {
-                yaml.as_str().unwrap().to_string()
-            }) .collect();
-        }
-        let new_path = ClassifierPath{
-            path: path.path.clone(),
-            keywords: path.keywords.clone()
-        };
-        paths.push(new_path);
-        if !dir_params.contains_key(&sub_key) {
+/// Files listed on stdin, one per line or separated by NUL characters.
+/// Only the size and time limits of `filter` apply to them.
+fn read_files(filter: &filter::Filter) -> anyhow::Result<Vec<walkdir::DirEntry>> {
+    let mut list = Vec::new();
+    std::io::stdin().read_to_end(&mut list)
+        .context("Failed to read the list of files from stdin")?;
+    let separator = if list.contains(&b'\0') { b'\0' } else { b'\n' };
+    let mut files = Vec::new();
+    for path in list.split(|byte| *byte == separator) {
+        let path = path.strip_suffix(b"\r").unwrap_or(path);
+        if path.is_empty() {
             continue;
         }
-        let sub_dirs = dir_params[&sub_key].as_vec().with_context(||
-            format!("'{}' element should be a list of directories",
-                sub_key.as_str().unwrap())
-        )?;
-        let mut sub = parse_layout(sub_dirs)?;
-        for it in sub.iter_mut() {
-            let mut clone = path.path.clone();
-            clone.push(it.path.clone());
-            it.path = clone.clone();
-            it.keywords.extend(path.keywords.clone());
+        let path = paths::from_bytes(path);
+        match WalkDir::new(&path).follow_links(true).into_iter().next() {
+            Some(Ok(file)) if file.file_type().is_file() => {
+                if file.metadata().is_ok_and(|metadata| filter.accepts(&metadata))
+                    && is_document(file.path(), filter) {
+                    files.push(file);
+                }
+            }
+            Some(Err(error)) => log::warn!("Skipping {:?}: {}", path, error),
+            _ => log::warn!("Skipping {:?}: not a file", path)
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `path` has one of the extensions of `filter`, or has no
+/// extension and, with `sniff`, starts like a PDF.
+fn is_document(path: &std::path::Path, filter: &filter::Filter) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            filter.extensions.contains(&extension.to_string_lossy().to_lowercase())
+        }
+        None => filter.sniff && starts_like_pdf(path)
+    }
+}
+
+/// Whether the PDF header is in the first kilobyte of `path`, where
+/// readers look for it.
+fn starts_like_pdf(path: &std::path::Path) -> bool {
+    let mut start = Vec::new();
+    let read = std::fs::File::open(path)
+        .and_then(|file| file.take(1024).read_to_end(&mut start));
+    read.is_ok() && start.windows(5).any(|window| window == b"%PDF-")
+}
+
+/// Resolve the rules matching `text` according to `policy`.
+fn resolve<'a>(config: &'a ClassifierPaths, doc: &Document, policy: MatchPolicy)
+    -> Vec<&'a ClassifierPath> {
+    match policy {
+        MatchPolicy::First => {
+            config.iter().find(|path| path.matches(doc)).into_iter().collect()
+        }
+        MatchPolicy::Best => {
+            let mut best: Option<&ClassifierPath> = None;
+            let mut best_score = 0;
+            for path in config.iter() {
+                let score = path.score(doc);
+                if score > best_score {
+                    best = Some(path);
+                    best_score = score;
+                }
+            }
+            best.into_iter().collect()
+        }
+        MatchPolicy::All => {
+            config.iter().filter(|path| path.matches(doc)).collect()
+        }
+    }
+}
+
+/// Matching rules sharing the highest score, when there are several.
+fn competing<'a>(config: &'a ClassifierPaths, doc: &Document)
+    -> Vec<&'a ClassifierPath> {
+    let scored: Vec<(&ClassifierPath, usize)> = config.iter()
+        .map(|path| (path, path.score(doc)))
+        .filter(|(_, score)| *score > 0)
+        .collect();
+    let best = scored.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    let tied: Vec<&ClassifierPath> = scored.into_iter()
+        .filter(|(_, score)| *score == best)
+        .map(|(path, _)| path)
+        .collect();
+    if tied.len() > 1 { tied } else { Vec::new() }
+}
+
+/// Stop condition of `--stop-at-match`: a rule of `config` matches the
+/// pages extracted so far.
+fn stop_at_match(config: &Config) -> extract::Stop {
+    let rules = config.paths.clone();
+    let fold = config.fold_diacritics;
+    let uses_doctype = config.uses_doctype();
+    Arc::new(move |source, text| {
+        let text = if fold { normalize::fold(text) } else { text.to_string() };
+        let doc = Document {
+            text: &text,
+            doctype: if uses_doctype { doctype::detect(&text) } else { None },
+            file_type: source.extension().map(|ext| ext.to_string_lossy().to_lowercase()),
+            words: Default::default()
+        };
+        rules.iter().any(|rule| rule.matches(&doc))
+    })
+}
+
+/// Rules chosen for `text`, extracted from `source` if given, whether
+/// competing rules matched it, and the confidence of the choice. With an
+/// ambiguous directory, the competing rules are chosen so that the file is
+/// sent there, otherwise the rules are chosen as usual.
+fn choose<'a>(config: &'a Config, text: &str, source: Option<&std::path::Path>,
+    policy: MatchPolicy) -> (Vec<&'a ClassifierPath>, bool, Option<f64>) {
+    let text = config.prepare(text);
+    let doc = Document::new(config, &text, source);
+    let candidates = if policy != MatchPolicy::All {
+        competing(&config.paths, &doc)
+    } else {
+        Vec::new()
+    };
+    let ambiguous = !candidates.is_empty();
+    if ambiguous && config.ambiguous_dir.is_some() {
+        let confidence = confidence::of(candidates[0], &config.paths, &doc);
+        return (candidates, true, Some(confidence));
+    }
+    let matches = resolve(&config.paths, &doc, policy);
+    let fallback = || config.model.as_ref().and_then(|model| model.classify(&text))
+        .or_else(|| config.similar.as_ref().and_then(|similar| similar.classify(&text)));
+    let (matches, confidence) = match matches.first() {
+        Some(rule) => (matches.clone(), confidence::of(rule, &config.paths, &doc)),
+        None => match fallback() {
+            Some((rule, confidence)) => (vec![rule], confidence),
+            None => return (matches, false, None)
+        }
+    };
+    if config.min_confidence.is_some_and(|min| confidence < min) {
+        return (Vec::new(), false, Some(confidence));
+    }
+    (matches, ambiguous, Some(confidence))
+}
+
+fn classify(source: &std::path::Path, extracted: &extract::Extracted,
+    config: &Config, policy: MatchPolicy) -> Classification {
+    let text = &extracted.text;
+    let (matches, ambiguous, confidence) = choose(config, text, Some(source), policy);
+    if matches.is_empty() {
+        if let Some(confidence) = confidence {
+            log::info!("{:?}: confidence {:.2} is below --min-confidence", source, confidence);
         }
-        paths.extend(sub);
+        return Classification {
+            source: source.to_path_buf(),
+            matches: Vec::new(),
+            ambiguous: false,
+            confidence,
+            title: None,
+            date: None,
+            text: text.clone(),
+            destinations: Vec::new()
+        };
+    }
+    Classification {
+        source: source.to_path_buf(),
+        matches: matches.into_iter().cloned().collect(),
+        ambiguous,
+        confidence,
+        title: extracted.title.clone(),
+        date: date::extract(text, extracted.metadata.as_deref(), &config.date_order),
+        text: text.clone(),
+        destinations: Vec::new()
+    }
+}
+
+fn train(dir: &std::path::Path, path: Option<PathBuf>, no_cache: bool)
+    -> anyhow::Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => model::default_path()?
+    };
+    let cache = if no_cache { None } else { Some(cache::Cache::open()?) };
+    let mut model = model::train(dir, &mut extract::Extractor::new(cache))?;
+    let corrections = corrections::load()?;
+    if !corrections.is_empty() {
+        log::info!("Learning {} corrections", corrections.len());
+    }
+    for correction in corrections {
+        model.add(correction.to, correction.tokens);
+    }
+    model.save(&path)?;
+    log::info!("Learned {} into {:?}", model.summary(), path);
+    Ok(())
+}
+
+fn print_config(path: &std::path::Path) -> anyhow::Result<()> {
+    println!("{}", settings::to_str(path)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Configuration with the rules of the YAML `layout`, which must all
+    /// be valid.
+    fn config(layout: &str) -> Config {
+        let settings = settings::parse(layout, settings::Format::Yaml).unwrap();
+        let sets = settings::keyword_sets(&settings.keyword_sets).unwrap();
+        let paths = settings::rules(&settings.layout, false, &sets,
+            &mut |path, error| panic!("{:?}: {:#}", path, error));
+        Config { paths, ..Default::default() }
+    }
+
+    /// Directories `choose` picks for `text`, and whether they compete.
+    fn chosen(config: &Config, text: &str, policy: MatchPolicy) -> (Vec<PathBuf>, bool) {
+        let (matches, ambiguous, _) = choose(config, text, None, policy);
+        (matches.into_iter().map(|rule| rule.path.clone()).collect(), ambiguous)
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        let config = config("layout:\n  - dir: Acme\n    keywords: [acme]\n");
+        assert_eq!(chosen(&config, "Invoice from ACME and acme", MatchPolicy::First).0,
+            [PathBuf::from("Acme")]);
+        assert!(chosen(&config, "Invoice from acmeco", MatchPolicy::First).0.is_empty());
+        assert!(chosen(&config, "Invoice from Acme", MatchPolicy::First).0.is_empty());
+    }
+
+    #[test]
+    fn applies_keyword_options() {
+        let config = config("layout:\n  - dir: Acme\n    keywords:\n      \
+            - { word: acme, case: insensitive, whole_word: false }\n");
+        assert_eq!(chosen(&config, "Invoice from ACMECO", MatchPolicy::First).0,
+            [PathBuf::from("Acme")]);
+    }
+
+    #[test]
+    fn reports_invalid_patterns() {
+        let settings = settings::parse("layout:\n  - dir: Drafts\n    keywords: ['(draft']\n",
+            settings::Format::Yaml).unwrap();
+        let mut invalid = Vec::new();
+        let paths = settings::rules(&settings.layout, false, &KeywordSets::new(),
+            &mut |path, error| invalid.push((path.to_path_buf(), format!("{:#}", error))));
+        assert!(paths.is_empty());
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].0, PathBuf::from("Drafts"));
+        assert!(invalid[0].1.contains("not a valid pattern"), "{}", invalid[0].1);
+
+        let config = config("layout:\n  - dir: Drafts\n    keywords:\n      \
+            - { word: '\\(draft\\)', whole_word: false }\n");
+        assert_eq!(chosen(&config, "Report (draft)", MatchPolicy::First).0,
+            [PathBuf::from("Drafts")]);
+        assert!(chosen(&config, "Report draft", MatchPolicy::First).0.is_empty());
+    }
+
+    #[test]
+    fn needs_min_matches_keywords() {
+        let config = config("layout:\n  - dir: Bank\n    keywords: [statement, iban, bic]\n    \
+            min_matches: 2\n");
+        assert!(chosen(&config, "statement", MatchPolicy::First).0.is_empty());
+        assert_eq!(chosen(&config, "statement with iban", MatchPolicy::First).0,
+            [PathBuf::from("Bank")]);
+    }
+
+    #[test]
+    fn subdirectories_inherit_keywords() {
+        let config = config("layout:\n  - dir: Acme\n    keywords: [acme]\n    sub:\n      \
+            - dir: Invoices\n        keywords: [invoice]\n");
+        assert_eq!(chosen(&config, "acme invoice", MatchPolicy::Best).0,
+            [PathBuf::from("Acme/Invoices")]);
+        assert_eq!(chosen(&config, "acme letter", MatchPolicy::Best).0,
+            [PathBuf::from("Acme")]);
+        assert_eq!(chosen(&config, "acme invoice", MatchPolicy::All).0,
+            [PathBuf::from("Acme"), PathBuf::from("Acme/Invoices")]);
+    }
+
+    #[test]
+    fn detects_competing_rules_without_an_ambiguous_directory() {
+        let layout = "layout:\n  - dir: Bank\n    keywords: [statement]\n  \
+            - dir: Tax\n    keywords: [statement]\n  - dir: Other\n    keywords: [letter]\n";
+        let mut config = config(layout);
+        assert_eq!(chosen(&config, "a statement", MatchPolicy::Best),
+            (vec![PathBuf::from("Bank")], true));
+        assert_eq!(chosen(&config, "a letter", MatchPolicy::Best),
+            (vec![PathBuf::from("Other")], false));
+        // Keeping every match is never ambiguous.
+        assert!(!chosen(&config, "a statement", MatchPolicy::All).1);
+
+        config.ambiguous_dir = Some(PathBuf::from("Review"));
+        assert_eq!(chosen(&config, "a statement", MatchPolicy::Best),
+            (vec![PathBuf::from("Bank"), PathBuf::from("Tax")], true));
     }
-    Ok(paths)
 }
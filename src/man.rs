@@ -0,0 +1,44 @@
+//! Man pages generated from the command line definition.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+/// Render the page of `command` to `out`.
+fn render(command: clap::Command, out: &mut dyn std::io::Write) -> anyhow::Result<()> {
+    clap_mangen::Man::new(command).render(out).context("Failed to render the man page")
+}
+
+/// Print the page of `command`, or write it and the pages of its
+/// subcommands, named like `classy-undo.1`, to `dir`.
+pub fn generate(command: clap::Command, dir: Option<&Path>) -> anyhow::Result<()> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => return render(command, &mut std::io::stdout())
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let name = command.get_name().to_string();
+    let version = command.get_version();
+    let mut command = command;
+    command.build();
+    let subcommands = command.get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set() && subcommand.get_name() != "help");
+    for subcommand in subcommands {
+        let page_name = format!("{}-{}", name, subcommand.get_name());
+        let path = dir.join(format!("{}.1", page_name));
+        let mut file = std::fs::File::create(&path)
+            .with_context(|| format!("Failed to create {:?}", path))?;
+        let mut page = subcommand.clone().name(page_name);
+        if let Some(version) = version {
+            page = page.version(version);
+        }
+        render(page, &mut file)?;
+        println!("{}", path.display());
+    }
+    let path = dir.join(format!("{}.1", name));
+    let mut file = std::fs::File::create(&path)
+        .with_context(|| format!("Failed to create {:?}", path))?;
+    render(command, &mut file)?;
+    println!("{}", path.display());
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Prometheus metrics of the daemon and the HTTP API.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+
+use crate::Classification;
+
+/// Upper bounds of the buckets of the extraction time histogram, in
+/// seconds.
+const BUCKETS: [f64; 10] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+pub struct Metrics {
+    processed: AtomicU64,
+    matched: AtomicU64,
+    unmatched: AtomicU64,
+    ambiguous: AtomicU64,
+    errors: AtomicU64,
+    /// Extractions that took at most each bound of `BUCKETS`.
+    extraction_buckets: [AtomicU64; BUCKETS.len()],
+    extraction_count: AtomicU64,
+    extraction_micros: AtomicU64
+}
+
+impl Metrics {
+    /// Count a processed file, classified as `result` or failed if `None`.
+    pub fn file_done(&self, result: Option<&Classification>) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+        let counter = match result.map(crate::output::status) {
+            None => &self.errors,
+            Some("unmatched") => &self.unmatched,
+            Some("ambiguous") => &self.ambiguous,
+            Some(_) => &self.matched
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the time taken to extract the text of a document.
+    pub fn extracted(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, bucket) in BUCKETS.iter().zip(self.extraction_buckets.iter()) {
+            if seconds <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.extraction_count.fetch_add(1, Ordering::Relaxed);
+        self.extraction_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            ("classy_files_processed_total", "Files processed.", &self.processed),
+            ("classy_files_matched_total", "Files classified by the rules.", &self.matched),
+            ("classy_files_unmatched_total", "Files no rule matched.", &self.unmatched),
+            ("classy_files_ambiguous_total", "Files matched by competing rules.",
+                &self.ambiguous),
+            ("classy_errors_total", "Files that failed to be filed.", &self.errors)
+        ];
+        for (name, help, counter) in counters {
+            text.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n{} {}\n",
+                name, help, name, name, counter.load(Ordering::Relaxed)));
+        }
+        let name = "classy_extraction_seconds";
+        text.push_str(&format!("# HELP {} Time taken to extract the text of a document.\n\
+            # TYPE {} histogram\n", name, name));
+        for (bound, bucket) in BUCKETS.iter().zip(self.extraction_buckets.iter()) {
+            text.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n",
+                name, bound, bucket.load(Ordering::Relaxed)));
+        }
+        let count = self.extraction_count.load(Ordering::Relaxed);
+        text.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", name, count));
+        text.push_str(&format!("{}_sum {}\n", name,
+            self.extraction_micros.load(Ordering::Relaxed) as f64 / 1e6));
+        text.push_str(&format!("{}_count {}\n", name, count));
+        text
+    }
+}
+
+/// Response to a scrape of `metrics`.
+pub fn response(metrics: &Metrics) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+    let header = tiny_http::Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+        .expect("valid header");
+    tiny_http::Response::from_string(metrics.render()).with_header(header)
+}
+
+/// Answer `GET /metrics` on `listen` from a background thread.
+pub fn serve(metrics: Arc<Metrics>, listen: &str) -> anyhow::Result<()> {
+    let server = tiny_http::Server::http(listen)
+        .map_err(|error| anyhow!("Failed to listen on {}: {}", listen, error))?;
+    log::info!("Serving metrics on http://{}/metrics", listen);
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                response(&metrics)
+            } else {
+                tiny_http::Response::from_string("Not found").with_status_code(404)
+            };
+            if let Err(error) = request.respond(response) {
+                log::error!("Failed to respond: {}", error);
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Naive Bayes model learned from an already sorted directory tree, used
+//! for files no rule matches.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::extract::Extractor;
+use crate::{ClassifierPath, ClassifierPaths};
+
+/// Words shorter than this are too common to tell documents apart.
+const MIN_WORD_LENGTH: usize = 3;
+
+#[derive(Serialize, Deserialize)]
+struct Class {
+    /// Directory of the documents, relative to the labeled directory.
+    path: PathBuf,
+    documents: usize,
+    /// Occurrences of each word in the documents.
+    words: HashMap<String, u64>,
+    /// Sum of `words`.
+    total: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Model {
+    classes: Vec<Class>,
+    /// Number of distinct words across classes.
+    vocabulary: usize,
+    /// Rule each class files documents with.
+    #[serde(skip)]
+    rules: ClassifierPaths,
+    /// Probability below which a prediction is ignored.
+    #[serde(skip)]
+    threshold: f64
+}
+
+/// Default location of the model file.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().join("model.json"))
+}
+
+/// Lowercase words of `text` the model is made of, without diacritics
+/// and numbers.
+pub fn tokens(text: &str) -> Vec<String> {
+    let text = crate::normalize::fold(&text.to_lowercase());
+    crate::fuzzy::words(&text).into_iter()
+        .map(|(_, word)| word)
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH
+            && !word.chars().all(|c| c.is_numeric()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Tokens of the PDF files below `dir`, each labeled with the directory
+/// it is in, relative to `dir`. Files directly in `dir` are left out.
+pub fn documents(dir: &Path, extractor: &mut Extractor)
+    -> anyhow::Result<Vec<(PathBuf, Vec<String>)>> {
+    if !dir.is_dir() {
+        bail!("{:?} is not a directory", dir);
+    }
+    let mut documents = Vec::new();
+    let files = walkdir::WalkDir::new(dir).into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file()
+            && entry.path().extension().is_some_and(|extension| extension == "pdf"));
+    for file in files {
+        let label = match file.path().parent().and_then(|parent| parent.strip_prefix(dir).ok()) {
+            Some(label) if !label.as_os_str().is_empty() => label.to_path_buf(),
+            _ => continue
+        };
+        let extracted = match extractor.extract(file.path()) {
+            Ok(extracted) => extracted,
+            Err(error) => {
+                log::warn!("Skipping {:?}: {:#}", file.path(), error);
+                continue;
+            }
+        };
+        let tokens = tokens(&extracted.text);
+        if tokens.is_empty() {
+            log::warn!("{:?}: no text to learn from", file.path());
+            continue;
+        }
+        documents.push((label, tokens));
+    }
+    if documents.is_empty() {
+        bail!("No documents found in the subdirectories of {:?}", dir);
+    }
+    Ok(documents)
+}
+
+/// Learn a model from the PDF files below `dir`, each labeled with the
+/// directory it is in.
+pub fn train(dir: &Path, extractor: &mut Extractor) -> anyhow::Result<Model> {
+    let mut model = Model { classes: Vec::new(), vocabulary: 0, rules: Vec::new(),
+        threshold: 0.0 };
+    for (label, tokens) in documents(dir, extractor)? {
+        model.insert(label, tokens);
+    }
+    model.update();
+    Ok(model)
+}
+
+/// Load the model at `path`. Documents of a class are filed like the rule
+/// for the same directory in `rules`, if any.
+pub fn load(path: &Path, rules: &ClassifierPaths, threshold: f64) -> anyhow::Result<Model> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("Failed to read model {:?}", path))?;
+    let mut model: Model = serde_json::from_slice(&content)
+        .with_context(|| format!("Failed to parse model {:?}", path))?;
+    model.rules = model.classes.iter()
+        .map(|class| rules.iter()
+            .find(|rule| rule.path == class.path)
+            .cloned()
+            .unwrap_or_else(|| ClassifierPath { path: class.path.clone(), ..Default::default() }))
+        .collect();
+    model.threshold = threshold;
+    Ok(model)
+}
+
+impl Model {
+    fn insert(&mut self, label: PathBuf, tokens: Vec<String>) {
+        let index = match self.classes.iter().position(|class| class.path == label) {
+            Some(index) => index,
+            None => {
+                self.classes.push(Class { path: label, documents: 0, words: HashMap::new(),
+                    total: 0 });
+                self.classes.len() - 1
+            }
+        };
+        let class = &mut self.classes[index];
+        class.documents += 1;
+        class.total += tokens.len() as u64;
+        for token in tokens {
+            *class.words.entry(token).or_default() += 1;
+        }
+    }
+
+    /// Sort the classes and count the vocabulary after inserting documents.
+    fn update(&mut self) {
+        self.classes.sort_by(|a, b| a.path.cmp(&b.path));
+        self.vocabulary = self.classes.iter()
+            .flat_map(|class| class.words.keys())
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+    }
+
+    /// Learn the document made of `tokens` as one of the class `label`.
+    pub fn add(&mut self, label: PathBuf, tokens: Vec<String>) {
+        self.insert(label, tokens);
+        self.update();
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)
+            .with_context(|| format!("Failed to write model {:?}", path))
+    }
+
+    pub fn summary(&self) -> String {
+        let documents: usize = self.classes.iter().map(|class| class.documents).sum();
+        format!("{} documents in {} directories, {} distinct words",
+            documents, self.classes.len(), self.vocabulary)
+    }
+
+    /// Index of the most likely class of `text` and its probability.
+    fn predict(&self, text: &str) -> Option<(usize, f64)> {
+        // Words the model never saw tell nothing about the class.
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for token in tokens(text) {
+            if self.classes.iter().any(|class| class.words.contains_key(&token)) {
+                *counts.entry(token).or_default() += 1;
+            }
+        }
+        if counts.is_empty() {
+            return None;
+        }
+        let documents: usize = self.classes.iter().map(|class| class.documents).sum();
+        // Log likelihoods with Laplace smoothing.
+        let scores: Vec<f64> = self.classes.iter().map(|class| {
+            let denominator = (class.total + self.vocabulary as u64) as f64;
+            let words: f64 = counts.iter()
+                .map(|(word, count)| {
+                    let occurrences = class.words.get(word).copied().unwrap_or(0);
+                    *count as f64 * ((occurrences + 1) as f64 / denominator).ln()
+                })
+                .sum();
+            (class.documents as f64 / documents as f64).ln() + words
+        }).collect();
+        let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let sum: f64 = scores.iter().map(|score| (score - max).exp()).sum();
+        scores.iter().enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, score)| (index, (score - max).exp() / sum))
+    }
+
+    /// Rule of the class predicted for `text` with its probability, unless
+    /// the model is not confident enough.
+    pub fn classify(&self, text: &str) -> Option<(&ClassifierPath, f64)> {
+        let (index, probability) = self.predict(text)?;
+        let rule = &self.rules[index];
+        log::debug!("model predicts {:?} with probability {:.2}", rule.path, probability);
+        (probability >= self.threshold).then_some((rule, probability))
+    }
+}
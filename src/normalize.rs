@@ -0,0 +1,19 @@
+//! Unicode normalization of extracted text and keywords.
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters PDF extraction leaves inside words without being visible:
+/// soft hyphens, zero-width spaces and joiners, byte order marks.
+const INVISIBLE: &[char] = &['\u{ad}', '\u{200b}', '\u{200c}', '\u{200d}', '\u{feff}'];
+
+/// Compatibility composition (NFKC) of `text`, so that ligatures such as
+/// "ﬁ" and decomposed accents match keywords, without invisible characters.
+pub fn nfkc(text: &str) -> String {
+    text.nfkc().filter(|c| !INVISIBLE.contains(c)).collect()
+}
+
+/// `text` without diacritics, "Gebührenbescheid" becoming "Gebuhrenbescheid".
+pub fn fold(text: &str) -> String {
+    text.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect()
+}
@@ -0,0 +1,148 @@
+//! Per-file output of a run.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::explain::Hit;
+use crate::Classification;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per file.
+    Json
+}
+
+#[derive(Serialize)]
+struct FileRecord<'a> {
+    #[serde(serialize_with = "crate::paths::lossy")]
+    path: &'a Path,
+    rules: Vec<&'a Path>,
+    keywords: Vec<&'a str>,
+    ambiguous: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<f64>,
+    action: &'a str,
+    /// Where the file was put, or would be put in a dry run.
+    #[serde(serialize_with = "crate::paths::lossy_all")]
+    destinations: &'a [PathBuf],
+    dry_run: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hits: Option<&'a [Hit]>
+}
+
+/// Print the outcome of filing `result` with `action`. `destinations`
+/// are the planned directories in a dry run and the actual paths
+/// otherwise. `hits` are only given when explaining matches.
+pub fn print(format: Format, result: &Classification, unmatched_dir: Option<&Path>,
+    action: &str, destinations: &[PathBuf], dry_run: bool, hits: Option<&[Hit]>)
+    -> anyhow::Result<()> {
+    match format {
+        Format::Text => print_text(result, unmatched_dir, hits),
+        Format::Json => println!("{}", json(&result.source, result, action, destinations,
+            dry_run, hits)?)
+    }
+    Ok(())
+}
+
+/// JSON object describing the outcome of filing `result`, for the file
+/// at `path`.
+pub fn json(path: &Path, result: &Classification, action: &str, destinations: &[PathBuf],
+    dry_run: bool, hits: Option<&[Hit]>) -> anyhow::Result<String> {
+    Ok(record(path, result, action, destinations, dry_run, hits)?.to_string())
+}
+
+/// Same as `json`, as a value.
+pub fn record(path: &Path, result: &Classification, action: &str, destinations: &[PathBuf],
+    dry_run: bool, hits: Option<&[Hit]>) -> anyhow::Result<serde_json::Value> {
+    let record = FileRecord {
+        path,
+        rules: result.matches.iter().map(|rule| rule.path.as_path()).collect(),
+        keywords: result.keywords(),
+        ambiguous: result.ambiguous,
+        confidence: result.confidence.map(crate::confidence::round),
+        action,
+        destinations,
+        dry_run,
+        hits
+    };
+    Ok(serde_json::to_value(&record)?)
+}
+
+/// Counts of the outcomes of a run.
+#[derive(Default)]
+pub struct Summary {
+    pub classified: usize,
+    pub unmatched: usize,
+    /// Files matched by competing rules, whether they were sent to the
+    /// ambiguous directory or filed as usual without one.
+    pub ambiguous: usize,
+    pub errors: usize
+}
+
+/// Short description of the outcome of `result`.
+pub fn status(result: &Classification) -> &'static str {
+    if result.matches.is_empty() {
+        "unmatched"
+    } else if result.ambiguous {
+        "ambiguous"
+    } else {
+        "classified"
+    }
+}
+
+impl Summary {
+    pub fn add(&mut self, result: &Classification) {
+        match status(result) {
+            "unmatched" => self.unmatched += 1,
+            "ambiguous" => self.ambiguous += 1,
+            _ => self.classified += 1
+        }
+    }
+
+    /// Print the summary, on stderr when stdout is reserved for JSON.
+    pub fn print(&self, format: Format) {
+        match format {
+            Format::Text => println!("{}", self),
+            Format::Json => eprintln!("{}", self)
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} classified, {} unmatched, {} ambiguous, {} errors",
+            self.classified, self.unmatched, self.ambiguous, self.errors)
+    }
+}
+
+fn print_text(result: &Classification, unmatched_dir: Option<&Path>,
+    hits: Option<&[Hit]>) {
+    if result.matches.is_empty() {
+        if let Some(dir) = unmatched_dir {
+            println!(" src: {}", result.source.display());
+            println!("unmatched: {:?}", dir);
+            print_confidence(result);
+            println!();
+        }
+        return;
+    }
+    println!(" src: {}", result.source.display());
+    for m in result.matches.iter() {
+        let label = if result.ambiguous { "ambiguous" } else { "dest" };
+        println!("{}: {:?} using keywords: {:?}", label, m.path, m.keywords);
+        for hit in hits.unwrap_or_default().iter().filter(|hit| hit.rule == m.path) {
+            println!("    {}", hit);
+        }
+    }
+    print_confidence(result);
+    println!();
+}
+
+fn print_confidence(result: &Classification) {
+    if let Some(confidence) = result.confidence {
+        println!("confidence: {:.2}", confidence);
+    }
+}
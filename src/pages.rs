@@ -0,0 +1,56 @@
+//! Page selections restricting the text a rule is matched against.
+//!
+//! Extracted text separates pages with form feeds.
+
+use anyhow::bail;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Page {
+    /// Page number, starting at 1.
+    Number(usize),
+    Last
+}
+
+impl std::fmt::Display for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Page::Number(number) => write!(f, "{}", number),
+            Page::Last => write!(f, "last")
+        }
+    }
+}
+
+/// Parse page selections such as "1", "2-4" or "last".
+pub fn parse(items: &[String]) -> anyhow::Result<Vec<Page>> {
+    let mut pages = Vec::new();
+    for item in items {
+        let item = item.trim();
+        if item == "last" {
+            pages.push(Page::Last);
+            continue;
+        }
+        let (first, last) = item.split_once('-').unwrap_or((item, item));
+        match (first.trim().parse::<usize>(), last.trim().parse::<usize>()) {
+            (Ok(first), Ok(last)) if first >= 1 && first <= last => {
+                pages.extend((first..=last).map(Page::Number));
+            }
+            _ => bail!("Invalid page '{}', expected a page number from 1, a range \
+                such as 2-4 or 'last'", item)
+        }
+    }
+    Ok(pages)
+}
+
+/// Text of the `pages` of `text`, in document order.
+pub fn select(text: &str, pages: &[Page]) -> String {
+    let all: Vec<&str> = text.split('\x0c').collect();
+    let count = all.len();
+    all.into_iter().enumerate()
+        .filter(|(index, _)| pages.iter().any(|page| match page {
+            Page::Number(number) => number - 1 == *index,
+            Page::Last => index + 1 == count
+        }))
+        .map(|(_, page)| page)
+        .collect::<Vec<_>>()
+        .join("\x0c")
+}
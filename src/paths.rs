@@ -0,0 +1,154 @@
+//! Expansion of `~` and environment variables in configured paths.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Expand a leading `~` to the home directory, and `$NAME` or `${NAME}` to
+/// the value of the environment variable `NAME`.
+pub fn expand_str(text: &str) -> anyhow::Result<String> {
+    let text = match text.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let dirs = directories::BaseDirs::new()
+                .context("Unable to locate the home directory")?;
+            format!("{}{}", dirs.home_dir().display(), rest)
+        }
+        _ => text.to_string()
+    };
+    static VARIABLE: OnceLock<regex::Regex> = OnceLock::new();
+    let variable = VARIABLE.get_or_init(|| regex::Regex::new(
+        r"\$(?:\{([A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))").unwrap());
+    let mut expanded = String::new();
+    let mut last = 0;
+    for captures in variable.captures_iter(&text) {
+        let whole = captures.get(0).unwrap();
+        let name = captures.get(1).or_else(|| captures.get(2)).unwrap().as_str();
+        let value = match std::env::var(name) {
+            Ok(value) => value,
+            Err(_) => bail!("Environment variable '{}' used in '{}' is not set", name, text)
+        };
+        expanded.push_str(&text[last..whole.start()]);
+        expanded.push_str(&value);
+        last = whole.end();
+    }
+    expanded.push_str(&text[last..]);
+    Ok(expanded)
+}
+
+/// `expand_str` for paths. Paths that are not valid Unicode are left as is.
+pub fn expand(path: &Path) -> anyhow::Result<PathBuf> {
+    match path.to_str() {
+        Some(text) => Ok(PathBuf::from(expand_str(text)?)),
+        None => Ok(path.to_path_buf())
+    }
+}
+
+/// Path made of the bytes `bytes`, as read from a list of files. They are
+/// taken as they are on Unix and decoded as UTF-8 elsewhere.
+pub fn from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Serialize `path` as text, replacing what is not valid Unicode, so that
+/// files with such names can be recorded. Use with `serialize_with`.
+pub fn lossy<P: AsRef<Path>, S: Serializer>(path: &P, serializer: S)
+    -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.as_ref().to_string_lossy())
+}
+
+/// `lossy` for optional paths.
+pub fn lossy_option<P: AsRef<Path>, S: Serializer>(path: &Option<P>, serializer: S)
+    -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => lossy(path, serializer),
+        None => serializer.serialize_none()
+    }
+}
+
+/// `lossy` for lists of paths.
+pub fn lossy_all<P: AsRef<Path>, S: Serializer>(paths: &[P], serializer: S)
+    -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| path.as_ref().to_string_lossy()))
+}
+
+/// A path as stored by `exact`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Text(String),
+    Bytes(Vec<u8>)
+}
+
+impl From<Stored> for PathBuf {
+    fn from(stored: Stored) -> PathBuf {
+        match stored {
+            Stored::Text(text) => PathBuf::from(text),
+            Stored::Bytes(bytes) => from_bytes(&bytes)
+        }
+    }
+}
+
+/// Store paths without loss, for the state that is read back: as text when
+/// they are valid Unicode and as their bytes otherwise, on Unix. Use with
+/// `serde(with)`; `lossy` is for what people read.
+pub mod exact {
+    use super::*;
+
+    pub fn serialize<P: AsRef<Path>, S: Serializer>(path: &P, serializer: S)
+        -> Result<S::Ok, S::Error> {
+        let path = path.as_ref();
+        match path.to_str() {
+            Some(text) => serializer.serialize_str(text),
+            #[cfg(unix)]
+            None => {
+                use std::os::unix::ffi::OsStrExt;
+                serializer.collect_seq(path.as_os_str().as_bytes())
+            }
+            #[cfg(not(unix))]
+            None => lossy(&path, serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+        -> Result<PathBuf, D::Error> {
+        Ok(Stored::deserialize(deserializer)?.into())
+    }
+}
+
+/// `exact` for optional paths.
+pub mod exact_option {
+    use super::*;
+
+    pub fn serialize<P: AsRef<Path>, S: Serializer>(path: &Option<P>, serializer: S)
+        -> Result<S::Ok, S::Error> {
+        match path {
+            Some(path) => exact::serialize(path, serializer),
+            None => serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+        -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<Stored>::deserialize(deserializer)?.map(PathBuf::from))
+    }
+}
+
+/// `std::path::absolute`, leaving the URLs of remote outputs as they are.
+pub fn absolute(path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+    let path = path.as_ref();
+    if crate::remote::is_remote(path) {
+        Ok(path.to_path_buf())
+    } else {
+        std::path::absolute(path)
+    }
+}
@@ -0,0 +1,641 @@
+//! Moving classified files into the output tree.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+
+use crate::date;
+use crate::duplicate;
+use crate::hash;
+use crate::hook;
+use crate::journal::Journal;
+use crate::remote::Remote;
+use crate::rename;
+use crate::tag;
+use crate::ClassifierPath;
+
+/// Ownership and permission bits applied to placed files and to the
+/// directories created for them.
+#[derive(Clone, Debug, Default)]
+pub struct Permissions {
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub mode: Option<u32>,
+    pub dir_mode: Option<u32>
+}
+
+impl Permissions {
+    /// Fill every setting left unspecified from `parent`.
+    pub fn inherit(&mut self, parent: &Permissions) {
+        if self.owner.is_none() { self.owner = parent.owner.clone(); }
+        if self.group.is_none() { self.group = parent.group.clone(); }
+        if self.mode.is_none() { self.mode = parent.mode; }
+        if self.dir_mode.is_none() { self.dir_mode = parent.dir_mode; }
+    }
+
+    /// Mode used for directories. Defaults to the file mode with the
+    /// search bit added wherever the read bit is set.
+    fn directory_mode(&self) -> Option<u32> {
+        self.dir_mode.or_else(|| self.mode.map(|mode| mode | ((mode & 0o444) >> 2)))
+    }
+
+    #[cfg(unix)]
+    fn apply(&self, path: &Path, is_dir: bool) -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = if is_dir { self.directory_mode() } else { self.mode };
+        if let Some(mode) = mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set mode {:o} on {:?}", mode, path))?;
+        }
+        if self.owner.is_some() || self.group.is_some() {
+            let uid = self.owner.as_deref().map(lookup_user).transpose()?;
+            let gid = self.group.as_deref().map(lookup_group).transpose()?;
+            std::os::unix::fs::chown(path, uid, gid)
+                .with_context(|| format!("Failed to change ownership of {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn apply(&self, _path: &Path, _is_dir: bool) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn lookup_user(name: &str) -> anyhow::Result<u32> {
+    if let Ok(uid) = name.parse() {
+        return Ok(uid);
+    }
+    let user = nix::unistd::User::from_name(name)
+        .with_context(|| format!("Failed to look up user '{}'", name))?
+        .with_context(|| format!("Unknown user '{}'", name))?;
+    Ok(user.uid.as_raw())
+}
+
+#[cfg(unix)]
+fn lookup_group(name: &str) -> anyhow::Result<u32> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let group = nix::unistd::Group::from_name(name)
+        .with_context(|| format!("Failed to look up group '{}'", name))?
+        .with_context(|| format!("Unknown group '{}'", name))?;
+    Ok(group.gid.as_raw())
+}
+
+/// Parse an octal mode such as "0640" or "640".
+pub fn parse_mode(mode: &str) -> anyhow::Result<u32> {
+    let digits = mode.trim().trim_start_matches("0o");
+    let mode = u32::from_str_radix(digits, 8)
+        .with_context(|| format!("Invalid file mode '{}'", mode))?;
+    if mode > 0o7777 {
+        bail!("Invalid file mode '{:o}'", mode);
+    }
+    Ok(mode)
+}
+
+/// How a file is put at its destination.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Move the file out of the input directory.
+    Move,
+    /// Copy the file, leaving the original in place.
+    Copy,
+    /// Create a symbolic link to the original.
+    Symlink,
+    /// Create a hard link to the original.
+    Hardlink,
+    /// Record the matching directories in extended attributes of the file,
+    /// leaving it in place.
+    Tag
+}
+
+impl Action {
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Action::Move => "move",
+            Action::Copy => "copy",
+            Action::Symlink => "symlink",
+            Action::Hardlink => "hardlink",
+            Action::Tag => "tag"
+        }
+    }
+}
+
+/// What to do when a file already exists at the destination.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leave the source file where it is.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+    /// Add a numeric suffix to the new file name.
+    Rename,
+    /// Report an error and leave the source file where it is.
+    Error
+}
+
+/// What to do when a file with the same content already exists in the
+/// output tree.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Leave the source file where it is.
+    Skip,
+    /// Link the destination to the existing file, removing the source when
+    /// moving.
+    Link,
+    /// File the copy as any other file, warning about the existing one.
+    Report,
+    /// File the copy as any other file.
+    KeepBoth
+}
+
+/// Copy `source` to `destination` with its permission bits, and if
+/// `preserve` is set, its access and modification times, and its owner
+/// when running as root.
+///
+/// The copy is written to a hidden file next to `destination` and synced
+/// before being renamed, so that an interruption never leaves a partial
+/// file at `destination`.
+fn copy(source: &Path, destination: &Path, preserve: bool) -> std::io::Result<()> {
+    let dir = destination.parent().unwrap_or(Path::new("."));
+    let name = destination.file_name().unwrap_or_default().to_string_lossy();
+    let partial = dir.join(format!(".{}.classy-{}", name, std::process::id()));
+    let copied = copy_file(source, &partial, preserve)
+        .and_then(|_| std::fs::rename(&partial, destination));
+    if copied.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    copied?;
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn copy_file(source: &Path, destination: &Path, preserve: bool) -> std::io::Result<()> {
+    std::fs::copy(source, destination)?;
+    let file = std::fs::File::options().write(true).open(destination)?;
+    if preserve {
+        let metadata = source.metadata()?;
+        let times = std::fs::FileTimes::new()
+            .set_accessed(metadata.accessed()?)
+            .set_modified(metadata.modified()?);
+        file.set_times(times)?;
+        #[cfg(unix)]
+        if nix::unistd::geteuid().is_root() {
+            use std::os::unix::fs::MetadataExt;
+            std::os::unix::fs::chown(destination, Some(metadata.uid()),
+                Some(metadata.gid()))?;
+        }
+    }
+    file.sync_all()
+}
+
+/// Move `from` to `to`. Across filesystems, the file is copied, verified,
+/// and only then removed from `from`.
+pub fn relocate(from: &Path, to: &Path) -> anyhow::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(error) if error.kind() == std::io::ErrorKind::CrossesDevices => {
+            log::debug!("copying {:?} to {:?} on another filesystem", from, to);
+            copy(from, to, true)?;
+            verify(from, to)?;
+            std::fs::remove_file(from)
+                .with_context(|| format!("Failed to remove {:?}", from))
+        }
+        result => Ok(result?)
+    }
+}
+
+/// Whether `path` is on another filesystem than the directory `dir`.
+#[cfg(unix)]
+fn crosses_devices(path: &Path, dir: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(path.metadata()?.dev() != dir.metadata()?.dev())
+}
+
+#[cfg(not(unix))]
+fn crosses_devices(_path: &Path, _dir: &Path) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+/// Check that `copy` has the same content as `source`. Both files are left
+/// in place when they differ.
+fn verify(source: &Path, copy: &Path) -> anyhow::Result<()> {
+    let expected = hash::hash_file(source)?;
+    let actual = hash::hash_file(copy)?;
+    if expected != actual {
+        bail!("The copy {:?} of {:?} is corrupt: its SHA-256 is {} instead of {}, both \
+            files were kept", copy, source, actual, expected);
+    }
+    log::debug!("verified {:?}", copy);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Check that files can be created in `dir` by writing an empty file.
+fn writable(dir: &Path) -> anyhow::Result<()> {
+    let probe = dir.join(format!(".classy-probe-{}", std::process::id()));
+    std::fs::OpenOptions::new().write(true).create_new(true).open(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .with_context(|| format!("Cannot write in {:?}", dir))
+}
+
+/// Check, without writing anything, that `dir` exists or that its closest
+/// existing ancestor is a writable directory.
+fn creatable(dir: &Path) -> anyhow::Result<()> {
+    let existing = dir.ancestors()
+        .map(|ancestor| if ancestor.as_os_str().is_empty() { Path::new(".") } else { ancestor })
+        .find(|ancestor| ancestor.exists())
+        .with_context(|| format!("No existing parent directory for {:?}", dir))?;
+    if !existing.is_dir() {
+        bail!("{:?} is not a directory", existing);
+    }
+    if std::fs::metadata(existing)?.permissions().readonly() {
+        bail!("Cannot write in {:?}", existing);
+    }
+    Ok(())
+}
+
+/// Puts files into the output tree.
+pub struct Placer {
+    pub output: PathBuf,
+    pub action: Action,
+    /// Give copies the times and owner of the originals.
+    pub preserve: bool,
+    pub on_conflict: ConflictPolicy,
+    pub on_duplicate: DuplicatePolicy,
+    /// Files of the output tree, to detect duplicates.
+    pub duplicates: duplicate::Index,
+    /// Runs the `exec` command of a rule once a file is filed, unless
+    /// disabled.
+    pub hooks: Option<hook::Runner>,
+    /// Records every operation so that the run can be undone.
+    pub journal: Option<Journal>,
+    /// Host of the output directory when it is a URL. Files are then
+    /// uploaded, without permissions, and undoing the run only restores
+    /// the files moved out of the input directories.
+    pub remote: Option<Remote>,
+    /// Slugify destination file names, unless a rule decides otherwise.
+    pub slugify: bool
+}
+
+impl Placer {
+    /// Create `dir` below the output directory, applying `permissions` to
+    /// every directory that did not exist yet.
+    fn create_dirs(&self, dir: &Path, permissions: &Permissions)
+        -> anyhow::Result<()> {
+        if let Some(remote) = &self.remote {
+            let mut missing = Vec::new();
+            let mut current = dir;
+            while current != self.output && !remote.exists(current)? {
+                missing.push(current);
+                match current.parent() {
+                    Some(parent) => current = parent,
+                    None => break
+                }
+            }
+            for created in missing.iter().rev() {
+                remote.create_dir(created)?;
+            }
+            return Ok(());
+        }
+        let mut missing = Vec::new();
+        let mut current = dir;
+        while !current.exists() && current != self.output {
+            missing.push(current.to_path_buf());
+            match current.parent() {
+                Some(parent) => current = parent,
+                None => break
+            }
+        }
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {:?}", dir))?;
+        for created in missing.iter().rev() {
+            permissions.apply(created, true)?;
+            if let Some(journal) = &self.journal {
+                journal.record("mkdir", None, created, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `path` exists, in the output directory or on its host.
+    fn exists(&self, path: &Path) -> anyhow::Result<bool> {
+        match &self.remote {
+            Some(remote) => remote.exists(path),
+            None => Ok(path.exists())
+        }
+    }
+
+    /// `rename::unique` in the output directory.
+    fn unique(&self, dir: &Path, name: &str) -> anyhow::Result<String> {
+        rename::unique_with(dir, name, |path| self.exists(path))
+    }
+
+    /// Fail if the `rename` template of `rule` needs to list its remote
+    /// directory, which only works locally.
+    fn renamable(&self, rule: &ClassifierPath) -> anyhow::Result<()> {
+        if self.remote.is_some() && rule.rename.as_deref().is_some_and(rename::has_counter) {
+            bail!("Directory {:?} renames files with {{counter}}, which cannot be used with \
+                a remote output", rule.path);
+        }
+        Ok(())
+    }
+
+    /// Upload `source` to `destination` on `remote`, removing it when
+    /// moving. The upload is downloaded again and compared with `source`
+    /// first, which is kept if they differ.
+    fn upload(&self, remote: &Remote, source: &Path, destination: &Path)
+        -> anyhow::Result<()> {
+        remote.upload(source, destination)?;
+        let expected = hash::hash_file(source)?;
+        let actual = remote.hash(destination)?;
+        if expected != actual {
+            bail!("The upload {} of {:?} is corrupt: its SHA-256 is {} instead of {}, {:?} \
+                was kept", destination.display(), source, actual, expected, source);
+        }
+        log::debug!("verified {}", destination.display());
+        if self.action == Action::Move {
+            match &self.journal {
+                Some(journal) => {
+                    let backup = journal.back_up(source)?;
+                    journal.record("remove", None, source, Some(backup))?;
+                }
+                None => std::fs::remove_file(source)
+                    .with_context(|| format!("Failed to remove {:?}", source))?
+            }
+        }
+        Ok(())
+    }
+
+    fn transfer(&self, source: &Path, destination: &Path) -> anyhow::Result<()> {
+        match self.action {
+            Action::Move => relocate(source, destination)?,
+            Action::Copy => copy(source, destination, self.preserve)?,
+            Action::Symlink => symlink(&source.canonicalize()?, destination)?,
+            Action::Hardlink => std::fs::hard_link(source, destination)?,
+            Action::Tag => bail!("tagged files stay in place")
+        }
+        Ok(())
+    }
+
+    /// Put `source` at `destination`, resolving an existing destination
+    /// according to the conflict policy. Returns where the file went, or
+    /// `None` if it was skipped.
+    fn move_file(&self, source: &Path, destination: &Path)
+        -> anyhow::Result<Option<PathBuf>> {
+        if self.on_duplicate != DuplicatePolicy::KeepBoth {
+            match self.duplicates.find(source)? {
+                Some(existing) if self.on_duplicate == DuplicatePolicy::Report =>
+                    log::warn!("{:?} is a copy of {:?}", source, existing),
+                Some(existing) => return self.link_duplicate(source, destination, &existing),
+                None => {}
+            }
+        }
+        let mut destination = destination.to_path_buf();
+        let mut backup = None;
+        if self.exists(&destination)? {
+            match self.on_conflict {
+                ConflictPolicy::Skip => {
+                    log::warn!("skipped: {:?} already exists", destination);
+                    return Ok(None);
+                }
+                ConflictPolicy::Overwrite if self.remote.is_some() => {
+                    bail!("Remote file {:?} cannot be overwritten", destination);
+                }
+                ConflictPolicy::Overwrite => match &self.journal {
+                    Some(journal) => backup = Some(journal.back_up(&destination)?),
+                    None => std::fs::remove_file(&destination).with_context(|| {
+                        format!("Failed to replace {:?}", destination)
+                    })?
+                },
+                ConflictPolicy::Rename => {
+                    let dir = destination.parent().unwrap_or(Path::new(""));
+                    let name = destination.file_name().unwrap_or_default()
+                        .to_string_lossy().into_owned();
+                    destination = dir.join(self.unique(dir, &name)?);
+                }
+                ConflictPolicy::Error => {
+                    bail!("Destination {:?} already exists", destination);
+                }
+            }
+        }
+        if let Some(remote) = &self.remote {
+            self.upload(remote, source, &destination)?;
+            return Ok(Some(destination));
+        }
+        let dir = destination.parent().unwrap_or(Path::new("."));
+        let verified = self.action == Action::Copy && crosses_devices(source, dir)?;
+        self.transfer(source, &destination).with_context(|| {
+            format!("Failed to {} {:?} to {:?}", self.action.verb(), source,
+                destination)
+        })?;
+        // Copies to other filesystems are checked before they are trusted.
+        if verified {
+            verify(source, &destination)?;
+        }
+        if let Some(journal) = &self.journal {
+            journal.record(self.action.verb(), Some(source), &destination, backup)?;
+        }
+        self.duplicates.add(&destination);
+        Ok(Some(destination))
+    }
+
+    /// Handle `source`, a copy of `existing` in the output tree, according
+    /// to the duplicate policy.
+    fn link_duplicate(&self, source: &Path, destination: &Path, existing: &Path)
+        -> anyhow::Result<Option<PathBuf>> {
+        if self.on_duplicate == DuplicatePolicy::Skip {
+            log::warn!("skipped: {:?} is a copy of {:?}", source, existing);
+            return Ok(None);
+        }
+        let existing = existing.canonicalize()?;
+        let mut destination = destination.to_path_buf();
+        if destination.canonicalize().ok().as_ref() != Some(&existing) {
+            if destination.exists() {
+                let dir = destination.parent().unwrap_or(Path::new(""));
+                let name = destination.file_name().unwrap_or_default()
+                    .to_string_lossy().into_owned();
+                destination = dir.join(rename::unique(dir, &name));
+            }
+            symlink(&existing, &destination).with_context(|| {
+                format!("Failed to link {:?} to {:?}", destination, existing)
+            })?;
+            if let Some(journal) = &self.journal {
+                journal.record("symlink", Some(&existing), &destination, None)?;
+            }
+        }
+        if self.action == Action::Move {
+            match &self.journal {
+                Some(journal) => {
+                    let backup = journal.back_up(source)?;
+                    journal.record("remove", None, source, Some(backup))?;
+                }
+                None => std::fs::remove_file(source)
+                    .with_context(|| format!("Failed to remove {:?}", source))?
+            }
+        }
+        log::info!("{:?} is a copy of {:?}, linked at {:?}", source, existing, destination);
+        Ok(Some(destination))
+    }
+
+    /// Move `source` into the existing directory `dir`, keeping its name.
+    fn move_into(&self, source: &Path, dir: &Path)
+        -> anyhow::Result<Option<PathBuf>> {
+        let file_name = source.file_name()
+            .with_context(|| format!("No file name in {:?}", source))?;
+        self.move_file(source, &dir.join(file_name))
+    }
+
+    /// Move the file described by `fields` into the directory of its rule
+    /// and return the destination path. The file is renamed when the rule
+    /// has a `rename` template.
+    pub fn place(&self, fields: &rename::Fields)
+        -> anyhow::Result<Option<PathBuf>> {
+        let (source, rule) = (fields.source, fields.rule);
+        let dir = self.output.join(date::expand(&rule.path, fields.date)?);
+        self.create_dirs(&dir, &rule.permissions)?;
+        let slugify = rule.slugify.unwrap_or(self.slugify);
+        let destination = match &rule.rename {
+            Some(template) => {
+                self.renamable(rule)?;
+                let name = rename::render(template, fields, &dir, slugify)?;
+                self.move_file(source, &dir.join(name))?
+            }
+            None if slugify => {
+                let name = source.file_name()
+                    .with_context(|| format!("No file name in {:?}", source))?;
+                self.move_file(source, &dir.join(rename::slugify(&name.to_string_lossy())))?
+            }
+            None => self.move_into(source, &dir)?
+        };
+        // Links share the original file, which must be left untouched.
+        let owned = matches!(self.action, Action::Move | Action::Copy) && self.remote.is_none();
+        if let Some(destination) = destination.as_ref().filter(|_| owned) {
+            rule.permissions.apply(destination, false)?;
+        }
+        if let (Some(hooks), Some(hook), Some(destination))
+            = (&self.hooks, &rule.exec, &destination) {
+            hooks.run(hook, source, destination, &rule.path);
+        }
+        Ok(destination)
+    }
+
+    /// Record the directories of `rules` in the attributes of `source`,
+    /// which stays in place, and return its path.
+    pub fn tag(&self, source: &Path, rules: &[ClassifierPath], date: Option<NaiveDate>)
+        -> anyhow::Result<Option<PathBuf>> {
+        let categories = rules.iter()
+            .map(|rule| date::expand(&rule.path, date))
+            .collect::<anyhow::Result<Vec<PathBuf>>>()?;
+        tag::write(source, &categories.iter().map(PathBuf::as_path).collect::<Vec<_>>())?;
+        if let Some(journal) = &self.journal {
+            journal.record("tag", None, source, None)?;
+        }
+        if let Some(hooks) = &self.hooks {
+            for rule in rules {
+                if let Some(hook) = &rule.exec {
+                    hooks.run(hook, source, source, &rule.path);
+                }
+            }
+        }
+        Ok(Some(source.to_path_buf()))
+    }
+
+    /// Create the directories of `rules`, up to their first date
+    /// placeholder, and the directories `others` of the files set aside,
+    /// then check that files can be written in them. Without `create`,
+    /// only check that they could be created. Every problem is reported at
+    /// once.
+    pub fn prepare(&self, rules: &[ClassifierPath], others: &[&Path], create: bool)
+        -> anyhow::Result<()> {
+        let default = Permissions::default();
+        let dirs = rules.iter()
+            .map(|rule| (rule.path.as_path(), &rule.permissions))
+            .chain(others.iter().map(|dir| (*dir, &default)));
+        let mut problems: Vec<String> = rules.iter()
+            .filter_map(|rule| self.renamable(rule).err())
+            .map(|error| format!("{:#}", error))
+            .collect();
+        for (dir, permissions) in dirs {
+            let fixed: PathBuf = dir.components()
+                .take_while(|part| !date::has_placeholders(Path::new(part)))
+                .collect();
+            let dir = self.output.join(fixed);
+            // Remote directories cannot be probed with local files.
+            let prepared = if create && self.remote.is_some() {
+                self.create_dirs(&dir, permissions)
+            } else if create {
+                self.create_dirs(&dir, permissions).and_then(|_| writable(&dir))
+            } else if self.remote.is_none() {
+                creatable(&dir)
+            } else {
+                Ok(())
+            };
+            if let Err(error) = prepared {
+                problems.push(format!("{:#}", error));
+            }
+        }
+        problems.dedup();
+        if !problems.is_empty() {
+            bail!("The output tree is not usable:\n  {}", problems.join("\n  "));
+        }
+        Ok(())
+    }
+
+    /// Move `source` into `dir` below the output directory, creating it if
+    /// needed.
+    pub fn set_aside(&self, source: &Path, dir: &Path)
+        -> anyhow::Result<Option<PathBuf>> {
+        let dir = self.output.join(dir);
+        self.create_dirs(&dir, &Permissions::default())?;
+        self.move_into(source, &dir)
+    }
+
+    /// Move `source` into `dir` for manual review, next to a note listing
+    /// the competing rules `candidates`.
+    pub fn quarantine(&self, source: &Path, dir: &Path,
+        candidates: &[ClassifierPath]) -> anyhow::Result<Option<PathBuf>> {
+        let destination = match self.set_aside(source, dir)? {
+            Some(destination) => destination,
+            None => return Ok(None)
+        };
+
+        let mut note = format!("Competing destinations for {:?}:\n", source);
+        for candidate in candidates {
+            note.push_str(&format!("  {:?} using keywords: {:?}\n",
+                candidate.path, candidate.keywords));
+        }
+        let mut note_path = destination.clone().into_os_string();
+        note_path.push(".ambiguous.txt");
+        if let Some(remote) = &self.remote {
+            // Removed when dropped.
+            let mut local = tempfile::NamedTempFile::new()
+                .context("Failed to create a temporary file")?;
+            local.write_all(note.as_bytes())
+                .with_context(|| format!("Failed to write {:?}", local.path()))?;
+            remote.upload(local.path(), Path::new(&note_path))?;
+            return Ok(Some(destination));
+        }
+        std::fs::write(&note_path, note)
+            .with_context(|| format!("Failed to write {:?}", note_path))?;
+        if let Some(journal) = &self.journal {
+            journal.record("create", None, Path::new(&note_path), None)?;
+        }
+        Ok(Some(destination))
+    }
+}
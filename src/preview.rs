@@ -0,0 +1,82 @@
+//! Tree of the planned output directory, printed by dry runs with
+//! `--preview tree`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{Classification, Run};
+
+/// How a dry run shows where files would go.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preview {
+    /// One line per file.
+    Flat,
+    /// The output directory tree, with the files landing in each folder.
+    Tree
+}
+
+#[derive(Default)]
+struct Node {
+    dirs: BTreeMap<String, Node>,
+    files: Vec<String>
+}
+
+impl Node {
+    fn insert(&mut self, dir: &Path, file: String) {
+        let node = dir.components().fold(self, |node, component| {
+            node.dirs.entry(component.as_os_str().to_string_lossy().into_owned()).or_default()
+        });
+        node.files.push(file);
+    }
+
+    /// Number of files in this directory and below.
+    fn count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(Node::count).sum::<usize>()
+    }
+
+    fn print(&self, prefix: &str) {
+        let entries = self.dirs.len() + self.files.len();
+        let mut files = self.files.clone();
+        files.sort();
+        for (index, (name, node)) in self.dirs.iter().enumerate() {
+            let last = index + 1 == entries;
+            println!("{}{}{}/ ({})", prefix, if last { "└── " } else { "├── " }, name,
+                node.count());
+            node.print(&format!("{}{}", prefix, if last { "    " } else { "│   " }));
+        }
+        for (index, file) in files.iter().enumerate() {
+            let last = self.dirs.len() + index + 1 == entries;
+            println!("{}{}{}", prefix, if last { "└── " } else { "├── " }, file);
+        }
+    }
+}
+
+/// Print where the files of `results` would be filed, as a tree below the
+/// output directory, then the files that would be left in place.
+pub fn print_tree(run: &Run, results: &[Classification]) -> anyhow::Result<()> {
+    let output = &run.placer.output;
+    let mut root = Node::default();
+    let mut left = Vec::new();
+    for result in results {
+        let name = result.source.file_name()
+            .map_or_else(|| result.source.display().to_string(),
+                |name| name.to_string_lossy().into_owned());
+        let planned = run.planned(result)?;
+        if planned.is_empty() {
+            left.push(&result.source);
+        }
+        for dir in planned {
+            let dir: PathBuf = dir.strip_prefix(output).map_or(dir.clone(), Path::to_path_buf);
+            root.insert(&dir, name.clone());
+        }
+    }
+    println!("{}/ ({})", output.display(), root.count());
+    root.print("");
+    if !left.is_empty() {
+        println!("Left in place:");
+        for source in left {
+            println!("    {}", source.display());
+        }
+    }
+    Ok(())
+}
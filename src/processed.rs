@@ -0,0 +1,134 @@
+//! Files handled by previous runs, so that incremental runs over a
+//! long-lived input directory only process new or changed files.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::hash;
+
+/// Size and modification time of a file, to tell cheaply that it did not
+/// change.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    size: u64,
+    /// Nanoseconds since the Unix epoch.
+    modified: u128
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Option<Stamp> {
+        let metadata = path.metadata().ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp { size: metadata.len(), modified: modified.as_nanos() })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    hash: String,
+    stamp: Stamp,
+    /// "classified", "unmatched" or "ambiguous".
+    outcome: String
+}
+
+/// An entry of the state file, which lists them so that paths that are not
+/// valid Unicode are kept exactly.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    #[serde(with = "crate::paths::exact")]
+    path: PathBuf,
+    hash: String,
+    stamp: Stamp,
+    outcome: String
+}
+
+/// Entries of the state file `content`, which earlier versions wrote as an
+/// object keyed by path.
+fn parse(content: &[u8]) -> serde_json::Result<HashMap<PathBuf, Entry>> {
+    if content.trim_ascii_start().starts_with(b"{") {
+        return serde_json::from_slice(content);
+    }
+    let records: Vec<Record> = serde_json::from_slice(content)?;
+    Ok(records.into_iter()
+        .map(|Record { path, hash, stamp, outcome }| (path, Entry { hash, stamp, outcome }))
+        .collect())
+}
+
+/// Default location of the state file.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application data directory")?;
+    Ok(dirs.data_dir().join("processed.json"))
+}
+
+pub struct Processed {
+    path: PathBuf,
+    /// Entries by absolute path of the processed file.
+    entries: HashMap<PathBuf, Entry>,
+    changed: bool
+}
+
+impl Processed {
+    pub fn load(path: PathBuf) -> anyhow::Result<Processed> {
+        let entries = match std::fs::read(&path) {
+            Ok(content) => parse(&content)
+                .with_context(|| format!("Failed to parse state file {:?}", path))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error)
+                .with_context(|| format!("Failed to read state file {:?}", path))
+        };
+        Ok(Processed { path, entries, changed: false })
+    }
+
+    /// Whether `path` was processed by a previous run and did not change
+    /// since. A file with a new modification time but the same content
+    /// is not processed again.
+    pub fn is_done(&self, path: &Path) -> bool {
+        self.outcome(path).is_some()
+    }
+
+    /// Outcome of `path` if it `is_done`.
+    pub fn outcome(&self, path: &Path) -> Option<&str> {
+        let entry = std::path::absolute(path).ok()
+            .and_then(|path| self.entries.get(&path))?;
+        let unchanged = Stamp::of(path) == Some(entry.stamp)
+            || hash::hash_file(path).is_ok_and(|hash| hash == entry.hash);
+        unchanged.then_some(entry.outcome.as_str())
+    }
+
+    /// Remember that the file at `source`, with `hash` and `stamp` before
+    /// it was filed, was processed with `outcome`.
+    pub fn record(&mut self, source: PathBuf, hash: String, stamp: Stamp, outcome: &str) {
+        self.entries.insert(source, Entry { hash, stamp, outcome: outcome.to_string() });
+        self.changed = true;
+    }
+
+    /// Write the state file if anything was recorded.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let records: Vec<Record> = self.entries.iter()
+            .map(|(path, entry)| Record {
+                path: path.clone(),
+                hash: entry.hash.clone(),
+                stamp: entry.stamp,
+                outcome: entry.outcome.clone()
+            })
+            .collect();
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&records)?)
+            .with_context(|| format!("Failed to write state file {:?}", temporary))?;
+        std::fs::rename(&temporary, &self.path)
+            .with_context(|| format!("Failed to write state file {:?}", self.path))?;
+        self.changed = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+//! Progress of a batch run.
+//!
+//! A progress bar is drawn on stderr when it is a terminal. Otherwise one
+//! line per file is logged instead.
+
+use std::io::IsTerminal;
+use std::path::Path;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    /// Number of files, unknown while they are still being walked.
+    total: Option<usize>,
+    done: usize
+}
+
+impl Progress {
+    pub fn new(total: Option<usize>) -> Progress {
+        let shown = log::max_level() >= log::LevelFilter::Info;
+        let bar = (shown && std::io::stderr().is_terminal()).then(|| match total {
+            Some(total) => ProgressBar::new(total as u64).with_style(ProgressStyle::with_template(
+                "{bar:40} {pos}/{len} [{elapsed_precise}, ETA {eta}] {msg}").unwrap()),
+            None => ProgressBar::new_spinner().with_style(ProgressStyle::with_template(
+                "{spinner} {pos} files [{elapsed_precise}, {per_sec}] {msg}").unwrap())
+        });
+        Progress { bar, total, done: 0 }
+    }
+
+    /// Run `f` with the bar hidden, so that it can print.
+    pub fn suspend<T>(&self, f: impl FnOnce() -> T) -> T {
+        match &self.bar {
+            Some(bar) => bar.suspend(f),
+            None => f()
+        }
+    }
+
+    /// Mark `path` as processed with `status`.
+    pub fn file_done(&mut self, path: &Path, status: &str) {
+        self.done += 1;
+        match &self.bar {
+            Some(bar) => {
+                bar.set_message(format!("{}: {}", path.display(), status));
+                bar.inc(1);
+            }
+            None => match self.total {
+                Some(total) => log::info!("[{}/{}] {}: {}", self.done, total,
+                    path.display(), status),
+                None => log::info!("[{}] {}: {}", self.done, path.display(), status)
+            }
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
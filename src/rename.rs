@@ -0,0 +1,318 @@
+//! Destination file names built from `rename:` templates.
+//!
+//! A template such as `"{date}_{counter}_{original}"` is expanded with
+//! the fields below. The original extension is appended unless the
+//! template uses `{ext}` itself.
+//!
+//! - `original`: file name without extension
+//! - `ext`: original extension
+//! - `date`: date of the document, as `YYYY-MM-DD`
+//! - `year`, `month`, `day`: parts of the document date
+//! - `counter`: running number within the destination directory, one
+//!   more than the highest one in the names the template already gave
+//!   there. Not available for remote outputs, which cannot be listed
+//! - `title`: document title from the file metadata
+//! - `rule`: name of the destination directory
+//! - `keywords`: keywords of the matching rule, joined with dashes
+//!
+//! With `--slugify` or `slugify: true`, names are then lowercased, with
+//! diacritics removed and runs of other characters turned into dashes.
+
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+
+use crate::ClassifierPath;
+
+const FIELDS: &[&str] = &[
+    "original", "ext", "date", "year", "month", "day", "counter", "title",
+    "rule", "keywords"
+];
+
+/// Values available to a template for one file.
+pub struct Fields<'a> {
+    pub source: &'a Path,
+    pub rule: &'a ClassifierPath,
+    pub title: Option<&'a str>,
+    /// Document date, falling back to the modification date of the file.
+    pub date: Option<NaiveDate>
+}
+
+enum Token<'a> {
+    Literal(&'a str),
+    Field(&'a str)
+}
+
+fn tokenize(template: &str) -> anyhow::Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            tokens.push(Token::Literal(&rest[..start]));
+        }
+        let end = rest[start..].find('}').with_context(|| {
+            format!("Unterminated field in template '{}'", template)
+        })?;
+        let name = &rest[start + 1..start + end];
+        if !FIELDS.contains(&name) {
+            bail!("Unknown field '{{{}}}' in template '{}'", name, template);
+        }
+        tokens.push(Token::Field(name));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Literal(rest));
+    }
+    Ok(tokens)
+}
+
+/// Check that `template` only refers to known fields.
+pub fn validate(template: &str) -> anyhow::Result<()> {
+    tokenize(template).map(|_| ())
+}
+
+/// Whether `template` numbers files with `{counter}`.
+pub fn has_counter(template: &str) -> bool {
+    tokenize(template).is_ok_and(|tokens| tokens.iter()
+        .any(|token| matches!(token, Token::Field("counter"))))
+}
+
+/// Replace characters that cannot appear in a file name.
+fn sanitize(value: &str) -> String {
+    value.chars()
+        .map(|c| if c == '/' || c == '\\' || c == '\0' { '_' } else { c })
+        .collect()
+}
+
+/// `name` in lowercase, without diacritics, each run of characters other
+/// than ASCII letters and digits replaced by a dash: "Facture Été (2).PDF"
+/// becomes "facture-ete-2.pdf".
+pub fn slugify(name: &str) -> String {
+    let path = Path::new(name);
+    let (stem, ext) = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => (stem.to_string_lossy(), Some(ext.to_string_lossy())),
+        _ => (name.into(), None)
+    };
+    let slug = |text: &str| crate::normalize::fold(text).to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let mut name = slug(&stem);
+    if name.is_empty() {
+        name.push_str("document");
+    }
+    if let Some(ext) = ext.map(|ext| slug(&ext)).filter(|ext| !ext.is_empty()) {
+        name.push('.');
+        name.push_str(&ext);
+    }
+    name
+}
+
+/// One more than the highest counter among the names of `dir` that
+/// `name` renders, so that numbers are not reused when files are removed.
+fn counter(dir: &Path, name: impl Fn(usize) -> String) -> usize {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 1
+    };
+    entries.filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter_map(|file_name| file_name.split(|c: char| !c.is_ascii_digit())
+            .filter_map(|digits| digits.parse().ok())
+            .filter(|n| name(*n) == file_name)
+            .max())
+        .max()
+        .map_or(1, |n| n + 1)
+}
+
+fn value(name: &str, fields: &Fields) -> anyhow::Result<String> {
+    let source = fields.source;
+    let value = match name {
+        "original" => source.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        "ext" => source.extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        "date" | "year" | "month" | "day" => {
+            let date = fields.date.with_context(|| {
+                format!("No date found for {:?}", source)
+            })?;
+            let format = match name {
+                "date" => "%Y-%m-%d",
+                "year" => "%Y",
+                "month" => "%m",
+                _ => "%d"
+            };
+            date.format(format).to_string()
+        }
+        "title" => fields.title.unwrap_or_default().trim().to_string(),
+        "rule" => fields.rule.path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        "keywords" => fields.rule.keywords.join("-"),
+        _ => unreachable!()
+    };
+    Ok(sanitize(&value))
+}
+
+/// Expand `template` for a file moving into `dir`, slugifying the result
+/// if `slugify` is set.
+pub fn render(template: &str, fields: &Fields, dir: &Path, slugify: bool)
+    -> anyhow::Result<String> {
+    let tokens = tokenize(template)?;
+    // Every part but the counters, which depend on the names already taken.
+    let mut parts = Vec::new();
+    for token in tokens.iter() {
+        parts.push(match token {
+            Token::Literal(text) => Some(text.to_string()),
+            Token::Field("counter") => None,
+            Token::Field(field) => Some(value(field, fields)?)
+        });
+    }
+    let has_ext = tokens.iter().any(|token| matches!(token, Token::Field("ext")));
+    if !has_ext {
+        if let Some(ext) = fields.source.extension() {
+            parts.push(Some(format!(".{}", ext.to_string_lossy())));
+        }
+    }
+    let name = |counter: usize| {
+        let name: String = parts.iter()
+            .map(|part| part.clone().unwrap_or_else(|| format!("{:03}", counter)))
+            .collect();
+        if slugify { self::slugify(&name) } else { name }
+    };
+    let number = if parts.contains(&None) { counter(dir, name) } else { 1 };
+    let name = name(number);
+    if name.trim().is_empty() {
+        bail!("Template '{}' produced an empty file name", template);
+    }
+    Ok(name)
+}
+
+/// First name derived from `name` that does not exist in `dir`, adding
+/// "-2", "-3"… before the extension when needed.
+pub fn unique(dir: &Path, name: &str) -> String {
+    unique_with(dir, name, |path| Ok(path.exists())).expect("local lookups do not fail")
+}
+
+/// `unique`, telling whether a path exists with `exists`.
+pub fn unique_with(dir: &Path, name: &str,
+    mut exists: impl FnMut(&Path) -> anyhow::Result<bool>) -> anyhow::Result<String> {
+    if !exists(&dir.join(name))? {
+        return Ok(name.to_string());
+    }
+    let path = Path::new(name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    for n in 2.. {
+        let candidate = format!("{}-{}{}", stem, n, ext);
+        if !exists(&dir.join(&candidate))? {
+            return Ok(candidate);
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> ClassifierPath {
+        ClassifierPath {
+            path: "Invoices/Acme".into(),
+            keywords: vec!["acme".to_string(), "invoice".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn fields<'a>(source: &'a Path, rule: &'a ClassifierPath) -> Fields<'a> {
+        Fields {
+            source,
+            rule,
+            title: Some(" March / April "),
+            date: NaiveDate::from_ymd_opt(2023, 4, 5)
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_and_unterminated_fields() {
+        assert!(validate("{date}_{original}").is_ok());
+        assert!(validate("{colour}").is_err());
+        assert!(validate("{date").is_err());
+    }
+
+    #[test]
+    fn has_counter() {
+        assert!(super::has_counter("{date}_{counter}"));
+        assert!(!super::has_counter("{date}_{original}"));
+        assert!(!super::has_counter("{counter"));
+    }
+
+    #[test]
+    fn renders_fields_and_appends_the_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let rule = rule();
+        let source = Path::new("/inbox/scan 1.pdf");
+        let name = render("{year}-{month}-{day} {rule} {keywords} {title}", &fields(source, &rule),
+            dir.path(), false).unwrap();
+        assert_eq!(name, "2023-04-05 Acme acme-invoice March _ April.pdf");
+        let name = render("{original}.{ext}", &fields(source, &rule), dir.path(), false)
+            .unwrap();
+        assert_eq!(name, "scan 1.pdf");
+    }
+
+    #[test]
+    fn fails_without_a_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let rule = rule();
+        let fields = Fields { date: None, ..fields(Path::new("scan.pdf"), &rule) };
+        assert!(render("{date}", &fields, dir.path(), false).is_err());
+    }
+
+    #[test]
+    fn counts_past_the_highest_number_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let rule = rule();
+        let fields = fields(Path::new("scan.pdf"), &rule);
+        assert_eq!(render("{date}_{counter}", &fields, dir.path(), false).unwrap(),
+            "2023-04-05_001.pdf");
+        for name in ["2023-04-05_001.pdf", "2023-04-05_007.pdf", "2023-04-06_009.pdf",
+            "other_012.pdf"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(render("{date}_{counter}", &fields, dir.path(), false).unwrap(),
+            "2023-04-05_008.pdf");
+    }
+
+    #[test]
+    fn slugifies() {
+        assert_eq!(slugify("Facture Été (2).PDF"), "facture-ete-2.pdf");
+        assert_eq!(slugify("???.pdf"), "document.pdf");
+        assert_eq!(slugify("README"), "readme");
+    }
+
+    #[test]
+    fn slugifies_rendered_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let rule = rule();
+        let name = render("{rule} {title}", &fields(Path::new("Scan.PDF"), &rule), dir.path(),
+            true).unwrap();
+        assert_eq!(name, "acme-march-april.pdf");
+    }
+
+    #[test]
+    fn unique_adds_a_number_before_the_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(unique(dir.path(), "scan.pdf"), "scan.pdf");
+        std::fs::write(dir.path().join("scan.pdf"), "").unwrap();
+        std::fs::write(dir.path().join("scan-2.pdf"), "").unwrap();
+        assert_eq!(unique(dir.path(), "scan.pdf"), "scan-3.pdf");
+        assert_eq!(unique_with(dir.path(), "notes", |path| Ok(path.ends_with("notes"))).unwrap(),
+            "notes-2");
+    }
+}
@@ -0,0 +1,156 @@
+//! CSV and HTML summaries of a run.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::output::Summary;
+use crate::Classification;
+
+const STYLE: &str = "
+    body { font-family: sans-serif; margin: 2em; color: #222; }
+    table { border-collapse: collapse; margin-bottom: 2em; }
+    th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+    th { background: #eee; }
+    td.count { text-align: right; }
+    .error { color: #a00; }
+";
+
+/// Quote `field` if it contains characters special to CSV.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn status(result: &Classification, dry_run: bool) -> &'static str {
+    if result.matches.is_empty() {
+        "unmatched"
+    } else if result.ambiguous {
+        "ambiguous"
+    } else if dry_run {
+        "dry_run"
+    } else if result.destinations.is_empty() {
+        "skipped"
+    } else {
+        "filed"
+    }
+}
+
+fn confidence(result: &Classification) -> String {
+    result.confidence.map(|confidence| format!("{:.2}", confidence)).unwrap_or_default()
+}
+
+/// Write one line per file of the run to `path`.
+pub fn write(path: &Path, results: &[Classification], dry_run: bool)
+    -> anyhow::Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create report {:?}", path))?;
+    let mut file = std::io::BufWriter::new(file);
+    writeln!(file, "source,destination,rule,keywords,status,confidence")?;
+    for result in results {
+        let join = |paths: Vec<&Path>| paths.iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let fields = [
+            result.source.display().to_string(),
+            join(result.destinations.iter().map(|p| p.as_path()).collect()),
+            join(result.matches.iter().map(|rule| rule.path.as_path()).collect()),
+            result.keywords().join("; "),
+            status(result, dry_run).to_string(),
+            confidence(result)
+        ];
+        let line: Vec<String> = fields.iter().map(|field| escape(field)).collect();
+        writeln!(file, "{}", line.join(","))?;
+    }
+    file.flush()
+        .with_context(|| format!("Failed to write report {:?}", path))
+}
+
+/// Escape the characters special to HTML in `text`.
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_paths<'p>(paths: impl Iterator<Item = &'p Path>) -> String {
+    paths.map(|path| html(&path.display().to_string()))
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+/// Write a self-contained HTML page with the counts per directory, the
+/// files that were filed or left unmatched, and the `errors` of the run to
+/// `path`.
+pub fn write_html(path: &Path, results: &[Classification], errors: &[(PathBuf, String)],
+    summary: &Summary, dry_run: bool) -> anyhow::Result<()> {
+    let mut counts: BTreeMap<&Path, usize> = BTreeMap::new();
+    for rule in results.iter().flat_map(|result| result.matches.iter()) {
+        *counts.entry(rule.path.as_path()).or_default() += 1;
+    }
+    let (unmatched, matched): (Vec<&Classification>, Vec<&Classification>) = results.iter()
+        .partition(|result| result.matches.is_empty());
+
+    let mut page = String::new();
+    let title = format!("classy run of {}", chrono::Local::now().format("%Y-%m-%d %H:%M"));
+    page.push_str(&format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, STYLE, title));
+    if dry_run {
+        page.push_str("<p>Dry run, no file was moved.</p>\n");
+    }
+    page.push_str(&format!("<p>{}</p>\n", summary));
+
+    page.push_str("<h2>Directories</h2>\n<table>\n<tr><th>Directory</th><th>Files</th></tr>\n");
+    for (dir, count) in counts.iter() {
+        page.push_str(&format!("<tr><td>{}</td><td class=\"count\">{}</td></tr>\n",
+            html(&dir.display().to_string()), count));
+    }
+    page.push_str("</table>\n");
+
+    page.push_str("<h2>Classified files</h2>\n<table>\n<tr><th>Source</th>\
+        <th>Destination</th><th>Directory</th><th>Keywords</th><th>Status</th>\
+        <th>Confidence</th></tr>\n");
+    for result in matched.iter() {
+        page.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+            <td class=\"count\">{}</td></tr>\n",
+            html(&result.source.display().to_string()),
+            html_paths(result.destinations.iter().map(|p| p.as_path())),
+            html_paths(result.matches.iter().map(|rule| rule.path.as_path())),
+            html(&result.keywords().join(", ")),
+            status(result, dry_run),
+            confidence(result)));
+    }
+    page.push_str("</table>\n");
+
+    page.push_str(&format!("<h2>Unmatched files ({})</h2>\n", unmatched.len()));
+    if !unmatched.is_empty() {
+        page.push_str("<table>\n<tr><th>Source</th><th>Destination</th></tr>\n");
+        for result in unmatched.iter() {
+            page.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n",
+                html(&result.source.display().to_string()),
+                html_paths(result.destinations.iter().map(|p| p.as_path()))));
+        }
+        page.push_str("</table>\n");
+    }
+
+    page.push_str(&format!("<h2>Errors ({})</h2>\n", errors.len()));
+    if !errors.is_empty() {
+        page.push_str("<table>\n<tr><th>File</th><th>Error</th></tr>\n");
+        for (file, error) in errors.iter() {
+            page.push_str(&format!("<tr><td>{}</td><td class=\"error\">{}</td></tr>\n",
+                html(&file.display().to_string()), html(error)));
+        }
+        page.push_str("</table>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    std::fs::write(path, page)
+        .with_context(|| format!("Failed to write report {:?}", path))
+}
@@ -0,0 +1,222 @@
+//! Full-screen review of a run before anything is filed.
+//!
+//! Every file is listed with its proposed destination. Files can be
+//! approved in bulk or one by one and reassigned to another rule, and the
+//! text around the matched keywords is previewed for the selected file.
+//! Approved files are then filed in one go, and those that were reassigned
+//! are recorded as corrections.
+
+use std::io::IsTerminal;
+
+use anyhow::bail;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::corrections::{self, Correction};
+use crate::{explain, Classification, Run};
+
+/// Characters of text previewed when no keyword matched.
+const PREVIEW: usize = 500;
+
+struct Item {
+    result: Classification,
+    text: String,
+    approved: bool,
+    /// Rule proposed before the file was reassigned, if it was.
+    proposed: Option<Option<std::path::PathBuf>>
+}
+
+struct App<'a> {
+    run: &'a Run,
+    items: Vec<Item>,
+    list: ListState,
+    /// Rule selection when reassigning the selected file.
+    picker: Option<ListState>
+}
+
+impl App<'_> {
+    fn proposal(&self, item: &Item) -> String {
+        let result = &item.result;
+        if result.ambiguous {
+            let rules: Vec<String> = result.matches.iter()
+                .map(|rule| rule.path.display().to_string())
+                .collect();
+            return format!("ambiguous: {}", rules.join(" | "));
+        }
+        match self.run.planned(result) {
+            Ok(dirs) if dirs.is_empty() => "(unmatched)".to_string(),
+            Ok(dirs) => dirs.iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            Err(error) => format!("error: {:#}", error)
+        }
+    }
+
+    fn preview(item: &Item) -> Vec<Line<'static>> {
+        let hits = explain::hits(&item.result, &item.text);
+        if hits.is_empty() {
+            let text: String = item.text.chars().take(PREVIEW).collect();
+            return text.lines().map(|line| Line::from(line.to_string())).collect();
+        }
+        hits.iter().map(|hit| Line::from(hit.to_string())).collect()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [top, bottom, help] = Layout::vertical([
+            Constraint::Percentage(60),
+            Constraint::Fill(1),
+            Constraint::Length(1)
+        ]).areas(frame.area());
+
+        let approved = self.items.iter().filter(|item| item.approved).count();
+        let rows: Vec<ListItem> = self.items.iter().map(|item| {
+            let mark = if item.approved { "[x]" } else { "[ ]" };
+            ListItem::new(format!("{} {} -> {}", mark, item.result.source.display(),
+                self.proposal(item)))
+        }).collect();
+        let list = List::new(rows)
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                " Files ({} of {} approved) ", approved, self.items.len())))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, top, &mut self.list);
+
+        if let Some(item) = self.list.selected().and_then(|i| self.items.get(i)) {
+            let preview = Paragraph::new(App::preview(item))
+                .block(Block::default().borders(Borders::ALL).title(" Preview "))
+                .wrap(Wrap { trim: false });
+            frame.render_widget(preview, bottom);
+        }
+
+        frame.render_widget(Line::from(
+            "up/down: move  space: approve  a: all  n: none  r: reassign  \
+             enter: apply  q: quit"), help);
+
+        if let Some(picker) = &mut self.picker {
+            let area = centered(frame.area());
+            let rules: Vec<ListItem> = self.run.config.paths.iter()
+                .map(|rule| ListItem::new(rule.path.display().to_string()))
+                .collect();
+            let list = List::new(rules)
+                .block(Block::default().borders(Borders::ALL).title(" Reassign to "))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(list, area, picker);
+        }
+    }
+
+    fn reassign(&mut self, rule: usize) {
+        let rule = self.run.config.paths[rule].clone();
+        if let Some(item) = self.list.selected().and_then(|i| self.items.get_mut(i)) {
+            if item.proposed.is_none() {
+                item.proposed = Some(item.result.matches.first().map(|rule| rule.path.clone()));
+            }
+            item.result.matches = vec![rule];
+            item.result.ambiguous = false;
+            item.result.confidence = None;
+            item.approved = true;
+        }
+    }
+
+    /// Handle events until the user applies (true) or quits (false).
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<bool> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key.code,
+                _ => continue
+            };
+            if let Some(picker) = &mut self.picker {
+                match key {
+                    KeyCode::Up | KeyCode::Char('k') => picker.select_previous(),
+                    KeyCode::Down | KeyCode::Char('j') => picker.select_next(),
+                    KeyCode::Enter => {
+                        let rule = picker.selected();
+                        self.picker = None;
+                        if let Some(rule) = rule {
+                            self.reassign(rule);
+                        }
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => self.picker = None,
+                    _ => {}
+                }
+                continue;
+            }
+            match key {
+                KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+                KeyCode::Char(' ') => {
+                    if let Some(item) = self.list.selected().and_then(|i| self.items.get_mut(i)) {
+                        item.approved = !item.approved;
+                    }
+                }
+                KeyCode::Char('a') => self.items.iter_mut().for_each(|item| item.approved = true),
+                KeyCode::Char('n') => self.items.iter_mut().for_each(|item| item.approved = false),
+                KeyCode::Char('r') if !self.run.config.paths.is_empty() => {
+                    self.picker = Some(ListState::default().with_selected(Some(0)));
+                }
+                KeyCode::Enter => return Ok(true),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn centered(area: Rect) -> Rect {
+    let [_, middle, _] = Layout::vertical([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20)
+    ]).areas(area);
+    let [_, center, _] = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20)
+    ]).areas(middle);
+    center
+}
+
+/// Let the user review classified files along with their text. Returns,
+/// in the same order, the approved files with their possibly reassigned
+/// rules, or `None` if the review was abandoned.
+pub fn review(run: &Run, files: Vec<(Classification, String)>)
+    -> anyhow::Result<Option<Vec<Option<Classification>>>> {
+    if !std::io::stdout().is_terminal() {
+        bail!("Reviewing a run requires a terminal");
+    }
+    let items = files.into_iter().map(|(result, text)| Item {
+        // Only confident matches are approved by default.
+        approved: !result.matches.is_empty() && !result.ambiguous,
+        result,
+        text,
+        proposed: None
+    }).collect();
+    let mut app = App {
+        run,
+        items,
+        list: ListState::default().with_selected(Some(0)),
+        picker: None
+    };
+    let mut terminal = ratatui::init();
+    let applied = app.run(&mut terminal);
+    ratatui::restore();
+    if !applied? {
+        return Ok(None);
+    }
+    for item in app.items.iter().filter(|item| item.approved && !run.dry_run) {
+        if let (Some(proposed), Some(rule)) = (&item.proposed, item.result.matches.first()) {
+            if proposed.as_ref() != Some(&rule.path) {
+                corrections::record(&Correction::new(&item.result.source, proposed.as_deref(),
+                    &rule.path, &item.text)?)?;
+            }
+        }
+    }
+    Ok(Some(app.items.into_iter()
+        .map(|item| item.approved.then_some(item.result))
+        .collect()))
+}
@@ -0,0 +1,139 @@
+//! Cron-like schedules of the sweeps of the input directories in watch
+//! mode, such as "0 3 * * *" for every day at 3:00.
+//!
+//! The five fields are the minute, hour, day of the month, month and day of
+//! the week, 0 or 7 being Sunday. Each is `*`, a number, a range such as
+//! `1-5`, a step such as `*/15` or `8-18/2`, or a list of those separated by
+//! commas. As with cron, when both days are restricted a day matches if
+//! either does.
+
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+/// Name, smallest and largest value of each field.
+const FIELDS: [(&str, u32, u32); 5] = [
+    ("minute", 0, 59), ("hour", 0, 23), ("day of the month", 1, 31), ("month", 1, 12),
+    ("day of the week", 0, 7)
+];
+const MINUTE: usize = 0;
+const HOUR: usize = 1;
+const DAY: usize = 2;
+const MONTH: usize = 3;
+const WEEKDAY: usize = 4;
+
+/// Days searched for the next time of a schedule, enough to reach the
+/// next February 29.
+const HORIZON: usize = 366 * 8;
+
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    expression: String,
+    /// Values allowed by each field, as bit sets.
+    fields: [u64; 5],
+    /// Whether the day of the month and the day of the week are `*`.
+    any_day: bool,
+    any_weekday: bool
+}
+
+fn number(text: &str, name: &str) -> anyhow::Result<u32> {
+    text.parse().with_context(|| format!("Invalid {} '{}'", name, text))
+}
+
+fn parse_field(text: &str, (name, min, max): (&str, u32, u32)) -> anyhow::Result<u64> {
+    let mut set = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, number(step, name)?),
+            None => (part, 1)
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (number(first, name)?, number(last, name)?),
+            // "5/10" runs from 5 to the end, like "5-59/10".
+            None if part.contains('/') => (number(range, name)?, max),
+            None => (number(range, name)?, number(range, name)?)
+        };
+        if first < min || last > max || first > last || step == 0 {
+            bail!("Invalid {} '{}', expected values from {} to {}", name, part, min, max);
+        }
+        for value in (first..=last).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+impl Schedule {
+    fn has(&self, field: usize, value: u32) -> bool {
+        self.fields[field] & (1 << value) != 0
+    }
+
+    fn matches_day(&self, date: chrono::NaiveDate) -> bool {
+        let day = self.has(DAY, date.day());
+        let weekday = self.has(WEEKDAY, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday
+        }
+    }
+
+    /// First time of the schedule after `time`, to the minute.
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = (time + chrono::Duration::minutes(1)).with_second(0)?.with_nanosecond(0)?;
+        let mut date = start.date();
+        for _ in 0..HORIZON {
+            if self.has(MONTH, date.month()) && self.matches_day(date) {
+                let times = (0..24).filter(|hour| self.has(HOUR, *hour))
+                    .flat_map(|hour| (0..60).map(move |minute| (hour, minute)))
+                    .filter(|(_, minute)| self.has(MINUTE, *minute))
+                    .filter_map(|(hour, minute)| date.and_hms_opt(hour, minute, 0));
+                for next in times {
+                    if next >= start {
+                        return Some(next);
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> anyhow::Result<Schedule> {
+        let parts: Vec<&str> = expression.split_whitespace().collect();
+        if parts.len() != FIELDS.len() {
+            bail!("Invalid schedule '{}', expected five fields such as \"0 3 * * *\"",
+                expression);
+        }
+        let mut fields = [0; 5];
+        for (index, part) in parts.iter().enumerate() {
+            fields[index] = parse_field(part, FIELDS[index])
+                .with_context(|| format!("In schedule '{}'", expression))?;
+        }
+        // Sunday is both 0 and 7.
+        if fields[WEEKDAY] & (1 << 7) != 0 {
+            fields[WEEKDAY] |= 1;
+        }
+        let schedule = Schedule {
+            expression: expression.to_string(),
+            fields,
+            any_day: parts[DAY] == "*",
+            any_weekday: parts[WEEKDAY] == "*"
+        };
+        if schedule.next_after(chrono::Local::now().naive_local()).is_none() {
+            bail!("Schedule '{}' never runs", expression);
+        }
+        Ok(schedule)
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
@@ -0,0 +1,237 @@
+//! HTTP API classifying uploaded documents, for scanners and other
+//! devices that can POST files.
+//!
+//! - `GET /health` answers "ok".
+//! - `GET /metrics` answers the metrics of the server for Prometheus.
+//! - `POST /classify` takes the document as the request body and answers
+//!   with the same JSON object as `--format json`. With `?store=true`, the
+//!   document is also filed into the output directory, which a server
+//!   started with `--dry-run` refuses. `?name=` gives its file name.
+//!   Documents larger than 256 MiB are refused.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use tiny_http::{Header, Method, Request, Response};
+
+use crate::{extract, metrics, output, place, Run};
+
+/// Largest document accepted, in bytes.
+const MAX_UPLOAD: u64 = 256 * 1024 * 1024;
+
+/// Error of a document larger than `MAX_UPLOAD`, answered with 413.
+#[derive(Debug)]
+struct TooLarge;
+
+impl std::fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Documents are limited to {} MiB", MAX_UPLOAD / 1024 / 1024)
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// Decode the `%XX` escapes and `+` of a query string component.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => match value.get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    index += 2;
+                }
+                None => decoded.push(b'%')
+            },
+            byte => decoded.push(byte)
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Path and query parameters of `url`.
+fn parse_url(url: &str) -> (&str, Vec<(String, String)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let parameters = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect();
+    (path, parameters)
+}
+
+fn json_response(status: u16, body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes("Content-Type", "application/json")
+        .expect("valid header");
+    Response::from_string(body).with_status_code(status).with_header(header)
+}
+
+fn error_response(status: u16, error: &anyhow::Error) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(status, serde_json::json!({ "error": format!("{:#}", error) }).to_string())
+}
+
+/// Whether the query of `url` asks for the document to be filed.
+fn store(url: &str) -> bool {
+    let (_, parameters) = parse_url(url);
+    parameters.iter().any(|(name, value)| name == "store" && matches!(value.as_str(),
+        "true" | "1" | "yes"))
+}
+
+/// Classify the document in the body of `request`, and file it if asked
+/// to. The document is written to `uploads` while it is classified.
+fn classify(run: &Run, request: &mut Request, uploads: &Path) -> anyhow::Result<String> {
+    let (_, parameters) = parse_url(request.url());
+    let parameter = |key: &str| parameters.iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.as_str());
+    // Only the file name of the given name, never a path.
+    let name = parameter("name")
+        .and_then(|name| Path::new(name).file_name())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(chrono::Local::now().format("upload-%Y%m%d-%H%M%S.pdf").to_string())
+        });
+    let path = uploads.join(&name);
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(&path)
+        .with_context(|| format!("Failed to create {:?}", path))?;
+    let received = std::io::copy(&mut request.as_reader().take(MAX_UPLOAD + 1), &mut file);
+    drop(file);
+    match received {
+        Ok(length) if length <= MAX_UPLOAD => {}
+        received => {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {:?}", path))?;
+            received.context("Failed to receive the document")?;
+            return Err(TooLarge.into());
+        }
+    }
+
+    let extracted = extract::Extractor::new(None)
+        .with_chains(&run.config.extraction)
+        .with_metrics(run.metrics.clone())
+        .with_limits(run.limits.clone())
+        .extract(&path);
+    let outcome = if store(request.url()) {
+        run.lock(true).and_then(|_lock| run.process(&path, extracted)).and_then(|result| {
+            let action = run.action(&result);
+            output::json(&name, &result, action, &result.destinations, false, None)
+        })
+    } else {
+        let result = extracted.and_then(|extracted| {
+            let config = run.config_for(&path)?;
+            Ok(crate::classify(&path, &extracted, &config, run.policy))
+        });
+        if let Some(metrics) = &run.metrics {
+            metrics.file_done(result.as_ref().ok());
+        }
+        result.and_then(|result| {
+            let planned = run.planned(&result)?;
+            output::json(&name, &result, run.action(&result), &planned, true, None)
+        })
+    };
+    // Documents that were not filed, such as unmatched ones without an
+    // unmatched directory, are not kept.
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove {:?}", path))?;
+    }
+    outcome
+}
+
+fn handle(run: &Run, mut request: Request, uploads: &Path) -> std::io::Result<()> {
+    let (path, _) = parse_url(request.url());
+    let response = match (request.method(), path) {
+        (Method::Get, "/health") => Response::from_string("ok"),
+        (Method::Get, "/metrics") => match &run.metrics {
+            Some(metrics) => metrics::response(metrics),
+            None => Response::from_string("Not found").with_status_code(404)
+        },
+        (Method::Post, "/classify") if run.dry_run && store(request.url()) => {
+            error_response(400, &anyhow!("Documents cannot be stored by a server started \
+                with --dry-run"))
+        }
+        (Method::Post, "/classify") if request.body_length()
+            .is_some_and(|length| length as u64 > MAX_UPLOAD) => {
+            error_response(413, &TooLarge.into())
+        }
+        (Method::Post, "/classify") => match classify(run, &mut request, uploads) {
+            Ok(body) => json_response(200, body),
+            Err(error) if error.is::<TooLarge>() => error_response(413, &error),
+            Err(error) => {
+                log::error!("{}: {:#}", request.url(), error);
+                error_response(500, &error)
+            }
+        },
+        (_, "/health" | "/metrics" | "/classify") => Response::from_string("Method not allowed")
+            .with_status_code(405),
+        _ => Response::from_string("Not found").with_status_code(404)
+    };
+    request.respond(response)
+}
+
+/// Answer requests on `listen` until stopped by SIGTERM or SIGINT.
+pub fn serve(run: &Run, listen: &str) -> anyhow::Result<()> {
+    if matches!(run.placer.action, place::Action::Symlink | place::Action::Tag) {
+        bail!("Uploaded documents cannot be filed with --action {}, use move, copy or \
+            hardlink", run.placer.action.verb());
+    }
+    // Only readable by this user, and removed when dropped.
+    let uploads = tempfile::Builder::new().prefix("classy-").tempdir()
+        .context("Failed to create a directory for uploads")?;
+    let server = tiny_http::Server::http(listen)
+        .map_err(|error| anyhow!("Failed to listen on {}: {}", listen, error))?;
+    let stop = crate::watch::stop_flag()?;
+    log::info!("Listening on http://{}", listen);
+    while !stop.load(Ordering::Relaxed) {
+        match server.recv_timeout(Duration::from_millis(200)) {
+            Ok(Some(request)) => {
+                if let Err(error) = handle(run, request, uploads.path()) {
+                    log::error!("Failed to respond: {}", error);
+                }
+                if let Err(error) = run.save() {
+                    log::error!("{:#}", error);
+                }
+                run.post_summary();
+            }
+            Ok(None) => {}
+            Err(error) => log::error!("Failed to receive a request: {}", error)
+        }
+    }
+    log::info!("Stopped listening on {}", listen);
+    uploads.close().context("Failed to remove the uploads directory")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_query_parameters() {
+        let (path, parameters) = parse_url("/classify?store=true&name=Scan%201+b.pdf&flag");
+        assert_eq!(path, "/classify");
+        assert_eq!(parameters, [("store".to_string(), "true".to_string()),
+            ("name".to_string(), "Scan 1 b.pdf".to_string()),
+            ("flag".to_string(), String::new())]);
+        assert_eq!(parse_url("/health"), ("/health", Vec::new()));
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz%41"), "%zzA");
+    }
+
+    #[test]
+    fn stores_only_when_asked() {
+        assert!(store("/classify?store=true"));
+        assert!(store("/classify?name=a.pdf&store=1"));
+        assert!(!store("/classify?store=false"));
+        assert!(!store("/classify"));
+    }
+}
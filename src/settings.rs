@@ -0,0 +1,897 @@
+//! Loading of the configuration file.
+//!
+//! The file is written in YAML, TOML or JSON, chosen by its extension. It
+//! is either a list of directories (the original format) or a map of
+//! settings with the directories under `layout`. Settings may `include`
+//! other configuration files, whose directories are appended to the
+//! layout, and define named `profiles` in either form. The directories of
+//! the selected profile are appended to the layout and its settings
+//! replace the top-level ones.
+//!
+//! Keywords shared by several directories can be listed once under
+//! `keyword_sets` and added to each directory with `use`.
+//!
+//! Unmatched files may be parked in a `holding_dir` of the output, where
+//! every run classifies them again, and are reported once they have waited
+//! longer than `holding_retention`.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::{
+    filter, paths, place, rename, ClassifierPath, ClassifierPaths, Config, KeywordSets, Near
+};
+
+/// Configuration file names looked up in the configuration directory,
+/// in order of preference.
+const NAMES: &[&str] = &["config.yml", "config.yaml", "config.toml", "config.json"];
+
+/// Names of the files adding directories for the files of the input
+/// directory they are in, in order of preference.
+pub const OVERRIDE_NAMES: &[&str] = &[
+    ".classy.yml", ".classy.yaml", ".classy.toml", ".classy.json"
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json
+}
+
+impl Format {
+    pub fn of(path: &Path) -> Format {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("json") => Format::Json,
+            _ => Format::Yaml
+        }
+    }
+}
+
+/// Configuration file in `dir`: the first one existing, or `config.yml`.
+pub fn default_path(dir: &Path) -> PathBuf {
+    NAMES.iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(NAMES[0]))
+}
+
+/// A value written either as a string or as a number, so that
+/// `mode: 640` and `mode: "0640"` are read the same way.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Scalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool)
+}
+
+impl std::fmt::Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Scalar::String(s) => write!(f, "{}", s),
+            Scalar::Integer(i) => write!(f, "{}", i),
+            Scalar::Float(r) => write!(f, "{}", r),
+            Scalar::Boolean(b) => write!(f, "{}", b)
+        }
+    }
+}
+
+/// A keyword, either alone or with its options.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Keyword {
+    Plain(Scalar),
+    Options(KeywordOptions)
+}
+
+/// `{ word: acme, case: insensitive, whole_word: false, fuzzy: 1 }`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeywordOptions {
+    word: Scalar,
+    case: Option<Case>,
+    whole_word: Option<bool>,
+    fuzzy: Option<Scalar>
+}
+
+#[derive(Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Case {
+    Sensitive,
+    Insensitive
+}
+
+impl Keyword {
+    fn word(&self) -> String {
+        match self {
+            Keyword::Plain(word) | Keyword::Options(KeywordOptions { word, .. }) =>
+                word.to_string()
+        }
+    }
+
+    fn options(&self) -> anyhow::Result<crate::KeywordOptions> {
+        let mut options = crate::KeywordOptions::default();
+        if let Keyword::Options(keyword) = self {
+            options.ignore_case = keyword.case == Some(Case::Insensitive);
+            options.whole_word = keyword.whole_word.unwrap_or(true);
+            options.fuzzy = keyword.fuzzy.as_ref().map(parse_fuzzy).transpose()?;
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Directory {
+    dir: Scalar,
+    #[serde(default)]
+    keywords: Vec<Keyword>,
+    /// Names of the keyword sets added to `keywords`.
+    #[serde(rename = "use")]
+    uses: Option<Uses>,
+    owner: Option<Scalar>,
+    group: Option<Scalar>,
+    mode: Option<Scalar>,
+    dir_mode: Option<Scalar>,
+    rename: Option<String>,
+    slugify: Option<bool>,
+    doctype: Option<Scalar>,
+    fuzzy: Option<Scalar>,
+    #[serde(default)]
+    near: Vec<Proximity>,
+    pages: Option<Pages>,
+    #[serde(default)]
+    types: Vec<Scalar>,
+    min_matches: Option<usize>,
+    exec: Option<Exec>,
+    #[serde(default)]
+    pub sub: Vec<Directory>
+}
+
+impl Directory {
+    /// Name of the directory, as written.
+    pub fn name(&self) -> String {
+        self.dir.to_string()
+    }
+}
+
+/// A command, such as `exec: notify {destination}` or
+/// `exec: [notify, "{destination}"]`.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Exec {
+    Line(String),
+    Args(Vec<String>)
+}
+
+pub fn exec_args(exec: Exec) -> Vec<String> {
+    match exec {
+        Exec::Line(line) => vec![line],
+        Exec::Args(args) => args
+    }
+}
+
+/// `use: banks` or `use: [banks, insurers]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Uses {
+    One(String),
+    Many(Vec<String>)
+}
+
+/// `pages: last` or `pages: [1, "3-4"]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Pages {
+    One(Scalar),
+    Many(Vec<Scalar>)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Proximity {
+    keywords: Vec<Scalar>,
+    within: usize
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    ambiguous_dir: Option<PathBuf>,
+    unmatched_dir: Option<PathBuf>,
+    /// Where unmatched files wait to be classified again, relative to the
+    /// output directory.
+    holding_dir: Option<PathBuf>,
+    /// How long unmatched files may wait before being reported, such as
+    /// "30d".
+    pub holding_retention: Option<String>,
+    errors_dir: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+    /// SQLite database of classified files.
+    index: Option<PathBuf>,
+    /// Files to merge, relative to the including file.
+    #[serde(default)]
+    include: Vec<PathBuf>,
+    /// Globs of the input files to classify.
+    #[serde(default)]
+    pub include_files: Vec<String>,
+    /// Globs of the input files and directories to skip.
+    #[serde(default)]
+    pub exclude_files: Vec<String>,
+    pub fold_diacritics: Option<bool>,
+    /// Order of the day and month tried for numeric dates such as
+    /// 03/04/2023.
+    date_order: Option<Vec<crate::date::Order>>,
+    /// URL notified of filed documents.
+    pub webhook: Option<String>,
+    webhook_batch: Option<bool>,
+    /// Cron-like times of the sweeps in watch mode.
+    pub schedule: Option<String>,
+    /// Mailbox attachments are fetched from.
+    imap: Option<crate::mail::Account>,
+    /// Names of the extraction backends of each file type, by extension.
+    #[serde(default)]
+    pub extraction: BTreeMap<String, Vec<String>>,
+    /// Commands printing the text of documents, by name.
+    #[serde(default)]
+    pub extractors: BTreeMap<String, Exec>,
+    /// Keywords directories share, by name.
+    #[serde(default)]
+    pub keyword_sets: BTreeMap<String, Vec<Keyword>>,
+    #[serde(default)]
+    pub layout: Vec<Directory>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>
+}
+
+#[derive(Deserialize)]
+#[serde(from = "Root")]
+pub struct Profile(pub Settings);
+
+impl From<Root> for Profile {
+    fn from(root: Root) -> Profile {
+        Profile(root.into())
+    }
+}
+
+enum Root {
+    Layout(Vec<Directory>),
+    Settings(Box<Settings>)
+}
+
+impl From<Root> for Settings {
+    fn from(root: Root) -> Settings {
+        match root {
+            Root::Layout(layout) => Settings { layout, ..Default::default() },
+            Root::Settings(settings) => *settings
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Root {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Root, D::Error> {
+        struct RootVisitor;
+
+        impl<'de> Visitor<'de> for RootVisitor {
+            type Value = Root;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a list of directories or a map of settings")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Root, A::Error> {
+                Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))
+                    .map(Root::Layout)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Root, A::Error> {
+                Settings::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(|settings| Root::Settings(Box::new(settings)))
+            }
+        }
+
+        deserializer.deserialize_any(RootVisitor)
+    }
+}
+
+/// Error of a configuration file that cannot be parsed, with the line it
+/// was found on.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    message: String
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse `content`, written in `format`, without the files it includes.
+pub fn parse(content: &str, format: Format) -> Result<Settings, ParseError> {
+    let root: Result<Root, ParseError> = match format {
+        Format::Yaml => serde_yaml::from_str(content).map_err(|error| ParseError {
+            line: error.location().map(|location| location.line()),
+            message: error.to_string()
+        }),
+        Format::Toml => toml::from_str(content).map_err(|error| ParseError {
+            line: error.span().map(|span| content[..span.start].matches('\n').count() + 1),
+            message: error.to_string()
+        }),
+        Format::Json => serde_json::from_str(content).map_err(|error| ParseError {
+            line: Some(error.line()).filter(|line| *line > 0),
+            message: error.to_string()
+        })
+    };
+    root.map(Into::into)
+}
+
+pub fn to_str(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).with_context(|| {
+        format!("Failed to read configuration file '{}'", path.display())
+    })
+}
+
+/// Read the settings in `path` and the files it includes. `including`
+/// holds the files being read, to detect include cycles, and `sources`
+/// receives every file read.
+fn read(path: &Path, including: &mut Vec<PathBuf>, sources: &mut Vec<PathBuf>)
+    -> anyhow::Result<Settings> {
+    let canonical = path.canonicalize().with_context(|| {
+        format!("Failed to read configuration file '{}'", path.display())
+    })?;
+    if including.contains(&canonical) {
+        bail!("Configuration file '{}' includes itself", path.display());
+    }
+    let content = to_str(path)?;
+    let mut settings = parse(&content, Format::of(path)).with_context(|| {
+        format!("Failed to parse configuration file '{}'", path.display())
+    })?;
+    sources.push(canonical.clone());
+    including.push(canonical);
+    resolve(&mut settings, path, including, sources)?;
+    for (name, profile) in settings.profiles.iter_mut() {
+        if !profile.0.profiles.is_empty() {
+            bail!("Profile '{}' cannot define profiles itself", name);
+        }
+        resolve(&mut profile.0, path, including, sources)
+            .with_context(|| format!("In profile '{}'", name))?;
+    }
+    including.pop();
+    Ok(settings)
+}
+
+/// Expand the paths of `settings`, read from `path`, and merge the files
+/// it includes.
+fn resolve(settings: &mut Settings, path: &Path, including: &mut Vec<PathBuf>,
+    sources: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for path in [&mut settings.ambiguous_dir, &mut settings.unmatched_dir,
+        &mut settings.holding_dir, &mut settings.errors_dir, &mut settings.audit_log,
+        &mut settings.index].into_iter()
+        .flatten().chain(settings.include.iter_mut()) {
+        *path = paths::expand(path)?;
+    }
+    let base = path.parent().unwrap_or(Path::new(""));
+    for include in std::mem::take(&mut settings.include) {
+        let included = read(&base.join(&include), including, sources).with_context(|| {
+            format!("Failed to include '{}' from '{}'", include.display(), path.display())
+        })?;
+        settings.layout.extend(included.layout);
+        settings.ambiguous_dir = settings.ambiguous_dir.take().or(included.ambiguous_dir);
+        settings.unmatched_dir = settings.unmatched_dir.take().or(included.unmatched_dir);
+        settings.holding_dir = settings.holding_dir.take().or(included.holding_dir);
+        settings.holding_retention =
+            settings.holding_retention.take().or(included.holding_retention);
+        settings.errors_dir = settings.errors_dir.take().or(included.errors_dir);
+        settings.audit_log = settings.audit_log.take().or(included.audit_log);
+        settings.index = settings.index.take().or(included.index);
+        settings.fold_diacritics = settings.fold_diacritics.or(included.fold_diacritics);
+        settings.date_order = settings.date_order.take().or(included.date_order);
+        settings.webhook = settings.webhook.take().or(included.webhook);
+        settings.webhook_batch = settings.webhook_batch.or(included.webhook_batch);
+        settings.schedule = settings.schedule.take().or(included.schedule);
+        settings.imap = settings.imap.take().or(included.imap);
+        for (extension, chain) in included.extraction {
+            settings.extraction.entry(extension).or_insert(chain);
+        }
+        for (name, command) in included.extractors {
+            settings.extractors.entry(name).or_insert(command);
+        }
+        for (name, keywords) in included.keyword_sets {
+            settings.keyword_sets.entry(name).or_insert(keywords);
+        }
+        if settings.include_files.is_empty() {
+            settings.include_files = included.include_files;
+        }
+        if settings.exclude_files.is_empty() {
+            settings.exclude_files = included.exclude_files;
+        }
+        for (name, profile) in included.profiles {
+            settings.profiles.entry(name).or_insert(profile);
+        }
+    }
+    Ok(())
+}
+
+/// Settings of the configuration file at `path`, with the files it
+/// includes.
+pub fn settings(path: &Path) -> anyhow::Result<Settings> {
+    read(path, &mut Vec::new(), &mut Vec::new())
+}
+
+/// Names of the profiles defined in the configuration file at `path`.
+pub fn profiles(path: &Path) -> anyhow::Result<Vec<String>> {
+    Ok(settings(path)?.profiles.into_keys().collect())
+}
+
+/// Read the configuration file at `path`, with the directories and
+/// settings of `profile` if given.
+pub fn load(path: &Path, profile: Option<&str>) -> anyhow::Result<Config> {
+    let mut sources = Vec::new();
+    let mut settings = read(path, &mut Vec::new(), &mut sources)?;
+    match profile {
+        Some(name) => {
+            let Profile(profile) = match settings.profiles.remove(name) {
+                Some(profile) => profile,
+                None => bail!("No profile '{}' in '{}', expected one of: {}", name,
+                    path.display(), settings.profiles.keys().cloned()
+                        .collect::<Vec<_>>().join(", "))
+            };
+            settings.layout.extend(profile.layout);
+            settings.ambiguous_dir = profile.ambiguous_dir.or(settings.ambiguous_dir);
+            settings.unmatched_dir = profile.unmatched_dir.or(settings.unmatched_dir);
+            settings.holding_dir = profile.holding_dir.or(settings.holding_dir);
+            settings.holding_retention =
+                profile.holding_retention.or(settings.holding_retention);
+            settings.errors_dir = profile.errors_dir.or(settings.errors_dir);
+            settings.audit_log = profile.audit_log.or(settings.audit_log);
+            settings.index = profile.index.or(settings.index);
+            settings.fold_diacritics = profile.fold_diacritics.or(settings.fold_diacritics);
+            settings.date_order = profile.date_order.or(settings.date_order);
+            settings.webhook = profile.webhook.or(settings.webhook);
+            settings.webhook_batch = profile.webhook_batch.or(settings.webhook_batch);
+            settings.schedule = profile.schedule.or(settings.schedule);
+            settings.imap = profile.imap.or(settings.imap);
+            settings.extraction.extend(profile.extraction);
+            settings.extractors.extend(profile.extractors);
+            settings.keyword_sets.extend(profile.keyword_sets);
+            if !profile.include_files.is_empty() {
+                settings.include_files = profile.include_files;
+            }
+            if !profile.exclude_files.is_empty() {
+                settings.exclude_files = profile.exclude_files;
+            }
+        }
+        None if settings.layout.is_empty() && !settings.profiles.is_empty() => {
+            bail!("Select a profile of '{}' with --profile: {}", path.display(),
+                settings.profiles.keys().cloned().collect::<Vec<_>>().join(", "))
+        }
+        None => {}
+    }
+    let fold_diacritics = settings.fold_diacritics.unwrap_or(false);
+    let mut commands = BTreeMap::new();
+    for (name, command) in settings.extractors {
+        let hook = crate::hook::parse(exec_args(command))
+            .with_context(|| format!("In 'extractors' of '{}'", name))?;
+        commands.insert(name, hook.args().to_vec());
+    }
+    let extraction = crate::extract::chains(&settings.extraction, &commands)?;
+    let keyword_sets = keyword_sets(&settings.keyword_sets)?;
+    Ok(Config {
+        paths: layout_paths(&settings.layout, fold_diacritics, &keyword_sets)?,
+        ambiguous_dir: settings.ambiguous_dir,
+        unmatched_dir: settings.unmatched_dir,
+        holding_dir: settings.holding_dir,
+        holding_retention: settings.holding_retention.as_deref()
+            .map(filter::parse_duration).transpose()
+            .map_err(|error| anyhow::anyhow!("Invalid 'holding_retention': {}", error))?,
+        errors_dir: settings.errors_dir,
+        audit_log: settings.audit_log,
+        index: settings.index,
+        filter: filter::Filter {
+            include: filter::patterns(&settings.include_files)?,
+            exclude: filter::patterns(&settings.exclude_files)?,
+            extensions: extraction.keys().cloned().collect(),
+            ..Default::default()
+        },
+        extraction,
+        fold_diacritics,
+        date_order: crate::date::Order::resolve(&settings.date_order.unwrap_or_default()),
+        webhook: settings.webhook.map(|url| crate::webhook::Webhook {
+            url,
+            batch: settings.webhook_batch.unwrap_or(false)
+        }),
+        model: None,
+        similar: None,
+        imap: settings.imap,
+        schedule: settings.schedule.as_deref().map(str::parse).transpose()?,
+        keyword_sets,
+        min_confidence: None,
+        sources
+    })
+}
+
+/// Override file in the input directory `dir`, if any.
+pub fn find_override(dir: &Path) -> Option<PathBuf> {
+    OVERRIDE_NAMES.iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Settings an override file may not set, as anyone able to write in an
+/// input directory could otherwise run commands or change where and how
+/// files are written.
+fn global_settings(settings: &Settings) -> Vec<&'static str> {
+    // No `..`, so that a new setting has to be sorted here.
+    let Settings {
+        ambiguous_dir, unmatched_dir, holding_dir, holding_retention, errors_dir, audit_log,
+        index, include, include_files, exclude_files, fold_diacritics, date_order, webhook,
+        webhook_batch, schedule, imap, extraction, extractors, keyword_sets: _, layout: _,
+        profiles
+    } = settings;
+    [
+        ("ambiguous_dir", ambiguous_dir.is_some()),
+        ("unmatched_dir", unmatched_dir.is_some()),
+        ("holding_dir", holding_dir.is_some()),
+        ("holding_retention", holding_retention.is_some()),
+        ("errors_dir", errors_dir.is_some()),
+        ("audit_log", audit_log.is_some()),
+        ("index", index.is_some()),
+        ("include", !include.is_empty()),
+        ("include_files", !include_files.is_empty()),
+        ("exclude_files", !exclude_files.is_empty()),
+        ("fold_diacritics", fold_diacritics.is_some()),
+        ("date_order", date_order.is_some()),
+        ("webhook", webhook.is_some()),
+        ("webhook_batch", webhook_batch.is_some()),
+        ("schedule", schedule.is_some()),
+        ("imap", imap.is_some()),
+        ("extraction", !extraction.is_empty()),
+        ("extractors", !extractors.is_empty()),
+        ("profiles", !profiles.is_empty())
+    ].into_iter().filter(|(_, set)| *set).map(|(key, _)| key).collect()
+}
+
+/// Check that the directories of `layout`, below `parent`, of the override
+/// file `path` set neither commands nor ownership.
+fn check_override_layout(layout: &[Directory], parent: &Path, path: &Path)
+    -> anyhow::Result<()> {
+    for dir in layout {
+        let name = parent.join(dir.name());
+        let keys = [
+            ("exec", dir.exec.is_some()),
+            ("owner", dir.owner.is_some()),
+            ("group", dir.group.is_some()),
+            ("mode", dir.mode.is_some()),
+            ("dir_mode", dir.dir_mode.is_some())
+        ];
+        if let Some((key, _)) = keys.iter().find(|(_, set)| *set) {
+            bail!("'{}' may not set '{}' of {:?}", path.display(), key, name);
+        }
+        check_override_layout(&dir.sub, &name, path)?;
+    }
+    Ok(())
+}
+
+/// `config` with the directories of the override file at `path`. They
+/// replace the directories of `config` with the same path and the others
+/// are appended. They may use the keyword sets of `config` and of the
+/// override file, but may not run commands, set ownership or include other
+/// files.
+pub fn apply_override(config: &Config, path: &Path) -> anyhow::Result<Config> {
+    let settings = parse(&to_str(path)?, Format::of(path)).with_context(|| {
+        format!("Failed to parse configuration file '{}'", path.display())
+    })?;
+    let global = global_settings(&settings);
+    if !global.is_empty() {
+        bail!("'{}' may only define directories and keyword sets, not {}", path.display(),
+            global.iter().map(|key| format!("'{}'", key)).collect::<Vec<_>>().join(", "));
+    }
+    check_override_layout(&settings.layout, Path::new(""), path)?;
+    let mut config = config.clone();
+    config.keyword_sets.extend(keyword_sets(&settings.keyword_sets)?);
+    for rule in layout_paths(&settings.layout, config.fold_diacritics, &config.keyword_sets)? {
+        match config.paths.iter_mut().find(|existing| existing.path == rule.path) {
+            Some(existing) => *existing = rule,
+            None => config.paths.push(rule)
+        }
+    }
+    Ok(config)
+}
+
+fn parse_fuzzy(value: &Scalar) -> anyhow::Result<usize> {
+    value.to_string().parse().with_context(|| {
+        format!("Invalid fuzzy '{}', expected a number of edits", value)
+    })
+}
+
+/// Keywords of the `keyword_sets` of a configuration, by name.
+pub fn keyword_sets(sets: &BTreeMap<String, Vec<Keyword>>) -> anyhow::Result<KeywordSets> {
+    sets.iter()
+        .map(|(name, keywords)| {
+            let keywords = keywords.iter()
+                .map(|keyword| Ok((keyword.word(), keyword.options()?)))
+                .collect::<anyhow::Result<_>>()
+                .with_context(|| format!("In keyword set '{}'", name))?;
+            Ok((name.clone(), keywords))
+        })
+        .collect()
+}
+
+/// Rules for `layout`, with their keywords compiled.
+fn layout_paths(layout: &[Directory], fold_diacritics: bool, sets: &KeywordSets)
+    -> anyhow::Result<ClassifierPaths> {
+    let mut error = None;
+    let paths = rules(layout, fold_diacritics, sets, &mut |_, invalid| {
+        error.get_or_insert(invalid);
+    });
+    match error {
+        Some(error) => Err(error),
+        None => Ok(paths)
+    }
+}
+
+/// Rules for `layout`, with their keywords compiled, subdirectories
+/// inheriting the keywords and settings of their parent. Directories that
+/// cannot be read are passed to `invalid` with the error, and left out
+/// with everything below them.
+pub fn rules(layout: &[Directory], fold_diacritics: bool, sets: &KeywordSets,
+    invalid: &mut dyn FnMut(&Path, anyhow::Error)) -> ClassifierPaths {
+    let mut paths = Default::default();
+    directories(layout, None, fold_diacritics, sets, &mut paths, invalid);
+    paths
+}
+
+fn directories(layout: &[Directory], parent: Option<&ClassifierPath>, fold_diacritics: bool,
+    sets: &KeywordSets, paths: &mut ClassifierPaths,
+    invalid: &mut dyn FnMut(&Path, anyhow::Error)) {
+    for dir in layout {
+        let mut path = match directory(dir, sets) {
+            Ok(path) => path,
+            Err(error) => {
+                let path = parent.map_or_else(|| PathBuf::from(dir.name()),
+                    |parent| parent.path.join(dir.name()));
+                invalid(&path, error);
+                continue;
+            }
+        };
+        if let Some(parent) = parent {
+            inherit(&mut path, parent);
+        }
+        if let Err(error) = path.compile(fold_diacritics) {
+            invalid(&path.path, error);
+            continue;
+        }
+        paths.push(path.clone());
+        directories(&dir.sub, Some(&path), fold_diacritics, sets, paths, invalid);
+    }
+}
+
+/// Rule for `dir` alone, with the keywords of the sets it uses.
+fn directory(dir: &Directory, sets: &KeywordSets) -> anyhow::Result<ClassifierPath> {
+    let name = PathBuf::from(paths::expand_str(&dir.dir.to_string())?);
+    // Files are never written outside of the output directory.
+    if name.components().any(|part| !matches!(part, Component::Normal(_) | Component::CurDir)) {
+        bail!("Directory {:?} should be relative to the output directory, without '..'",
+            name);
+    }
+    let mut path = ClassifierPath {
+        path: name,
+        keywords: dir.keywords.iter().map(Keyword::word).collect(),
+        keyword_options: dir.keywords.iter()
+            .map(Keyword::options)
+            .collect::<anyhow::Result<_>>()?,
+        ..Default::default()
+    };
+    let uses = match &dir.uses {
+        Some(Uses::One(name)) => vec![name.clone()],
+        Some(Uses::Many(names)) => names.clone(),
+        None => Vec::new()
+    };
+    for name in uses {
+        let set = sets.get(&name).with_context(|| format!(
+            "Unknown keyword set '{}' in {:?}, expected one of: {}", name, path.path,
+            sets.keys().cloned().collect::<Vec<_>>().join(", ")))?;
+        for (keyword, options) in set {
+            path.keywords.push(keyword.clone());
+            path.keyword_options.push(*options);
+        }
+    }
+    path.permissions.owner = dir.owner.as_ref().map(Scalar::to_string);
+    path.permissions.group = dir.group.as_ref().map(Scalar::to_string);
+    if let Some(mode) = &dir.mode {
+        path.permissions.mode = Some(place::parse_mode(&mode.to_string())?);
+    }
+    if let Some(mode) = &dir.dir_mode {
+        path.permissions.dir_mode = Some(place::parse_mode(&mode.to_string())?);
+    }
+    if let Some(template) = &dir.rename {
+        rename::validate(template)?;
+        path.rename = Some(template.clone());
+    }
+    path.slugify = dir.slugify;
+    if let Some(doctype) = &dir.doctype {
+        path.doctype = Some(doctype.to_string().parse()?);
+    }
+    if let Some(fuzzy) = &dir.fuzzy {
+        path.fuzzy = Some(parse_fuzzy(fuzzy)?);
+    }
+    if let Some(pages) = &dir.pages {
+        let items: Vec<String> = match pages {
+            Pages::One(page) => vec![page.to_string()],
+            Pages::Many(pages) => pages.iter().map(Scalar::to_string).collect()
+        };
+        path.pages = crate::pages::parse(&items)
+            .with_context(|| format!("In 'pages' of {:?}", path.path))?;
+    }
+    path.min_matches = dir.min_matches;
+    if let Some(exec) = &dir.exec {
+        path.exec = Some(crate::hook::parse(exec_args(exec.clone()))
+            .with_context(|| format!("In 'exec' of {:?}", path.path))?);
+    }
+    path.types = dir.types.iter()
+        .map(|file_type| file_type.to_string().trim_start_matches('.').to_lowercase())
+        .collect();
+    for near in dir.near.iter() {
+        let keywords = match &near.keywords[..] {
+            [first, second] => [first.to_string(), second.to_string()],
+            _ => bail!("'near' of {:?} should list two keywords", path.path)
+        };
+        path.near.push(Near { keywords, within: near.within, ..Default::default() });
+    }
+    Ok(path)
+}
+
+/// Place `it` below `parent`, with the keywords of `parent` and its
+/// settings where `it` has none.
+fn inherit(it: &mut ClassifierPath, parent: &ClassifierPath) {
+    it.path = parent.path.join(&it.path);
+    it.keywords.extend(parent.keywords.clone());
+    it.keyword_options.extend(parent.keyword_options.clone());
+    it.near.extend(parent.near.clone());
+    it.permissions.inherit(&parent.permissions);
+    if it.rename.is_none() {
+        it.rename = parent.rename.clone();
+    }
+    if it.slugify.is_none() {
+        it.slugify = parent.slugify;
+    }
+    if it.doctype.is_none() {
+        it.doctype = parent.doctype;
+    }
+    if it.fuzzy.is_none() {
+        it.fuzzy = parent.fuzzy;
+    }
+    if it.pages.is_empty() {
+        it.pages = parent.pages.clone();
+    }
+    if it.types.is_empty() {
+        it.types = parent.types.clone();
+    }
+    if it.min_matches.is_none() {
+        it.min_matches = parent.min_matches;
+    }
+    if it.exec.is_none() {
+        it.exec = parent.exec.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `content` to `name` in `dir` and return its path.
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn paths(config: &Config) -> Vec<&Path> {
+        config.paths.iter().map(|rule| rule.path.as_path()).collect()
+    }
+
+    /// Error of the override file `content` applied to a configuration
+    /// with an `Acme` directory.
+    fn override_error(content: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yml",
+            "layout:\n  - dir: Acme\n    keywords: [acme]\n");
+        let config = load(&main, None).unwrap();
+        let path = write(dir.path(), ".classy.yml", content);
+        match apply_override(&config, &path) {
+            Ok(_) => panic!("{:?} was accepted", content),
+            Err(error) => format!("{:#}", error)
+        }
+    }
+
+    #[test]
+    fn loads_included_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let included = write(dir.path(), "banks.toml",
+            "unmatched_dir = \"Unsorted\"\n[[layout]]\ndir = \"Bank\"\nkeywords = [\"iban\"]\n");
+        let main = write(dir.path(), "config.yml",
+            "include: [banks.toml]\nlayout:\n  - dir: Acme\n    keywords: [acme]\n");
+        let config = load(&main, None).unwrap();
+        assert_eq!(paths(&config), [Path::new("Acme"), Path::new("Bank")]);
+        assert_eq!(config.unmatched_dir.as_deref(), Some(Path::new("Unsorted")));
+        assert_eq!(config.sources, [main.canonicalize().unwrap(),
+            included.canonicalize().unwrap()]);
+    }
+
+    #[test]
+    fn rejects_include_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yml", "include: [other.yml]\nlayout: []\n");
+        write(dir.path(), "other.yml", "include: [config.yml]\nlayout: []\n");
+        let error = load(&main, None).err().unwrap();
+        assert!(format!("{:#}", error).contains("includes itself"), "{:#}", error);
+    }
+
+    #[test]
+    fn overrides_replace_and_add_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yml",
+            "layout:\n  - dir: Acme\n    keywords: [acme]\n  - dir: Bank\n    keywords: [iban]\n");
+        let config = load(&main, None).unwrap();
+        let path = write(dir.path(), ".classy.yml", "keyword_sets:\n  tax: [vat]\nlayout:\n  \
+            - dir: Acme\n    keywords: [acmeco]\n  - dir: Tax\n    use: tax\n");
+        let overridden = apply_override(&config, &path).unwrap();
+        assert_eq!(paths(&overridden), [Path::new("Acme"), Path::new("Bank"), Path::new("Tax")]);
+        assert_eq!(overridden.paths[0].keywords, ["acmeco"]);
+        assert_eq!(overridden.paths[2].keywords, ["vat"]);
+        // The configuration itself is left as it was.
+        assert_eq!(config.paths[0].keywords, ["acme"]);
+    }
+
+    #[test]
+    fn overrides_reject_global_settings() {
+        for (content, key) in [
+            ("include: [other.yml]\nlayout: []\n", "'include'"),
+            ("webhook: http://localhost/hook\nlayout: []\n", "'webhook'"),
+            ("unmatched_dir: /tmp\nlayout: []\n", "'unmatched_dir'"),
+            ("extractors:\n  ocr2: [ocr, '{file}']\nlayout: []\n", "'extractors'"),
+            ("schedule: '0 3 * * *'\nlayout: []\n", "'schedule'")
+        ] {
+            let error = override_error(content);
+            assert!(error.contains("may only define directories and keyword sets"), "{}", error);
+            assert!(error.contains(key), "{}", error);
+        }
+    }
+
+    #[test]
+    fn overrides_reject_commands_and_ownership() {
+        for key in ["exec: [touch, /tmp/owned]", "owner: root", "group: wheel", "mode: 777",
+            "dir_mode: 777"] {
+            let content = format!("layout:\n  - dir: Acme\n    sub:\n      - dir: Mine\n        \
+                {}\n", key);
+            let error = override_error(&content);
+            let name = key.split(':').next().unwrap();
+            assert!(error.contains(&format!("may not set '{}' of \"Acme/Mine\"", name)),
+                "{}", error);
+        }
+    }
+
+    #[test]
+    fn directories_stay_in_the_output() {
+        for dir in ["/etc", "../outside", "Acme/../../outside"] {
+            let error = override_error(&format!("layout:\n  - dir: {}\n    keywords: [x]\n", dir));
+            assert!(error.contains("should be relative to the output directory"), "{}", error);
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config.yml", "layout:\n  - dir: ../outside\n");
+        assert!(load(&main, None).is_err());
+        let main = write(dir.path(), "config.yml", "layout:\n  - dir: ./Acme/Invoices\n");
+        assert!(load(&main, None).is_ok());
+    }
+}
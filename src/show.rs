@@ -0,0 +1,165 @@
+//! The rules and settings the configuration resolves to, as printed by
+//! `classy config show --resolved`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::output::Format;
+use crate::{ClassifierPath, Config, KeywordOptions};
+
+#[derive(Serialize)]
+struct Near<'c> {
+    keywords: &'c [String; 2],
+    within: usize
+}
+
+/// Keyword alone, or with the options it does not share with the others.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Keyword<'c> {
+    Plain(&'c str),
+    Options {
+        word: &'c str,
+        case: &'static str,
+        whole_word: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fuzzy: Option<usize>
+    }
+}
+
+fn keyword(word: &str, options: KeywordOptions) -> Keyword<'_> {
+    if options == KeywordOptions::default() {
+        return Keyword::Plain(word);
+    }
+    Keyword::Options {
+        word,
+        case: if options.ignore_case { "insensitive" } else { "sensitive" },
+        whole_word: options.whole_word,
+        fuzzy: options.fuzzy
+    }
+}
+
+/// Rule of a single directory, relative to the output directory.
+#[derive(Serialize)]
+struct Rule<'c> {
+    dir: &'c Path,
+    keywords: Vec<Keyword<'c>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    near: Vec<Near<'c>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_matches: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fuzzy: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pages: Vec<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    types: &'c [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doctype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rename: Option<&'c str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slugify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<&'c str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'c str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dir_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exec: Option<&'c [String]>
+}
+
+#[derive(Serialize)]
+struct Resolved<'c> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ambiguous_dir: Option<&'c Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unmatched_dir: Option<&'c Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    holding_dir: Option<&'c Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    holding_retention: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors_dir: Option<&'c Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log: Option<&'c Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<&'c Path>,
+    fold_diacritics: bool,
+    date_order: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    include_files: Vec<&'c str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    exclude_files: Vec<&'c str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    webhook: Option<&'c str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedule: Option<String>,
+    extraction: BTreeMap<&'c str, Vec<String>>,
+    /// In the order they are tried.
+    rules: Vec<Rule<'c>>
+}
+
+fn mode(mode: Option<u32>) -> Option<String> {
+    mode.map(|mode| format!("{:04o}", mode))
+}
+
+fn rule(path: &ClassifierPath) -> Rule<'_> {
+    Rule {
+        dir: &path.path,
+        keywords: path.keywords.iter().enumerate()
+            .map(|(index, word)| {
+                keyword(word, path.keyword_options.get(index).copied().unwrap_or_default())
+            })
+            .collect(),
+        near: path.near.iter()
+            .map(|near| Near { keywords: &near.keywords, within: near.within })
+            .collect(),
+        min_matches: path.min_matches,
+        fuzzy: path.fuzzy,
+        pages: path.pages.iter().map(|page| page.to_string()).collect(),
+        types: &path.types,
+        doctype: path.doctype.map(|doctype| doctype.to_string()),
+        rename: path.rename.as_deref(),
+        slugify: path.slugify,
+        owner: path.permissions.owner.as_deref(),
+        group: path.permissions.group.as_deref(),
+        mode: mode(path.permissions.mode),
+        dir_mode: mode(path.permissions.dir_mode),
+        exec: path.exec.as_ref().map(|hook| hook.args())
+    }
+}
+
+/// Print `config` in YAML, or in JSON with `format`.
+pub fn resolved(config: &Config, format: Format) -> anyhow::Result<()> {
+    let resolved = Resolved {
+        ambiguous_dir: config.ambiguous_dir.as_deref(),
+        unmatched_dir: config.unmatched_dir.as_deref(),
+        holding_dir: config.holding_dir.as_deref(),
+        holding_retention: config.holding_retention.map(crate::filter::format_duration),
+        errors_dir: config.errors_dir.as_deref(),
+        audit_log: config.audit_log.as_deref(),
+        index: config.index.as_deref(),
+        fold_diacritics: config.fold_diacritics,
+        date_order: config.date_order.iter().map(|order| order.to_string()).collect(),
+        include_files: config.filter.include.iter().map(|glob| glob.as_str()).collect(),
+        exclude_files: config.filter.exclude.iter().map(|glob| glob.as_str()).collect(),
+        webhook: config.webhook.as_ref().map(|webhook| webhook.url.as_str()),
+        schedule: config.schedule.as_ref().map(|schedule| schedule.to_string()),
+        extraction: config.extraction.iter()
+            .map(|(extension, chain)| (extension.as_str(),
+                chain.iter().map(|kind| kind.to_string()).collect()))
+            .collect(),
+        rules: config.paths.iter().map(rule).collect()
+    };
+    match format {
+        Format::Text => print!("{}", serde_yaml::to_string(&resolved)?),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&resolved)?)
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! Classification of documents no rule matches by their similarity with
+//! the documents already filed in the output tree.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cache;
+use crate::extract::Extractor;
+use crate::{model, ClassifierPath, ClassifierPaths};
+
+/// Token counts scaled to a unit length.
+type Vector = HashMap<String, f64>;
+
+fn vector(text: &str) -> Vector {
+    let mut vector: Vector = HashMap::new();
+    for token in model::tokens(text) {
+        *vector.entry(token).or_default() += 1.0;
+    }
+    let length = vector.values().map(|count| count * count).sum::<f64>().sqrt();
+    for count in vector.values_mut() {
+        *count /= length;
+    }
+    vector
+}
+
+fn cosine(a: &Vector, b: &Vector) -> f64 {
+    let (small, large) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    small.iter()
+        .filter_map(|(token, weight)| large.get(token).map(|other| weight * other))
+        .fold(0.0, |sum, product| sum + product)
+}
+
+struct Exemplars {
+    /// Rule filing documents into each directory holding exemplars.
+    rules: ClassifierPaths,
+    /// Vector of each exemplar with the index of its directory in `rules`.
+    vectors: Vec<(usize, Vector)>
+}
+
+/// Documents of the output tree, extracted on first use.
+pub struct Index {
+    output: PathBuf,
+    /// Similarity below which the closest directory is not proposed.
+    threshold: f64,
+    rules: ClassifierPaths,
+    /// Directories of the output tree that are not destinations.
+    excluded: Vec<PathBuf>,
+    cache: Option<Cache>,
+    exemplars: OnceCell<Exemplars>
+}
+
+impl Index {
+    /// Index of the documents below `output`, except in `excluded`
+    /// directories. Documents are filed like the rule for the same
+    /// directory in `rules`, if any.
+    pub fn new(output: PathBuf, threshold: f64, rules: &ClassifierPaths,
+        excluded: Vec<PathBuf>, cache: Option<Cache>) -> Index {
+        Index { output, threshold, rules: rules.clone(), excluded, cache,
+            exemplars: OnceCell::new() }
+    }
+
+    fn scan(&self) -> Exemplars {
+        let mut extractor = Extractor::new(self.cache.clone());
+        let mut exemplars = Exemplars { rules: Vec::new(), vectors: Vec::new() };
+        let files = walkdir::WalkDir::new(&self.output).into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file()
+                && entry.path().extension().is_some_and(|extension| extension == "pdf"));
+        for file in files {
+            let dir = match file.path().parent()
+                .and_then(|parent| parent.strip_prefix(&self.output).ok()) {
+                Some(dir) if !dir.as_os_str().is_empty()
+                    && !self.excluded.iter().any(|excluded| dir.starts_with(excluded)) => dir,
+                _ => continue
+            };
+            let extracted = match extractor.extract(file.path()) {
+                Ok(extracted) => extracted,
+                Err(error) => {
+                    log::warn!("Skipping {:?}: {:#}", file.path(), error);
+                    continue;
+                }
+            };
+            let vector = vector(&extracted.text);
+            if vector.is_empty() {
+                continue;
+            }
+            let index = match exemplars.rules.iter().position(|rule| rule.path == dir) {
+                Some(index) => index,
+                None => {
+                    exemplars.rules.push(self.rule(dir));
+                    exemplars.rules.len() - 1
+                }
+            };
+            exemplars.vectors.push((index, vector));
+        }
+        log::debug!("{} exemplars in {} directories of {:?}", exemplars.vectors.len(),
+            exemplars.rules.len(), self.output);
+        exemplars
+    }
+
+    fn rule(&self, dir: &Path) -> ClassifierPath {
+        self.rules.iter()
+            .find(|rule| rule.path == dir)
+            .cloned()
+            .unwrap_or_else(|| ClassifierPath { path: dir.to_path_buf(), ..Default::default() })
+    }
+
+    /// Rule of the directory holding the document most similar to `text`
+    /// with their similarity, unless it is not similar enough.
+    pub fn classify(&self, text: &str) -> Option<(&ClassifierPath, f64)> {
+        let exemplars = self.exemplars.get_or_init(|| self.scan());
+        let vector = vector(text);
+        let (index, similarity) = exemplars.vectors.iter()
+            .map(|(index, exemplar)| (*index, cosine(&vector, exemplar)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        let rule = &exemplars.rules[index];
+        log::debug!("most similar documents are in {:?}, similarity {:.2}", rule.path,
+            similarity);
+        (similarity >= self.threshold).then_some((rule, similarity))
+    }
+}
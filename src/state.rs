@@ -0,0 +1,86 @@
+//! Export and import of classy's own state directories.
+
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+/// State directories, each stored under its name in the archive. The
+/// same physical directory is only listed once.
+fn directories() -> anyhow::Result<Vec<(&'static str, PathBuf)>> {
+    let dirs = crate::project_dirs()
+        .context("Unable to locate the application directories")?;
+    let mut result: Vec<(&'static str, PathBuf)> = Vec::new();
+    for (name, dir) in [
+        ("config", dirs.config_dir()),
+        ("data", dirs.data_dir()),
+        ("cache", dirs.cache_dir())
+    ] {
+        if !result.iter().any(|(_, known)| known == dir) {
+            result.push((name, dir.to_path_buf()));
+        }
+    }
+    Ok(result)
+}
+
+pub fn export(archive: &Path) -> anyhow::Result<()> {
+    let file = std::fs::File::create(archive)
+        .with_context(|| format!("Failed to create {:?}", archive))?;
+    let mut builder = tar::Builder::new(file);
+    for (name, dir) in directories()? {
+        if dir.is_dir() {
+            builder.append_dir_all(name, &dir)
+                .with_context(|| format!("Failed to archive {:?}", dir))?;
+            log::info!("Exported {:?}", dir);
+        }
+    }
+    builder.finish()
+        .with_context(|| format!("Failed to write {:?}", archive))?;
+    Ok(())
+}
+
+fn is_empty(dir: &Path) -> bool {
+    std::fs::read_dir(dir).map_or(true, |mut entries| entries.next().is_none())
+}
+
+/// Unpack `archive` into the state directories. Existing state is only
+/// overwritten with `force`.
+pub fn import(archive: &Path, force: bool) -> anyhow::Result<()> {
+    let directories = directories()?;
+    if !force {
+        if let Some((_, dir)) = directories.iter().find(|(_, dir)| !is_empty(dir)) {
+            bail!("{:?} already contains state, use --force to overwrite it", dir);
+        }
+    }
+
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("Failed to open {:?}", archive))?;
+    let mut tar = tar::Archive::new(file);
+    for entry in tar.entries()? {
+        let mut entry = entry
+            .with_context(|| format!("Corrupt state archive {:?}", archive))?;
+        let path = entry.path()?.into_owned();
+        let mut components = path.components();
+        let root = components.next()
+            .map(|root| root.as_os_str().to_string_lossy().into_owned());
+        let target = directories.iter()
+            .find(|(name, _)| root.as_deref() == Some(*name))
+            .map(|(_, dir)| dir)
+            .with_context(|| format!("Unexpected entry {:?} in state archive", path))?;
+        let relative = components.as_path();
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            bail!("Unsafe entry {:?} in state archive", path);
+        }
+        let destination = target.join(relative);
+        if entry.header().entry_type().is_dir() {
+            std::fs::create_dir_all(&destination)?;
+            continue;
+        }
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&destination)
+            .with_context(|| format!("Failed to extract {:?}", path))?;
+        log::info!("Imported {:?}", destination);
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Keywords suggested for the directories of an already sorted tree.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use serde::{Serialize, Serializer};
+
+use crate::extract::Extractor;
+use crate::model;
+
+/// Terms are lowercased and without diacritics, so the layout folds them
+/// and matches its keywords ignoring case.
+#[derive(Serialize)]
+struct Layout {
+    fold_diacritics: bool,
+    layout: Vec<Directory>
+}
+
+#[derive(Serialize)]
+struct Directory {
+    dir: PathBuf,
+    #[serde(serialize_with = "insensitive")]
+    keywords: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sub: Vec<Directory>
+}
+
+#[derive(Serialize)]
+struct Keyword<'k> {
+    word: &'k str,
+    case: &'static str
+}
+
+fn insensitive<S: Serializer>(keywords: &[String], serializer: S)
+    -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(keywords.iter().map(|word| Keyword { word, case: "insensitive" }))
+}
+
+/// A document of the tree with its distinct terms.
+pub type Document = (PathBuf, HashSet<String>);
+
+/// Terms of the documents below `label` ranked by TF-IDF, the share of
+/// its documents containing them weighted by how rare they are in
+/// `corpus`. Terms in `known` or found in less than `min_share` of the
+/// documents are left out.
+pub fn distinctive(corpus: &[&Document], label: &Path, count: usize, known: &[String],
+    min_share: f64) -> Vec<String> {
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for (_, terms) in corpus.iter() {
+        for term in terms {
+            *frequency.entry(term).or_default() += 1;
+        }
+    }
+    let own: Vec<&HashSet<String>> = corpus.iter()
+        .filter(|(other, _)| other.starts_with(label))
+        .map(|(_, terms)| terms)
+        .collect();
+    let mut scores: HashMap<&str, usize> = HashMap::new();
+    for term in own.iter().flat_map(|terms| terms.iter()) {
+        *scores.entry(term).or_default() += 1;
+    }
+    let mut ranked: Vec<(&str, f64)> = scores.into_iter()
+        .filter(|(term, _)| !known.iter().any(|known| known == term))
+        .map(|(term, found)| (term, found as f64 / own.len() as f64))
+        .filter(|(_, share)| *share >= min_share)
+        .map(|(term, share)| {
+            let idf = (corpus.len() as f64 / frequency[term] as f64).ln();
+            (term, share * idf)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    ranked.sort_by(|(a, x), (b, y)| y.total_cmp(x).then(a.cmp(b)));
+    ranked.into_iter().take(count).map(|(term, _)| term.to_string()).collect()
+}
+
+fn documents(dir: &Path, extractor: &mut Extractor) -> anyhow::Result<Vec<Document>> {
+    Ok(model::documents(dir, extractor)?
+        .into_iter()
+        .map(|(label, tokens)| (label, tokens.into_iter().collect()))
+        .collect())
+}
+
+/// Print the `count` most distinctive terms of each directory below `dir`
+/// as a configuration layout.
+pub fn suggest(dir: &Path, count: usize, extractor: &mut Extractor) -> anyhow::Result<()> {
+    let documents = documents(dir, extractor)?;
+    let corpus: Vec<&Document> = documents.iter().collect();
+    let mut labels: Vec<&PathBuf> = documents.iter().map(|(label, _)| label).collect();
+    labels.sort();
+    labels.dedup();
+    if labels.len() < 2 {
+        log::warn!("Only one directory in {:?}, terms cannot be compared with others", dir);
+    }
+    let layout = Layout {
+        fold_diacritics: true,
+        layout: labels.into_iter()
+            .map(|label| Directory {
+                dir: label.clone(),
+                keywords: distinctive(&corpus, label, count, &[], 0.0),
+                sub: Vec::new()
+            })
+            .collect()
+    };
+    print!("{}", serde_yaml::to_string(&layout)?);
+    Ok(())
+}
+
+/// Directories below `parent` mirroring the tree of `documents`, with the
+/// terms telling each one apart from its siblings. As every keyword of a
+/// rule is required, only terms found in all the documents of a directory
+/// are kept. Subdirectories inherit the `known` keywords of their parents.
+fn directories(documents: &[Document], parent: &Path, count: usize, known: &[String])
+    -> Vec<Directory> {
+    let corpus: Vec<&Document> = documents.iter()
+        .filter(|(label, _)| label.starts_with(parent))
+        .collect();
+    let mut children: Vec<&std::ffi::OsStr> = corpus.iter()
+        .filter_map(|(label, _)| label.strip_prefix(parent).ok()?.iter().next())
+        .collect();
+    children.sort();
+    children.dedup();
+    children.into_iter().map(|child| {
+        let path = parent.join(child);
+        let keywords = distinctive(&corpus, &path, count, known, 1.0);
+        if keywords.is_empty() {
+            log::warn!("No distinctive keywords found for {:?}", path);
+        }
+        let inherited = [known, &keywords[..]].concat();
+        Directory {
+            dir: PathBuf::from(child),
+            sub: directories(documents, &path, count, &inherited),
+            keywords
+        }
+    }).collect()
+}
+
+/// Write a configuration file at `path` mirroring the directory tree of
+/// `root`, each directory with its `count` most distinctive terms.
+pub fn learn(root: &Path, path: &Path, count: usize, force: bool,
+    extractor: &mut Extractor) -> anyhow::Result<()> {
+    if path.exists() && !force {
+        bail!("{:?} already exists, use --force to replace it", path);
+    }
+    let documents = documents(root, extractor)?;
+    let layout = Layout {
+        fold_diacritics: true,
+        layout: directories(&documents, Path::new(""), count, &[])
+    };
+    crate::init::write(path, &layout)?;
+    println!("Wrote {:?} from {} documents. Review the keywords, then check it with \
+        `classy config validate`.", path, documents.len());
+    if layout.layout.iter().any(|dir| !dir.sub.is_empty()) {
+        println!("Subdirectories only take precedence over their parent with \
+            `--match-policy best`.");
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! Classification recorded in extended attributes, for `--action tag`.
+//!
+//! The matched directories are written to the `user.classy.category`
+//! attribute, one per line. On macOS, they also become the Finder tags of
+//! files that have none yet.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+const ATTRIBUTE: &str = "user.classy.category";
+
+#[cfg(target_os = "macos")]
+const FINDER_TAGS: &str = "com.apple.metadata:_kMDItemUserTags";
+
+/// Finder tags `categories` as an XML property list.
+#[cfg(target_os = "macos")]
+fn finder_tags(categories: &str) -> Vec<u8> {
+    let mut plist = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+        "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\"><array>"));
+    for category in categories.lines() {
+        let escaped = category.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        plist.push_str(&format!("<string>{}</string>", escaped));
+    }
+    plist.push_str("</array></plist>\n");
+    plist.into_bytes()
+}
+
+/// Record `categories` in the attributes of `path`.
+#[cfg(unix)]
+pub fn write(path: &Path, categories: &[&Path]) -> anyhow::Result<()> {
+    let value = categories.iter()
+        .map(|category| category.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n");
+    xattr::set(path, ATTRIBUTE, value.as_bytes())
+        .with_context(|| format!("Failed to tag {:?}", path))?;
+    #[cfg(target_os = "macos")]
+    if xattr::get(path, FINDER_TAGS)?.is_none() {
+        xattr::set(path, FINDER_TAGS, &finder_tags(&value))
+            .with_context(|| format!("Failed to set the Finder tags of {:?}", path))?;
+    }
+    Ok(())
+}
+
+/// Remove the attributes written by `write`. Finder tags are only removed
+/// if they were not changed since.
+#[cfg(unix)]
+pub fn remove(path: &Path) -> anyhow::Result<()> {
+    let value = xattr::get(path, ATTRIBUTE)
+        .with_context(|| format!("Failed to read the tags of {:?}", path))?;
+    let categories = match value {
+        Some(value) => String::from_utf8_lossy(&value).into_owned(),
+        None => return Ok(())
+    };
+    log::debug!("removing tags {:?} of {:?}", categories, path);
+    #[cfg(target_os = "macos")]
+    if xattr::get(path, FINDER_TAGS)? == Some(finder_tags(&categories)) {
+        xattr::remove(path, FINDER_TAGS)?;
+    }
+    xattr::remove(path, ATTRIBUTE)
+        .with_context(|| format!("Failed to remove the tags of {:?}", path))
+}
+
+#[cfg(not(unix))]
+pub fn write(_path: &Path, _categories: &[&Path]) -> anyhow::Result<()> {
+    anyhow::bail!("Extended attributes are not supported on this platform")
+}
+
+#[cfg(not(unix))]
+pub fn remove(_path: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("Extended attributes are not supported on this platform")
+}
@@ -0,0 +1,224 @@
+//! Checks of a configuration file that report every problem at once.
+//!
+//! The file is read with the same model as when loading it, whatever its
+//! format, then each setting and directory is checked on its own.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+
+use crate::settings::{self, Directory, Profile, Settings};
+use crate::KeywordSets;
+
+struct Problem {
+    /// Line number, starting at 1.
+    line: Option<usize>,
+    message: String
+}
+
+struct Validator<'a> {
+    lines: Vec<&'a str>,
+    /// Line after the last located directory, as directories are visited
+    /// in document order.
+    cursor: usize,
+    problems: Vec<Problem>,
+    /// Destination paths with the line they are declared on.
+    paths: Vec<(PathBuf, Option<usize>)>
+}
+
+impl<'a> Validator<'a> {
+    fn new(source: &'a str) -> Validator<'a> {
+        Validator { lines: source.lines().collect(), cursor: 0, problems: Vec::new(),
+            paths: Vec::new() }
+    }
+
+    fn problem(&mut self, line: Option<usize>, message: String) {
+        self.problems.push(Problem { line, message });
+    }
+
+    /// Line declaring the directory `name`, as `dir: name` in YAML,
+    /// `dir = "name"` in TOML or `"dir": "name"` in JSON.
+    fn locate(&mut self, name: &str) -> Option<usize> {
+        let pattern = format!(
+            r#"^\s*(-\s*)?\{{?\s*["']?dir["']?\s*[:=]\s*["']?{}["']?\s*([,}}]|$)"#,
+            regex::escape(name));
+        let regex = regex::Regex::new(&pattern).ok()?;
+        let index = self.lines.iter().skip(self.cursor)
+            .position(|line| regex.is_match(line))? + self.cursor;
+        self.cursor = index + 1;
+        Some(index + 1)
+    }
+
+    /// Record the paths of `layout` below `parent` with their lines.
+    fn locate_layout(&mut self, layout: &[Directory], parent: &Path) {
+        for dir in layout {
+            let name = dir.name();
+            let line = self.locate(&name);
+            let path = parent.join(name);
+            self.paths.push((path.clone(), line));
+            self.locate_layout(&dir.sub, &path);
+        }
+    }
+
+    fn check_root(&mut self, settings: &Settings) {
+        if settings.layout.is_empty() && settings.profiles.is_empty() {
+            self.problem(None, "Expected a 'layout' list of directories".to_string());
+        }
+        let sets = self.check_settings(settings, KeywordSets::new());
+        self.check_paths();
+        // Destinations of different profiles do not collide.
+        for Profile(profile) in settings.profiles.values() {
+            self.check_settings(profile, sets.clone());
+            self.check_paths();
+        }
+    }
+
+    /// Check `settings`, whose directories may also use the keyword sets
+    /// `sets`, and return the sets they may use.
+    fn check_settings(&mut self, settings: &Settings, mut sets: KeywordSets) -> KeywordSets {
+        for (key, globs) in [("include_files", &settings.include_files),
+            ("exclude_files", &settings.exclude_files)] {
+            for glob in globs {
+                if let Err(error) = glob::Pattern::new(glob) {
+                    self.problem(None, format!("Invalid glob '{}' in '{}': {}", glob, key, error));
+                }
+            }
+        }
+        if settings.webhook.as_ref().is_some_and(|url| !url.starts_with("http://")
+            && !url.starts_with("https://")) {
+            self.problem(None, "'webhook' should be an http or https URL".to_string());
+        }
+        if let Some(Err(error)) = settings.schedule.as_deref()
+            .map(str::parse::<crate::schedule::Schedule>) {
+            self.problem(None, format!("{:#}", error));
+        }
+        if let Some(Err(error)) = settings.holding_retention.as_deref()
+            .map(crate::filter::parse_duration) {
+            self.problem(None, format!("Invalid 'holding_retention': {}", error));
+        }
+        let mut commands = BTreeMap::new();
+        for (name, command) in settings.extractors.iter() {
+            if crate::extract::names().contains(&name.as_str()) {
+                self.problem(None, format!("Extractor '{}' is built in, give the command \
+                    another name", name));
+                continue;
+            }
+            match crate::hook::parse(settings::exec_args(command.clone())) {
+                Ok(hook) => {
+                    commands.insert(name.clone(), hook.args().to_vec());
+                }
+                Err(error) => self.problem(None,
+                    format!("In 'extractors' of '{}': {:#}", name, error))
+            }
+        }
+        for (extension, chain) in settings.extraction.iter() {
+            if chain.is_empty() {
+                self.problem(None, format!(
+                    "'{}' in 'extraction' should be a list of extractors", extension));
+                continue;
+            }
+            let configured = BTreeMap::from([(extension.clone(), chain.clone())]);
+            if let Err(error) = crate::extract::chains(&configured, &commands) {
+                self.problem(None, format!("{:#}", error));
+            }
+        }
+        match settings::keyword_sets(&settings.keyword_sets) {
+            Ok(own) => sets.extend(own),
+            Err(error) => self.problem(None, format!("{:#}", error))
+        }
+
+        let located = self.paths.len();
+        self.locate_layout(&settings.layout, Path::new(""));
+        let mut invalid = Vec::new();
+        settings::rules(&settings.layout, settings.fold_diacritics.unwrap_or(false), &sets,
+            &mut |path, error| invalid.push((path.to_path_buf(), error)));
+        for (path, error) in invalid {
+            let line = self.paths[located..].iter()
+                .find(|(declared, _)| *declared == path)
+                .and_then(|(_, line)| *line);
+            self.problem(line, format!("{:#}", error));
+        }
+        sets
+    }
+
+    /// Report destinations declared twice, or differing only by case,
+    /// which collide on case-insensitive file systems.
+    fn check_paths(&mut self) {
+        let mut seen: HashMap<String, (PathBuf, Option<usize>)> = HashMap::new();
+        let paths = std::mem::take(&mut self.paths);
+        for (path, line) in paths {
+            let key = path.to_string_lossy().to_lowercase();
+            match seen.get(&key) {
+                Some((first, first_line)) => {
+                    let at = first_line.map(|l| format!(" on line {}", l)).unwrap_or_default();
+                    let message = if *first == path {
+                        format!("Directory {:?} is declared twice{}", path,
+                            first_line.map(|l| format!(", first on line {}", l))
+                                .unwrap_or_default())
+                    } else {
+                        format!("Directory {:?} collides with {:?}{}", path, first, at)
+                    };
+                    self.problem(line, message);
+                }
+                None => {
+                    seen.insert(key, (path, line));
+                }
+            }
+        }
+    }
+}
+
+/// Check the configuration file at `path` and print every problem found.
+pub fn validate(path: &Path) -> anyhow::Result<()> {
+    let source = settings::to_str(path)?;
+    let mut validator = Validator::new(&source);
+    // Syntax errors of the file itself are reported on their line.
+    match settings::parse(&source, settings::Format::of(path)) {
+        Ok(_) => match settings::settings(path) {
+            Ok(settings) => validator.check_root(&settings),
+            Err(error) => validator.problem(None, format!("{:#}", error))
+        },
+        Err(error) => validator.problem(error.line, format!("Invalid configuration: {}", error))
+    }
+    if validator.problems.is_empty() {
+        // Check again each profile with the directories it adds.
+        let profiles = match settings::profiles(path) {
+            Ok(profiles) if profiles.is_empty() => vec![None],
+            Ok(profiles) => profiles.into_iter().map(Some).collect(),
+            Err(error) => {
+                validator.problem(None, format!("{:#}", error));
+                Vec::new()
+            }
+        };
+        for profile in profiles {
+            match settings::load(path, profile.as_deref()) {
+                Ok(config) => {
+                    validator.paths = config.paths.into_iter()
+                        .map(|rule| (rule.path, None))
+                        .collect();
+                    validator.check_paths();
+                }
+                Err(error) => validator.problem(None, format!("{:#}", error))
+            }
+        }
+    }
+
+    let problems = &validator.problems;
+    for problem in problems {
+        match problem.line {
+            Some(line) => {
+                println!("{}:{}: {}", path.display(), line, problem.message);
+                if let Some(text) = validator.lines.get(line - 1) {
+                    println!("    {}", text.trim_end());
+                }
+            }
+            None => println!("{}: {}", path.display(), problem.message)
+        }
+    }
+    if !problems.is_empty() {
+        bail!("{} problems found in {:?}", problems.len(), path);
+    }
+    println!("{}: OK", path.display());
+    Ok(())
+}
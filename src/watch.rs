@@ -0,0 +1,208 @@
+//! Watch mode: classify files as they appear in the input directories.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDateTime};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::{extract, Config, Run};
+
+/// Files modified more recently than this may still be being written.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Flag raised by SIGTERM or SIGINT. A second signal exits immediately.
+pub fn stop_flag() -> anyhow::Result<Arc<AtomicBool>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        signal_hook::flag::register_conditional_shutdown(signal, 1, stop.clone())?;
+        signal_hook::flag::register(signal, stop.clone())?;
+    }
+    Ok(stop)
+}
+
+/// Sleep for `duration`, or until `stop` is raised.
+fn sleep(duration: Duration, stop: &AtomicBool) {
+    let step = Duration::from_millis(100);
+    let mut slept = Duration::ZERO;
+    while slept < duration && !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(step.min(duration - slept));
+        slept += step;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().ok()?.modified().ok()
+}
+
+/// Modification times of `paths`, `None` for the missing ones.
+fn stamps(paths: Vec<PathBuf>) -> HashMap<PathBuf, Option<SystemTime>> {
+    paths.into_iter().map(|path| {
+        let modified = modified(&path);
+        (path, modified)
+    }).collect()
+}
+
+/// Next sweep of `config`, if it has a schedule.
+fn next_sweep(config: &Config) -> Option<NaiveDateTime> {
+    let next = config.schedule.as_ref()?.next_after(Local::now().naive_local());
+    if let Some(next) = next {
+        log::info!("Next sweep at {}", next.format("%Y-%m-%d %H:%M"));
+    }
+    next
+}
+
+/// Long-lived workers extracting the files found, and how far they may
+/// run ahead of the filing.
+pub struct Workers {
+    pub jobs: usize,
+    pub limits: extract::Limits
+}
+
+/// Poll `inputs` every `interval` if `poll` is set, extracting new files
+/// with `workers`. Errors are reported without stopping.
+///
+/// At the times of the `schedule` of the configuration, every file of
+/// `inputs` is tried again, including the ones that were not filed. Held
+/// files are tried again when watching starts, at each sweep and once the
+/// configuration is reloaded.
+///
+/// When the configuration file at `config_path`, a file it includes or the
+/// override file of an input directory changes, the configuration is read
+/// again with `load` before the next scan, and the output tree prepared
+/// for it as at startup. A configuration that fails to load is reported
+/// and the previous one is kept. The `index` and `audit_log` settings only
+/// change on restart.
+///
+/// SIGTERM and SIGINT stop watching once the file being filed is done.
+pub fn watch(run: &mut Run, inputs: &[PathBuf], interval: Duration, workers: Workers,
+    poll: bool, config_path: &Path, load: impl Fn() -> anyhow::Result<Config>)
+    -> anyhow::Result<()> {
+    let stop = stop_flag()?;
+    let Workers { jobs, limits } = workers;
+    let new_pool = |run: &Run| extract::Pool::new(jobs, &run.config.extraction,
+        run.cache.clone(), run.metrics.clone(), &run.limits);
+    let mut pool = new_pool(run);
+    let mut seen: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let mut sources = stamps(run.config_sources());
+    if poll {
+        log::info!("Watching {:?} with {} workers", inputs, jobs);
+    }
+    let mut sweep_at = next_sweep(&run.config);
+    let mut retry_held = true;
+    while !stop.load(Ordering::Relaxed) {
+        let changed = sources.iter().any(|(path, stamp)| modified(path) != *stamp);
+        if changed {
+            match load().and_then(|config| run.reload(config)) {
+                Ok(()) => {
+                    // Workers extract with the chains they were built with.
+                    pool = new_pool(run);
+                    log::info!("Reloaded {:?}", config_path);
+                    sweep_at = next_sweep(&run.config);
+                    retry_held = true;
+                }
+                Err(error) => log::error!("Keeping the previous configuration: {:#}", error)
+            }
+            sources = stamps(run.config_sources());
+        }
+        let sweep = sweep_at.is_some_and(|at| Local::now().naive_local() >= at);
+        if sweep {
+            log::info!("Sweeping {:?}", inputs);
+            seen.clear();
+            sweep_at = next_sweep(&run.config);
+            retry_held = true;
+        }
+        let held = if std::mem::take(&mut retry_held) { run.held() } else { Vec::new() };
+        if !held.is_empty() {
+            // Other runs may be filing, so each batch waits its turn.
+            let _lock = match run.lock(true) {
+                Ok(lock) => lock,
+                Err(error) => {
+                    log::error!("{:#}", error);
+                    retry_held = true;
+                    sleep(interval, &stop);
+                    continue;
+                }
+            };
+            for (path, extracted) in held {
+                if let Err(error) = run.process(&path, Ok(extracted)) {
+                    log::error!("{:?}: {:#}", path, error);
+                }
+            }
+            if let Err(error) = run.save() {
+                log::error!("{:#}", error);
+            }
+            if let Some(holding) = &run.holding {
+                holding.report_overdue();
+            }
+        }
+        if !sweep && !poll {
+            sleep(interval, &stop);
+            continue;
+        }
+        let now = SystemTime::now();
+        let mut ready = Vec::new();
+        let mut present = HashMap::new();
+        let files = inputs.iter()
+            .flat_map(|input| crate::walk_files(input, &run.config.filter));
+        for file in files {
+            let modified = match modified(file.path()) {
+                Some(modified) => modified,
+                None => continue
+            };
+            let path = file.path().to_path_buf();
+            present.insert(path.clone(), modified);
+            let settled = now.duration_since(modified)
+                .is_ok_and(|age| age >= SETTLE_TIME);
+            // Sweeps try again the files that were not filed.
+            let done = || if sweep { run.is_filed(&path) } else { run.is_done(&path) };
+            if settled && seen.get(&path) != Some(&modified) && !done() {
+                ready.push(path);
+            }
+        }
+        seen.retain(|path, _| present.contains_key(path));
+
+        let lock = if ready.is_empty() {
+            None
+        } else {
+            match run.lock(true) {
+                Ok(lock) => lock,
+                Err(error) => {
+                    log::error!("{:#}", error);
+                    sleep(interval, &stop);
+                    continue;
+                }
+            }
+        };
+        for (path, extracted) in pool.stream(ready.into_iter(), limits) {
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Some(modified) = present.get(&path) {
+                seen.insert(path.clone(), *modified);
+            }
+            if let Err(error) = run.process(&path, extracted) {
+                log::error!("{:?}: {:#}", path, error);
+            }
+        }
+        if let Err(error) = run.save() {
+            log::error!("{:#}", error);
+        }
+        run.post_summary();
+        drop(lock);
+        // Input directories seen for the first time may have override files.
+        for path in run.config_sources() {
+            if let Entry::Vacant(entry) = sources.entry(path) {
+                let stamp = modified(entry.key());
+                entry.insert(stamp);
+            }
+        }
+        sleep(interval, &stop);
+    }
+    log::info!("Stopped watching {:?}", inputs);
+    Ok(())
+}
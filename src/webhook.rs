@@ -0,0 +1,39 @@
+//! Notifications of filed documents posted to a webhook.
+
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::output::Summary;
+
+#[derive(Clone)]
+pub struct Webhook {
+    pub url: String,
+    /// Post one summary per run instead of one payload per file.
+    pub batch: bool
+}
+
+impl Webhook {
+    /// Post `payload` as JSON. Failures are only reported, as the documents
+    /// were filed anyway.
+    pub fn post(&self, payload: &Value) {
+        let response = ureq::post(&self.url)
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", "application/json")
+            .send_string(&payload.to_string());
+        if let Err(error) = response {
+            log::warn!("Failed to notify the webhook: {}", error);
+        }
+    }
+
+    /// Post the `summary` of a run with the `files` it filed.
+    pub fn post_summary(&self, summary: &Summary, files: Vec<Value>) {
+        self.post(&serde_json::json!({
+            "classified": summary.classified,
+            "unmatched": summary.unmatched,
+            "ambiguous": summary.ambiguous,
+            "errors": summary.errors,
+            "files": files
+        }));
+    }
+}